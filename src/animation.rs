@@ -0,0 +1,636 @@
+use std::f32::consts::PI;
+use std::ops::{Add, Mul};
+use glam::{Quat, Vec2, Vec3, Vec4};
+use crate::camera::Camera;
+use crate::light::LightUniform;
+use crate::model::ModelInstance;
+
+/// Shapes the progress between two keys, from 0 at the first to 1 at the second.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Holds the first value until the second key.
+    Step,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    ExpoIn,
+    ExpoOut,
+    ExpoInOut,
+    /// Pulls back a little before leaving.
+    BackIn,
+    /// Overshoots a little before settling.
+    BackOut,
+    BackInOut,
+    ElasticOut,
+    BounceOut,
+}
+
+impl Easing {
+    pub fn apply(self, t: f32) -> f32 {
+        const BACK: f32 = 1.70158;
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::Step => if t < 1.0 { 0.0 } else { 1.0 },
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t).powi(2),
+            Easing::QuadInOut => if t < 0.5 { 2.0 * t * t } else { 1.0 - (2.0 - 2.0 * t).powi(2) / 2.0 },
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => if t < 0.5 { 4.0 * t * t * t } else { 1.0 - (2.0 - 2.0 * t).powi(3) / 2.0 },
+            Easing::SineIn => 1.0 - (t * PI / 2.0).cos(),
+            Easing::SineOut => (t * PI / 2.0).sin(),
+            Easing::SineInOut => (1.0 - (t * PI).cos()) / 2.0,
+            Easing::ExpoIn => if t == 0.0 { 0.0 } else { 2.0_f32.powf(10.0 * t - 10.0) },
+            Easing::ExpoOut => if t == 1.0 { 1.0 } else { 1.0 - 2.0_f32.powf(-10.0 * t) },
+            Easing::ExpoInOut => match t {
+                0.0 => 0.0,
+                1.0 => 1.0,
+                t if t < 0.5 => 2.0_f32.powf(20.0 * t - 10.0) / 2.0,
+                t => 1.0 - 2.0_f32.powf(10.0 - 20.0 * t) / 2.0,
+            },
+            Easing::BackIn => (BACK + 1.0) * t * t * t - BACK * t * t,
+            Easing::BackOut => 1.0 - Easing::BackIn.apply(1.0 - t),
+            Easing::BackInOut => if t < 0.5 {
+                Easing::BackIn.apply(2.0 * t) / 2.0
+            } else {
+                0.5 + Easing::BackOut.apply(2.0 * t - 1.0) / 2.0
+            },
+            Easing::ElasticOut => match t {
+                0.0 => 0.0,
+                1.0 => 1.0,
+                t => 2.0_f32.powf(-10.0 * t) * ((10.0 * t - 0.75) * 2.0 * PI / 3.0).sin() + 1.0,
+            },
+            Easing::BounceOut => {
+                const N: f32 = 7.5625;
+                const D: f32 = 2.75;
+                if t < 1.0 / D {
+                    N * t * t
+                } else if t < 2.0 / D {
+                    let t = t - 1.5 / D;
+                    N * t * t + 0.75
+                } else if t < 2.5 / D {
+                    let t = t - 2.25 / D;
+                    N * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / D;
+                    N * t * t + 0.984375
+                }
+            }
+        }
+    }
+}
+
+/// A value that can be animated between keys.
+pub trait Keyframe: Copy {
+    fn interpolate(self, other: Self, t: f32) -> Self;
+    /// The Hermite curve from `self` to `other` with the tangents scaled by `duration`.
+    fn hermite(self, out_tangent: Self, other: Self, in_tangent: Self, t: f32, duration: f32) -> Self;
+}
+
+/// Values that interpolate component by component.
+pub trait Linear: Copy + Add<Output = Self> + Mul<f32, Output = Self> {}
+
+impl Linear for f32 {}
+impl Linear for Vec2 {}
+impl Linear for Vec3 {}
+impl Linear for Vec4 {}
+
+/// The weights of the two values and two tangents of a cubic Hermite curve.
+fn hermite_weights(t: f32) -> [f32; 4] {
+    let (t2, t3) = (t * t, t * t * t);
+    [2.0 * t3 - 3.0 * t2 + 1.0, t3 - 2.0 * t2 + t, -2.0 * t3 + 3.0 * t2, t3 - t2]
+}
+
+impl<T: Linear> Keyframe for T {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        self * (1.0 - t) + other * t
+    }
+
+    fn hermite(self, out_tangent: Self, other: Self, in_tangent: Self, t: f32, duration: f32) -> Self {
+        let [a, b, c, d] = hermite_weights(t);
+        self * a + out_tangent * (b * duration) + other * c + in_tangent * (d * duration)
+    }
+}
+
+impl Keyframe for Quat {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        self.slerp(other, t)
+    }
+
+    /// Runs the curve through the four components and normalizes the result.
+    fn hermite(self, out_tangent: Self, other: Self, in_tangent: Self, t: f32, duration: f32) -> Self {
+        let [p0, m0, p1, m1] = [self, out_tangent, other, in_tangent].map(Vec4::from);
+        Quat::from_vec4(p0.hermite(m0, p1, m1, t, duration)).normalize()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Key<T> {
+    pub time: f32,
+    pub value: T,
+    /// How the value moves on towards the next key.
+    pub easing: Easing,
+}
+
+/// Values of one property at points in time. Before the first key the track holds the first
+/// value and after the last key the last value; repeating is up to the [`Animation`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track<T> {
+    keys: Vec<Key<T>>,
+}
+
+impl<T: Keyframe> Default for Track<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Keyframe> Track<T> {
+    pub fn new() -> Self {
+        Self { keys: vec![] }
+    }
+
+    /// A single move from one value to another.
+    pub fn tween(from: T, to: T, duration: f32, easing: Easing) -> Self {
+        Self::new().key(0.0, from, easing).key(duration, to, Easing::Linear)
+    }
+
+    /// Adds a key, keeping the keys in order of time.
+    pub fn key(mut self, time: f32, value: T, easing: Easing) -> Self {
+        let index = self.keys.partition_point(|key| key.time <= time);
+        self.keys.insert(index, Key { time, value, easing });
+        self
+    }
+
+    pub fn keys(&self) -> &[Key<T>] {
+        &self.keys
+    }
+
+    /// The time of the last key.
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |key| key.time)
+    }
+
+    /// `None` for a track without keys.
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let next = self.keys.partition_point(|key| key.time <= time);
+        if next == 0 {
+            return self.keys.first().map(|key| key.value);
+        }
+        let key = &self.keys[next - 1];
+        let Some(next) = self.keys.get(next) else {
+            return Some(key.value);
+        };
+        let t = (time - key.time) / (next.time - key.time);
+        Some(key.value.interpolate(next.value, key.easing.apply(t)))
+    }
+
+    /// Animates any property of a `T` through the setter.
+    pub fn bind<U>(self, set: impl Fn(&mut U, T) + 'static) -> Binding<U, T> {
+        Binding { track: self, set: Box::new(set) }
+    }
+}
+
+/// Something that sets the state of a `T` for a point in time.
+pub trait Animate<T> {
+    fn duration(&self) -> f32;
+    fn apply(&self, time: f32, target: &mut T);
+}
+
+type Setter<U, T> = Box<dyn Fn(&mut U, T)>;
+
+/// A track feeding a property through a setter, made with [`Track::bind`].
+pub struct Binding<U, T> {
+    track: Track<T>,
+    set: Setter<U, T>,
+}
+
+impl<U, T: Keyframe> Animate<U> for Binding<U, T> {
+    fn duration(&self) -> f32 {
+        self.track.duration()
+    }
+
+    fn apply(&self, time: f32, target: &mut U) {
+        if let Some(value) = self.track.sample(time) {
+            (self.set)(target, value);
+        }
+    }
+}
+
+/// Tracks for the transform of a [`ModelInstance`]. Missing tracks leave their part alone.
+#[derive(Clone, Debug, Default)]
+pub struct InstanceTracks {
+    pub position: Option<Track<Vec3>>,
+    pub rotation: Option<Track<Quat>>,
+    pub scale: Option<Track<Vec3>>,
+}
+
+impl InstanceTracks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn position(mut self, track: Track<Vec3>) -> Self {
+        self.position = Some(track);
+        self
+    }
+
+    pub fn rotation(mut self, track: Track<Quat>) -> Self {
+        self.rotation = Some(track);
+        self
+    }
+
+    pub fn scale(mut self, track: Track<Vec3>) -> Self {
+        self.scale = Some(track);
+        self
+    }
+}
+
+impl Animate<ModelInstance> for InstanceTracks {
+    fn duration(&self) -> f32 {
+        longest([track_duration(&self.position), track_duration(&self.rotation), track_duration(&self.scale)])
+    }
+
+    fn apply(&self, time: f32, target: &mut ModelInstance) {
+        sample_into(&self.position, time, &mut target.position);
+        sample_into(&self.rotation, time, &mut target.rotation);
+        sample_into(&self.scale, time, &mut target.scale);
+    }
+}
+
+/// Tracks for the point light. Missing tracks leave their part alone.
+#[derive(Clone, Debug, Default)]
+pub struct LightTracks {
+    pub position: Option<Track<Vec3>>,
+    pub color: Option<Track<Vec3>>,
+    pub ambient: Option<Track<Vec3>>,
+}
+
+impl LightTracks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn position(mut self, track: Track<Vec3>) -> Self {
+        self.position = Some(track);
+        self
+    }
+
+    pub fn color(mut self, track: Track<Vec3>) -> Self {
+        self.color = Some(track);
+        self
+    }
+
+    pub fn ambient(mut self, track: Track<Vec3>) -> Self {
+        self.ambient = Some(track);
+        self
+    }
+}
+
+impl Animate<LightUniform> for LightTracks {
+    fn duration(&self) -> f32 {
+        longest([track_duration(&self.position), track_duration(&self.color), track_duration(&self.ambient)])
+    }
+
+    fn apply(&self, time: f32, target: &mut LightUniform) {
+        sample_into(&self.position, time, &mut target.position);
+        sample_into(&self.color, time, &mut target.color);
+        sample_into(&self.ambient, time, &mut target.ambient);
+    }
+}
+
+/// Tracks for the camera, with the field of view in degrees. Missing tracks leave their part
+/// alone. The uniform is rebuilt after every change, ready to be written to its buffer.
+#[derive(Clone, Debug, Default)]
+pub struct CameraTracks {
+    pub eye: Option<Track<Vec3>>,
+    pub target: Option<Track<Vec3>>,
+    pub fovy: Option<Track<f32>>,
+}
+
+impl CameraTracks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn eye(mut self, track: Track<Vec3>) -> Self {
+        self.eye = Some(track);
+        self
+    }
+
+    pub fn target(mut self, track: Track<Vec3>) -> Self {
+        self.target = Some(track);
+        self
+    }
+
+    pub fn fovy(mut self, track: Track<f32>) -> Self {
+        self.fovy = Some(track);
+        self
+    }
+}
+
+impl Animate<Camera> for CameraTracks {
+    fn duration(&self) -> f32 {
+        longest([track_duration(&self.eye), track_duration(&self.target), track_duration(&self.fovy)])
+    }
+
+    fn apply(&self, time: f32, target: &mut Camera) {
+        if let Some(eye) = self.eye.as_ref().and_then(|track| track.sample(time)) {
+            target.set_eye(eye);
+        }
+        if let Some(look_at) = self.target.as_ref().and_then(|track| track.sample(time)) {
+            target.set_target(look_at);
+        }
+        if let Some(fovy) = self.fovy.as_ref().and_then(|track| track.sample(time)) {
+            target.set_fovy(fovy);
+        }
+        target.build_view_projection_matrix();
+    }
+}
+
+fn track_duration<T: Keyframe>(track: &Option<Track<T>>) -> f32 {
+    track.as_ref().map_or(0.0, Track::duration)
+}
+
+fn sample_into<T: Keyframe>(track: &Option<Track<T>>, time: f32, value: &mut T) {
+    if let Some(sampled) = track.as_ref().and_then(|track| track.sample(time)) {
+        *value = sampled;
+    }
+}
+
+fn longest<const N: usize>(durations: [f32; N]) -> f32 {
+    durations.into_iter().fold(0.0, f32::max)
+}
+
+type Step<T> = Box<dyn Animate<T>>;
+
+/// Animations played one after another, with optional pauses in between.
+pub struct Sequence<T> {
+    /// The length of each step, with `None` for pauses.
+    steps: Vec<(f32, Option<Step<T>>)>,
+}
+
+impl<T> Default for Sequence<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Sequence<T> {
+    pub fn new() -> Self {
+        Self { steps: vec![] }
+    }
+
+    pub fn then(mut self, animation: impl Animate<T> + 'static) -> Self {
+        self.steps.push((animation.duration(), Some(Box::new(animation))));
+        self
+    }
+
+    /// Leaves the target alone for a while.
+    pub fn wait(mut self, seconds: f32) -> Self {
+        self.steps.push((seconds, None));
+        self
+    }
+}
+
+impl<T> Animate<T> for Sequence<T> {
+    fn duration(&self) -> f32 {
+        self.steps.iter().map(|(duration, _)| duration).sum()
+    }
+
+    /// Steps that are over are applied at their end, so that none are skipped by a long frame,
+    /// and steps that have not started yet are left out.
+    fn apply(&self, time: f32, target: &mut T) {
+        let mut start = 0.0;
+        for (duration, step) in &self.steps {
+            if time < start && start > 0.0 {
+                break;
+            }
+            if let Some(step) = step {
+                step.apply((time - start).min(*duration), target);
+            }
+            start += duration;
+        }
+    }
+}
+
+/// Animations played at the same time, such as tracks of different properties.
+pub struct Group<T> {
+    members: Vec<Box<dyn Animate<T>>>,
+}
+
+impl<T> Default for Group<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Group<T> {
+    pub fn new() -> Self {
+        Self { members: vec![] }
+    }
+
+    pub fn with(mut self, animation: impl Animate<T> + 'static) -> Self {
+        self.members.push(Box::new(animation));
+        self
+    }
+}
+
+impl<T> Animate<T> for Group<T> {
+    fn duration(&self) -> f32 {
+        self.members.iter().map(|member| member.duration()).fold(0.0, f32::max)
+    }
+
+    fn apply(&self, time: f32, target: &mut T) {
+        for member in &self.members {
+            member.apply(time, target);
+        }
+    }
+}
+
+type Callback = Box<dyn FnMut(&str)>;
+
+/// What an animation does once it reaches its end.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Repeat {
+    /// Stops at the end.
+    #[default]
+    Once,
+    /// Starts over from the beginning.
+    Loop,
+    /// Plays backwards to the beginning, then forwards again.
+    PingPong,
+}
+
+/// Plays an [`Animate`] over time with repeating, a speed and named events that call back when
+/// the playhead passes them.
+pub struct Animation<T> {
+    animation: Box<dyn Animate<T>>,
+    pub repeat: Repeat,
+    pub speed: f32,
+    time: f32,
+    events: Vec<(f32, String)>,
+    callbacks: Vec<Callback>,
+}
+
+impl<T> Animation<T> {
+    pub fn new(animation: impl Animate<T> + 'static) -> Self {
+        Self {
+            animation: Box::new(animation),
+            repeat: Repeat::Once,
+            speed: 1.0,
+            time: 0.0,
+            events: vec![],
+            callbacks: vec![],
+        }
+    }
+
+    pub fn repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Names a point in time of the animation, fired on every pass over it.
+    pub fn event(mut self, time: f32, name: impl Into<String>) -> Self {
+        self.events.push((time, name.into()));
+        self
+    }
+
+    pub fn on_event(mut self, callback: impl FnMut(&str) + 'static) -> Self {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.animation.duration()
+    }
+
+    /// The time within the animation, running backwards on the return legs of ping-pong.
+    pub fn time(&self) -> f32 {
+        let duration = self.duration();
+        if duration <= 0.0 {
+            return 0.0;
+        }
+        match self.repeat {
+            Repeat::Once => self.time.min(duration),
+            Repeat::Loop => self.time.rem_euclid(duration),
+            Repeat::PingPong => {
+                let time = self.time.rem_euclid(2.0 * duration);
+                if time > duration { 2.0 * duration - time } else { time }
+            }
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.repeat == Repeat::Once && self.time >= self.duration()
+    }
+
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+    }
+
+    /// Moves the playhead on, calling back for every event passed. Negative speeds are not
+    /// supported for events and stop at the beginning.
+    pub fn advance(&mut self, seconds: f32) {
+        let duration = self.duration();
+        let from = self.time;
+        let mut to = (self.time + seconds * self.speed).max(0.0);
+        if self.repeat == Repeat::Once {
+            to = to.min(duration.max(from));
+        }
+        self.time = to;
+        if duration <= 0.0 || to <= from || self.events.is_empty() {
+            return;
+        }
+
+        // Walks the passes over the animation that the step covers, forwards or backwards.
+        let first = (from / duration).floor() as u64;
+        let last = (to / duration).ceil() as u64;
+        for pass in first..last {
+            let start = pass as f32 * duration;
+            let (a, b) = (from.max(start) - start, to.min(start + duration) - start);
+            let backwards = self.repeat == Repeat::PingPong && pass % 2 == 1;
+            for (time, name) in &self.events {
+                let passed = if backwards {
+                    duration - b <= *time && *time < duration - a
+                } else {
+                    // A ping-pong turning at the beginning already fired it on the way back.
+                    let start = a == 0.0 && *time == 0.0 && !(self.repeat == Repeat::PingPong && pass > 0);
+                    (a < *time || start) && *time <= b
+                };
+                if passed {
+                    for callback in &mut self.callbacks {
+                        callback(name);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn apply(&self, target: &mut T) {
+        self.animation.apply(self.time(), target);
+    }
+
+    /// Advances and applies in one go, usually once a frame.
+    pub fn update(&mut self, seconds: f32, target: &mut T) {
+        self.advance(seconds);
+        self.apply(target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_samples(track: &Track<f32>, samples: &[(f32, f32)]) {
+        for &(time, expected) in samples {
+            let value = track.sample(time).unwrap();
+            assert!((value - expected).abs() < 1e-6, "{value} instead of {expected} at {time}");
+        }
+    }
+
+    #[test]
+    fn linear_keys_interpolate() {
+        let track = Track::new().key(3.0, 10.0, Easing::Linear).key(1.0, 0.0, Easing::Linear).key(4.0, 4.0, Easing::Linear);
+        assert_samples(&track, &[(1.0, 0.0), (3.0, 10.0), (4.0, 4.0), (2.0, 5.0), (1.5, 2.5), (3.5, 7.0)]);
+
+        let track = Track::tween(Vec3::ZERO, Vec3::new(2.0, 4.0, -6.0), 2.0, Easing::Linear);
+        assert_eq!(track.sample(0.5), Some(Vec3::new(0.5, 1.0, -1.5)));
+    }
+
+    #[test]
+    fn step_keys_hold_until_the_next_key() {
+        let track = Track::new().key(0.0, 1.0, Easing::Step).key(2.0, 5.0, Easing::Step).key(3.0, 9.0, Easing::Linear);
+        assert_samples(&track, &[(0.0, 1.0), (1.0, 1.0), (1.99, 1.0), (2.0, 5.0), (2.5, 5.0), (2.99, 5.0), (3.0, 9.0)]);
+    }
+
+    #[test]
+    fn sampling_clamps_outside_the_keys() {
+        let track = Track::new().key(1.0, 2.0, Easing::Linear).key(2.0, 6.0, Easing::Linear);
+        assert_samples(&track, &[(-5.0, 2.0), (0.0, 2.0), (2.5, 6.0), (100.0, 6.0)]);
+        assert_eq!(Track::<f32>::new().sample(1.0), None);
+        assert_eq!(Track::new().key(1.0, 3.0, Easing::Step).sample(0.0), Some(3.0));
+
+        // Played once, the animation stops on the last value.
+        let mut animation = Animation::new(track.bind(|target: &mut f32, value| *target = value));
+        let mut value = 0.0;
+        animation.update(10.0, &mut value);
+        assert!(animation.is_finished());
+        assert_eq!(animation.time(), 2.0);
+        assert_eq!(value, 6.0);
+    }
+}
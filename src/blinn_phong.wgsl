@@ -0,0 +1,70 @@
+// Blinn-Phong lighting in tangent space for the vertex shaders of lit.wgsl, lit_skinned.wgsl
+// and lit_morph.wgsl, which end in lit_vertex.
+
+// Vertex shader
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) texture_coordinates: vec2<f32>,
+    @location(1) tangent_position: vec3<f32>,
+    @location(2) tangent_light_position: vec3<f32>,
+    @location(3) tangent_view_position: vec3<f32>,
+};
+
+// Moves a vertex by its instance and sets up the tangent space lighting. Every lit vertex
+// shader ends here, after deforming the vertex its own way.
+fn lit_vertex(
+    position: vec3<f32>,
+    texture_coordinates: vec2<f32>,
+    normal: vec3<f32>,
+    tangent: vec4<f32>,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = instance_model_matrix(instance);
+    let normal_matrix = instance_normal_matrix(instance);
+
+    let world_normal = normalize(normal_matrix * normal);
+    let world_tangent = normalize(normal_matrix * tangent.xyz);
+    let world_bitangent = cross(world_normal, world_tangent) * tangent.w;
+    let tangent_matrix = transpose(mat3x3<f32>(
+        world_tangent,
+        world_bitangent,
+        world_normal,
+    ));
+
+    var world_position: vec4<f32> = model_matrix * vec4<f32>(position, 1.0);
+
+    var out: VertexOutput;
+    out.texture_coordinates = texture_coordinates;
+    out.clip_position = camera.view_proj * world_position;
+    out.tangent_position = tangent_matrix * world_position.xyz;
+    out.tangent_view_position = tangent_matrix * camera.view_pos.xyz;
+    out.tangent_light_position = tangent_matrix * light.position;
+
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texture_color = textureSample(t_diffuse, s_diffuse, in.texture_coordinates);
+    let normal: vec4<f32> = textureSample(t_normal, s_normal, in.texture_coordinates);
+    
+    let ambient_color = light.ambient;
+
+    let tangent_normal = normal.xyz * 2.0 - 1.0;
+    let light_dir = normalize(in.tangent_light_position - in.tangent_position);
+    let view_dir = normalize(in.tangent_view_position - in.tangent_position);
+    let half_dir = normalize(view_dir + light_dir);
+
+    let diffuse_strength = max(dot(tangent_normal, light_dir), 0.0);
+    let diffuse_color = light.color * diffuse_strength;
+
+    let specular_strength = pow(max(dot(tangent_normal, half_dir), 0.0), 32.0);
+    let specular_color = specular_strength * light.color;
+
+    let result = (ambient_color + diffuse_color + specular_color) * texture_color.xyz;
+
+    return vec4<f32>(result, texture_color.a);
+}
\ No newline at end of file
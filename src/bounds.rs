@@ -0,0 +1,384 @@
+use glam::{Mat3, Mat4, Vec2, Vec3, Vec4};
+use crate::hull::ConvexHull;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Returns `None` if there are no points.
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Vec3>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = *points.next()?;
+        Some(points.fold(Self::new(first, first), |aabb, &point| aabb.including(point)))
+    }
+
+    pub fn including(self, point: Vec3) -> Self {
+        Self::new(self.min.min(point), self.max.max(point))
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    /// Zero for points inside the box.
+    pub fn distance(&self, point: Vec3) -> f32 {
+        point.clamp(self.min, self.max).distance(point)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    /// Fits a sphere using Ritter's algorithm, which is fast but can be up to about 5% larger than the minimal sphere.
+    /// Returns `None` if there are no points.
+    pub fn from_points(points: &[Vec3]) -> Option<Self> {
+        let first = *points.first()?;
+        let farthest_from = |from: Vec3| {
+            points
+                .iter()
+                .copied()
+                .max_by(|a, b| a.distance_squared(from).total_cmp(&b.distance_squared(from)))
+                .unwrap()
+        };
+        let a = farthest_from(first);
+        let b = farthest_from(a);
+        let mut sphere = Self::new((a + b) * 0.5, a.distance(b) * 0.5);
+        for &point in points {
+            sphere = sphere.including(point);
+        }
+        Some(sphere)
+    }
+
+    /// Fits the smallest sphere around the points with Welzl's algorithm, in expected linear
+    /// time. Returns `None` if there are no points.
+    pub fn minimal(points: &[Vec3]) -> Option<Self> {
+        // Welzl's algorithm is only fast for points in random order, and sorted input is common.
+        let mut points = points.to_vec();
+        let mut state = 0x9e37_79b9_u32;
+        for i in (1..points.len()).rev() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            points.swap(i, state as usize % (i + 1));
+        }
+
+        let mut sphere = Self::new(*points.first()?, 0.0);
+        for i in 1..points.len() {
+            if sphere.encloses(points[i]) {
+                continue;
+            }
+            sphere = Self::new(points[i], 0.0);
+            for j in 0..i {
+                if sphere.encloses(points[j]) {
+                    continue;
+                }
+                sphere = Self::through_two(points[i], points[j]);
+                for k in 0..j {
+                    if sphere.encloses(points[k]) {
+                        continue;
+                    }
+                    sphere = Self::through_three(points[i], points[j], points[k]);
+                    for l in 0..k {
+                        if !sphere.encloses(points[l]) {
+                            sphere = Self::through_four(points[i], points[j], points[k], points[l]);
+                        }
+                    }
+                }
+            }
+        }
+        Some(sphere)
+    }
+
+    /// Like [`Self::contains`] with some slack for rounding, so the minimal sphere settles.
+    fn encloses(&self, point: Vec3) -> bool {
+        self.center.distance(point) <= self.radius * (1.0 + 1e-5) + f32::EPSILON
+    }
+
+    fn through_two(a: Vec3, b: Vec3) -> Self {
+        Self::new((a + b) * 0.5, a.distance(b) * 0.5)
+    }
+
+    /// The smallest sphere with all three points on its surface.
+    fn through_three(a: Vec3, b: Vec3, c: Vec3) -> Self {
+        let (ab, ac) = (b - a, c - a);
+        let normal = ab.cross(ac);
+        let denominator = 2.0 * normal.length_squared();
+        if denominator <= f32::EPSILON * ab.length_squared() * ac.length_squared() {
+            // In a line, the two farthest apart span the sphere.
+            return [Self::through_two(a, b), Self::through_two(a, c), Self::through_two(b, c)]
+                .into_iter()
+                .max_by(|x, y| x.radius.total_cmp(&y.radius))
+                .unwrap();
+        }
+        let offset = (normal.cross(ab) * ac.length_squared() + ac.cross(normal) * ab.length_squared()) / denominator;
+        Self::new(a + offset, offset.length())
+    }
+
+    fn through_four(a: Vec3, b: Vec3, c: Vec3, d: Vec3) -> Self {
+        let rows = Mat3::from_cols(b - a, c - a, d - a).transpose();
+        let determinant = rows.determinant();
+        let lengths = Vec3::new((b - a).length_squared(), (c - a).length_squared(), (d - a).length_squared());
+        if determinant.abs() > f32::EPSILON * lengths.max_element().powf(1.5) {
+            let offset = rows.inverse() * lengths * 0.5;
+            return Self::new(a + offset, offset.length());
+        }
+        // In a plane, the smallest sphere through three of them that holds the fourth.
+        [(a, b, c, d), (a, b, d, c), (a, c, d, b), (b, c, d, a)]
+            .into_iter()
+            .map(|(a, b, c, outside)| (Self::through_three(a, b, c), outside))
+            .filter(|(sphere, outside)| sphere.encloses(*outside))
+            .map(|(sphere, _)| sphere)
+            .min_by(|x, y| x.radius.total_cmp(&y.radius))
+            .unwrap_or_else(|| Self::through_three(a, b, c))
+    }
+
+    pub fn including(self, point: Vec3) -> Self {
+        let distance = self.center.distance(point);
+        if distance <= self.radius {
+            return self;
+        }
+        let radius = (self.radius + distance) * 0.5;
+        let center = self.center + (point - self.center) * ((radius - self.radius) / distance);
+        Self::new(center, radius)
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        self.center.distance_squared(point) <= self.radius * self.radius
+    }
+}
+
+/// A box turned to fit, as a center, three unit axes in the columns of `axes` and the half
+/// size along each of them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Obb {
+    pub center: Vec3,
+    pub axes: Mat3,
+    pub half_extents: Vec3,
+}
+
+impl Obb {
+    pub fn new(center: Vec3, axes: Mat3, half_extents: Vec3) -> Self {
+        Self { center, axes, half_extents }
+    }
+
+    pub fn from_aabb(aabb: &Aabb) -> Self {
+        Self::new(aabb.center(), Mat3::IDENTITY, aabb.size() * 0.5)
+    }
+
+    /// The box along `axes` just holding the points.
+    fn fit(points: &[Vec3], axes: Mat3) -> Self {
+        let local = Aabb::from_points(&points.iter().map(|&p| axes.transpose() * p).collect::<Vec<_>>()).unwrap();
+        Self::new(axes * local.center(), axes, local.size() * 0.5)
+    }
+
+    /// Fits a box around the points, trying the principal axes of their convex hull and a box
+    /// resting on each face of the hull, turned to the smallest footprint. The tightest box
+    /// nearly always rests on a face. Points with no volume get a flat box along their
+    /// principal axes. Returns `None` if there are no points.
+    pub fn from_points(points: &[Vec3]) -> Option<Self> {
+        let Some(hull) = ConvexHull::from_points(points) else {
+            return (!points.is_empty()).then(|| Self::fit(points, principal_axes(points)));
+        };
+        let points = &hull.positions;
+        let mut best = Self::fit(points, principal_axes(points));
+        let mut normals = hull
+            .indices
+            .chunks_exact(3)
+            .filter_map(|t| {
+                let [a, b, c] = [t[0], t[1], t[2]].map(|i| points[i as usize]);
+                (b - a).cross(c - a).try_normalize()
+            })
+            .collect::<Vec<_>>();
+        normals.dedup_by(|a, b| a.dot(*b) > 1.0 - 1e-6);
+        for normal in normals {
+            let (u, v) = normal.any_orthonormal_pair();
+            let flat = points.iter().map(|&p| Vec2::new(p.dot(u), p.dot(v))).collect::<Vec<_>>();
+            let direction = min_area_direction(&flat);
+            let side = u * direction.x + v * direction.y;
+            let candidate = Self::fit(points, Mat3::from_cols(side, normal.cross(side), normal));
+            if candidate.volume() < best.volume() {
+                best = candidate;
+            }
+        }
+        Some(best)
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.half_extents.x * self.half_extents.y * self.half_extents.z * 8.0
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|i| {
+            let signs = Vec3::new((i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2 & 1) as f32) * 2.0 - 1.0;
+            self.center + self.axes * (signs * self.half_extents)
+        })
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        (self.axes.transpose() * (point - self.center)).abs().cmple(self.half_extents).all()
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        let reach = Mat3::from_cols(self.axes.x_axis.abs(), self.axes.y_axis.abs(), self.axes.z_axis.abs()) * self.half_extents;
+        Aabb::new(self.center - reach, self.center + reach)
+    }
+}
+
+/// The eigenvectors of the covariance of the points, sorted from the largest spread down.
+fn principal_axes(points: &[Vec3]) -> Mat3 {
+    let mean = points.iter().sum::<Vec3>() / points.len() as f32;
+    let mut covariance = Mat3::ZERO;
+    for &p in points {
+        let d = p - mean;
+        covariance += Mat3::from_cols(d * d.x, d * d.y, d * d.z);
+    }
+    let (values, vectors) = symmetric_eigen(covariance);
+    let mut order = [0, 1, 2];
+    order.sort_by(|&a, &b| values[b].total_cmp(&values[a]));
+    let [x, y, _] = order.map(|i| vectors.col(i).normalize_or_zero());
+    // Rebuilding the last axis keeps the frame right-handed.
+    if x == Vec3::ZERO || y == Vec3::ZERO {
+        return Mat3::IDENTITY;
+    }
+    Mat3::from_cols(x, y, x.cross(y))
+}
+
+/// Diagonalizes a symmetric matrix with Jacobi rotations, returning the eigenvalues and the
+/// eigenvectors as columns.
+fn symmetric_eigen(mut matrix: Mat3) -> (Vec3, Mat3) {
+    let mut vectors = Mat3::IDENTITY;
+    for _ in 0..32 {
+        // The largest entry off the diagonal is rotated away each step.
+        let (p, q) = [(0, 1), (0, 2), (1, 2)]
+            .into_iter()
+            .max_by(|&(a, b), &(c, d)| matrix.col(b)[a].abs().total_cmp(&matrix.col(d)[c].abs()))
+            .unwrap();
+        let off_diagonal = matrix.col(q)[p];
+        if off_diagonal.abs() <= 1e-12 {
+            break;
+        }
+        let theta = (matrix.col(q)[q] - matrix.col(p)[p]) / (2.0 * off_diagonal);
+        let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+        let c = 1.0 / (t * t + 1.0).sqrt();
+        let s = t * c;
+        let mut rotation = Mat3::IDENTITY;
+        rotation.col_mut(p)[p] = c;
+        rotation.col_mut(q)[q] = c;
+        rotation.col_mut(q)[p] = s;
+        rotation.col_mut(p)[q] = -s;
+        matrix = rotation.transpose() * matrix * rotation;
+        vectors *= rotation;
+    }
+    (Vec3::new(matrix.x_axis.x, matrix.y_axis.y, matrix.z_axis.z), vectors)
+}
+
+/// The direction of the side of the smallest rectangle around the points, found with rotating
+/// calipers over their 2D convex hull.
+fn min_area_direction(points: &[Vec2]) -> Vec2 {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    // Andrew's monotone chain, lower half then upper half.
+    let mut hull: Vec<Vec2> = vec![];
+    for pass in 0..2 {
+        let start = hull.len();
+        for &p in sorted.iter() {
+            while hull.len() >= start + 2 && (hull[hull.len() - 1] - hull[hull.len() - 2]).perp_dot(p - hull[hull.len() - 2]) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        hull.pop();
+        if pass == 0 {
+            sorted.reverse();
+        }
+    }
+
+    let mut best = (f32::INFINITY, Vec2::X);
+    for i in 0..hull.len() {
+        let Some(direction) = (hull[(i + 1) % hull.len()] - hull[i]).try_normalize() else {
+            continue;
+        };
+        let normal = direction.perp();
+        let (mut min, mut max) = (Vec2::INFINITY, Vec2::NEG_INFINITY);
+        for &p in &hull {
+            let local = Vec2::new(p.dot(direction), p.dot(normal));
+            min = min.min(local);
+            max = max.max(local);
+        }
+        let area = (max - min).x * (max - min).y;
+        if area < best.0 {
+            best = (area, direction);
+        }
+    }
+    best.1
+}
+
+/// The six planes around the volume a camera sees, with normals pointing inwards. Each plane
+/// is stored as its normal in xyz and its distance from the origin in w.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frustum {
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from a view projection matrix with a clip space depth of 0 to 1,
+    /// following Gribb and Hartmann.
+    pub fn from_matrix(view_proj: Mat4) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| view_proj.row(i));
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| plane / plane.truncate().length());
+        Self { planes }
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        self.planes.iter().all(|plane| plane.dot(point.extend(1.0)) >= 0.0)
+    }
+
+    /// Conservative, so boxes near the corners of the frustum can pass without being inside it.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            let farthest = Vec3::select(normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            normal.dot(farthest) + plane.w >= 0.0
+        })
+    }
+
+    /// Conservative in the same way as [`Self::intersects_aabb`].
+    pub fn intersects_obb(&self, obb: &Obb) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            let reach = (obb.axes.transpose() * normal).abs().dot(obb.half_extents);
+            normal.dot(obb.center) + plane.w >= -reach
+        })
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes.iter().all(|plane| plane.dot(sphere.center.extend(1.0)) >= -sphere.radius)
+    }
+}
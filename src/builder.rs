@@ -0,0 +1,217 @@
+use std::marker::PhantomData;
+use glam::{UVec4, Vec2, Vec3, Vec4};
+use crate::{mesh_data::MeshData, model::{FromAttributes, Mesh, Vertex}, morph::MorphTarget, normals::{self, NormalMode}, optimize};
+
+pub struct MeshBuilder<V> {
+    name: String,
+    positions: Vec<Vec3>,
+    tex_coords: Option<Vec<Vec2>>,
+    normals: Option<Vec<Vec3>>,
+    tangents: Option<Vec<Vec4>>,
+    colors: Option<Vec<Vec4>>,
+    skin: Option<(Vec<UVec4>, Vec<Vec4>)>,
+    morph_targets: Vec<MorphTarget>,
+    indices: Option<Vec<u32>>,
+    material: usize,
+    normal_mode: NormalMode,
+    compute_tangents: bool,
+    compute_bounds: bool,
+    retain_data: bool,
+    optimize: bool,
+    _vertex: PhantomData<V>,
+}
+
+impl<V: Vertex + FromAttributes + bytemuck::Pod> MeshBuilder<V> {
+    pub fn new(name: &str, positions: Vec<Vec3>) -> Self {
+        Self {
+            name: name.to_owned(),
+            positions,
+            tex_coords: None,
+            normals: None,
+            tangents: None,
+            colors: None,
+            skin: None,
+            morph_targets: vec![],
+            indices: None,
+            material: 0,
+            normal_mode: NormalMode::default(),
+            compute_tangents: true,
+            compute_bounds: true,
+            retain_data: false,
+            optimize: false,
+            _vertex: PhantomData,
+        }
+    }
+
+    pub fn from_data(name: &str, data: MeshData) -> Self {
+        let mut builder = Self::new(name, data.positions)
+            .tex_coords(data.tex_coords)
+            .normals(data.normals)
+            .tangents(data.tangents)
+            .morph_targets(data.morph_targets)
+            .indices(data.indices);
+        if !data.colors.is_empty() {
+            builder = builder.colors(data.colors);
+        }
+        if !data.joints.is_empty() {
+            builder = builder.skin(data.joints, data.weights);
+        }
+        builder
+    }
+
+    pub fn tex_coords(mut self, tex_coords: Vec<Vec2>) -> Self {
+        self.tex_coords = Some(tex_coords);
+        self
+    }
+
+    /// When no normals are given they are generated with the [`MeshBuilder::normal_mode`].
+    pub fn normals(mut self, normals: Vec<Vec3>) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    /// When no tangents are given they are generated unless [`MeshBuilder::compute_tangents`] is turned off.
+    pub fn tangents(mut self, tangents: Vec<Vec4>) -> Self {
+        self.tangents = Some(tangents);
+        self
+    }
+
+    /// Linear RGBA vertex colors. Without them every vertex is white.
+    pub fn colors(mut self, colors: Vec<Vec4>) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    /// The four joints moving each vertex and their weights, for skinned meshes.
+    pub fn skin(mut self, joints: Vec<UVec4>, weights: Vec<Vec4>) -> Self {
+        self.skin = Some((joints, weights));
+        self
+    }
+
+    /// Blend shapes with an offset for every vertex, carried along when vertices are split.
+    pub fn morph_targets(mut self, morph_targets: Vec<MorphTarget>) -> Self {
+        self.morph_targets = morph_targets;
+        self
+    }
+
+    /// When no indices are given every three vertices form a triangle.
+    pub fn indices(mut self, indices: Vec<u32>) -> Self {
+        self.indices = Some(indices);
+        self
+    }
+
+    pub fn material(mut self, material: usize) -> Self {
+        self.material = material;
+        self
+    }
+
+    pub fn normal_mode(mut self, normal_mode: NormalMode) -> Self {
+        self.normal_mode = normal_mode;
+        self
+    }
+
+    pub fn compute_tangents(mut self, compute_tangents: bool) -> Self {
+        self.compute_tangents = compute_tangents;
+        self
+    }
+
+    pub fn compute_bounds(mut self, compute_bounds: bool) -> Self {
+        self.compute_bounds = compute_bounds;
+        self
+    }
+
+    /// Keeps a [`MeshData`] copy of the uploaded geometry in [`Mesh::data`].
+    pub fn retain_data(mut self, retain_data: bool) -> Self {
+        self.retain_data = retain_data;
+        self
+    }
+
+    /// Welds duplicate vertices and reorders the triangles and vertices for the GPU caches
+    /// once the attributes are filled in, logging how much it helped.
+    pub fn optimize(mut self, optimize: bool) -> Self {
+        self.optimize = optimize;
+        self
+    }
+
+    /// Fills in the missing attributes without uploading anything.
+    pub fn build_data(self) -> MeshData {
+        let positions = self.positions;
+        let indices = self.indices.unwrap_or_else(|| (0..positions.len() as u32).collect());
+        let tex_coords = self.tex_coords.unwrap_or_else(|| vec![Vec2::ZERO; positions.len()]);
+        let (joints, weights) = self.skin.unwrap_or_default();
+        let mut data = MeshData {
+            tex_coords,
+            normals: self.normals.unwrap_or_default(),
+            tangents: self.tangents.unwrap_or_default(),
+            colors: self.colors.unwrap_or_default(),
+            joints,
+            weights,
+            morph_targets: self.morph_targets,
+            indices,
+            positions,
+        };
+        if data.normals.is_empty() {
+            let generated = normals::generate_normals(&data.positions, &data.indices, self.normal_mode);
+            data.remap_vertices(&generated.remap);
+            data.indices = generated.indices;
+            data.normals = generated.normals;
+        }
+        if data.tangents.is_empty() {
+            if self.compute_tangents {
+                data.compute_tangents();
+            } else {
+                data.tangents = vec![Vec4::ZERO; data.num_vertices()];
+            }
+        }
+
+        if self.optimize {
+            let report = optimize::optimize(&mut data);
+            log::info!("Optimized {}: {report}", self.name);
+        }
+        data
+    }
+
+    pub fn build(self, device: &wgpu::Device) -> Mesh {
+        let name = self.name.clone();
+        let material = self.material;
+        let compute_bounds = self.compute_bounds;
+        let retain_data = self.retain_data;
+        let data = self.build_data();
+        let vertices = data.vertices::<V>();
+        let bounds = if compute_bounds { data.aabb() } else { None };
+        let mesh = Mesh::from_vertices(&name, &vertices, &data.indices, material, device);
+
+        Mesh {
+            bounds,
+            data: retain_data.then_some(data),
+            ..mesh
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+    use crate::model::ModelVertex;
+    use super::*;
+
+    #[test]
+    fn supplied_tangents_follow_split_vertices() {
+        // Two triangles folded at a right angle along their shared edge, so generating normals
+        // splits the edge's vertices.
+        let positions = vec![vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), vec3(0.0, 0.0, 1.0)];
+        let tangents = (0..4).map(|i| Vec4::new(i as f32, 0.0, 0.0, 1.0)).collect::<Vec<_>>();
+        let data = MeshBuilder::<ModelVertex>::new("fold", positions.clone())
+            .indices(vec![0, 1, 2, 0, 3, 1])
+            .tangents(tangents.clone())
+            .build_data();
+
+        assert!(data.num_vertices() > 4);
+        assert_eq!(data.tangents.len(), data.num_vertices());
+        for (position, tangent) in data.positions.iter().zip(&data.tangents) {
+            let source = positions.iter().position(|p| p == position).unwrap();
+            assert_eq!(*tangent, tangents[source]);
+        }
+        assert_eq!(data.vertices::<ModelVertex>().len(), data.num_vertices());
+    }
+}
@@ -1,4 +1,6 @@
 use glam::{vec4, Mat4, Quat, Vec3, Vec4};
+use crate::bounds::Frustum;
+use crate::spline::Spline;
 use winit::event::{DeviceEvent, ElementState, MouseButton, MouseScrollDelta, WindowEvent};
 
 pub struct Camera {
@@ -17,10 +19,54 @@ impl Camera {
         Self { eye, target, up, aspect, fovy, znear, zfar, uniform: CameraUniform::new() }
     }
 
-    pub fn build_view_projection_matrix(&mut self) {
+    pub fn eye(&self) -> Vec3 {
+        self.eye
+    }
+
+    pub fn target(&self) -> Vec3 {
+        self.target
+    }
+
+    /// The vertical field of view in degrees.
+    pub fn fovy(&self) -> f32 {
+        self.fovy
+    }
+
+    /// Changes take effect in the uniform at the next [`Camera::build_view_projection_matrix`].
+    pub fn set_eye(&mut self, eye: Vec3) {
+        self.eye = eye;
+    }
+
+    pub fn set_target(&mut self, target: Vec3) {
+        self.target = target;
+    }
+
+    pub fn set_fovy(&mut self, fovy: f32) {
+        self.fovy = fovy;
+    }
+
+    pub fn view_projection(&self) -> Mat4 {
         let view = Mat4::look_at_rh(self.eye, self.target, self.up);
         let proj = Mat4::perspective_rh(self.aspect, self.fovy.to_radians(), self.znear, self.zfar);
-        self.uniform.view_proj = OPENGL_TO_WGPU_MATRIX * proj * view;
+        OPENGL_TO_WGPU_MATRIX * proj * view
+    }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.view_projection())
+    }
+
+    /// How much of the view height a sphere at `center` covers, where 1 fills it top to bottom.
+    /// Spheres around the eye cover everything.
+    pub fn screen_size(&self, center: Vec3, radius: f32) -> f32 {
+        let distance = self.eye.distance(center);
+        if distance <= radius {
+            return f32::INFINITY;
+        }
+        radius / ((distance * distance - radius * radius).sqrt() * (self.fovy.to_radians() * 0.5).tan())
+    }
+
+    pub fn build_view_projection_matrix(&mut self) {
+        self.uniform.view_proj = self.view_projection();
         self.uniform.view_pos = vec4(self.eye.x, self.eye.y, self.eye.z, 1.0);
     }
 }
@@ -60,7 +106,7 @@ pub struct CameraController {
 impl CameraController {
     pub fn new(camera: Camera, sensitivity: f32) -> Self {
         let distance = camera.eye.distance(camera.target);
-        Self { camera: camera, rotation: Quat::from_euler(glam::EulerRot::XYZ, 0.0, std::f32::consts::PI, 0.0), distance, mouse_down: false, sensitivity }
+        Self { camera, rotation: Quat::from_euler(glam::EulerRot::XYZ, 0.0, std::f32::consts::PI, 0.0), distance, mouse_down: false, sensitivity }
     }
 
     pub fn window_event(&mut self, event: &WindowEvent) -> (bool, bool) {
@@ -111,4 +157,108 @@ impl CameraController {
         self.camera.eye = self.camera.target + -eye_direction * self.distance;
         self.camera.build_view_projection_matrix();
     }
-}
\ No newline at end of file
+}
+
+/// What a [`CameraRail`] looks at.
+#[derive(Clone, Debug)]
+pub enum RailTarget {
+    /// Straight ahead along the rail, this far in front of the eye.
+    Ahead(f32),
+    /// A fixed point, such as the center of a turntable.
+    Point(Vec3),
+    /// A point moving along its own curve, the same fraction of the way along as the eye.
+    Path(Spline),
+}
+
+/// Moves the camera along a curve at a constant speed, for flythroughs and turntables.
+pub struct CameraRail {
+    pub camera: Camera,
+    path: Spline,
+    target: RailTarget,
+    /// In units per second.
+    pub speed: f32,
+    /// Starts over at the end, which suits closed curves.
+    pub looping: bool,
+    distance: f32,
+}
+
+impl CameraRail {
+    pub fn new(camera: Camera, path: Spline, speed: f32) -> Self {
+        let mut rail = Self { camera, path, target: RailTarget::Ahead(1.0), speed, looping: false, distance: 0.0 };
+        rail.update_camera();
+        rail
+    }
+
+    pub fn target(mut self, target: RailTarget) -> Self {
+        self.target = target;
+        self.update_camera();
+        self
+    }
+
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn path(&self) -> &Spline {
+        &self.path
+    }
+
+    /// How far along the rail the camera is.
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    pub fn set_distance(&mut self, distance: f32) {
+        let length = self.path.length();
+        self.distance = if self.looping && length > 0.0 { distance.rem_euclid(length) } else { distance.clamp(0.0, length) };
+        self.update_camera();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.distance >= self.path.length()
+    }
+
+    /// Moves the camera on and rebuilds its uniform, ready to be written to its buffer.
+    pub fn advance(&mut self, seconds: f32) {
+        self.set_distance(self.distance + self.speed * seconds);
+    }
+
+    fn update_camera(&mut self) {
+        let t = self.path.parameter_at(self.distance);
+        let eye = self.path.position(t);
+        let target = match &self.target {
+            RailTarget::Ahead(distance) => eye + self.path.tangent(t) * *distance,
+            RailTarget::Point(point) => *point,
+            RailTarget::Path(path) => {
+                let fraction = if self.path.length() > 0.0 { self.distance / self.path.length() } else { 0.0 };
+                path.position_at(fraction * path.length())
+            }
+        };
+        // Looking at the eye itself has no direction, which happens where the rail stands still,
+        // so the camera keeps looking the way it did.
+        let direction = [target - eye, self.camera.target - self.camera.eye, self.path.tangent(1.0)]
+            .into_iter()
+            .find(|direction| direction.length_squared() > f32::EPSILON * eye.length_squared().max(1.0))
+            .unwrap_or(Vec3::NEG_Z);
+        self.camera.eye = eye;
+        self.camera.target = eye + direction;
+        self.camera.build_view_projection_matrix();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+    use super::*;
+
+    #[test]
+    fn rail_standing_still_keeps_looking_ahead() {
+        let camera = Camera::new(vec3(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y, 1.0, 45.0, 0.1, 100.0);
+        let mut rail = CameraRail::new(camera, Spline::catmull_rom(&[vec3(1.0, 2.0, 3.0)], false), 1.0);
+        rail.advance(1.0);
+        assert_eq!(rail.camera.eye(), vec3(1.0, 2.0, 3.0));
+        assert!(rail.camera.view_projection().is_finite());
+        assert!((rail.camera.target() - rail.camera.eye()).normalize().abs_diff_eq(Vec3::NEG_Z, 1e-6));
+    }
+}
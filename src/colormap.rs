@@ -0,0 +1,93 @@
+use std::ops::Range;
+use glam::{vec3, Vec3, Vec4};
+use crate::mesh_data::MeshData;
+
+const VIRIDIS: [Vec3; 9] = [
+    vec3(0.267, 0.005, 0.329),
+    vec3(0.278, 0.173, 0.478),
+    vec3(0.231, 0.317, 0.545),
+    vec3(0.173, 0.443, 0.557),
+    vec3(0.129, 0.565, 0.553),
+    vec3(0.153, 0.678, 0.506),
+    vec3(0.361, 0.784, 0.388),
+    vec3(0.667, 0.863, 0.196),
+    vec3(0.993, 0.906, 0.144),
+];
+
+const PLASMA: [Vec3; 9] = [
+    vec3(0.050, 0.030, 0.528),
+    vec3(0.298, 0.008, 0.631),
+    vec3(0.494, 0.012, 0.659),
+    vec3(0.663, 0.137, 0.584),
+    vec3(0.800, 0.278, 0.471),
+    vec3(0.898, 0.420, 0.365),
+    vec3(0.973, 0.580, 0.255),
+    vec3(0.992, 0.765, 0.157),
+    vec3(0.940, 0.975, 0.131),
+];
+
+const COOLWARM: [Vec3; 5] = [
+    vec3(0.230, 0.299, 0.754),
+    vec3(0.552, 0.690, 0.996),
+    vec3(0.865, 0.865, 0.865),
+    vec3(0.958, 0.604, 0.482),
+    vec3(0.706, 0.016, 0.150),
+];
+
+const GRAYSCALE: [Vec3; 2] = [Vec3::ZERO, Vec3::ONE];
+
+/// Gradients for coloring values, sampled from 0 to 1.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Colormap {
+    #[default]
+    Viridis,
+    Plasma,
+    /// Diverging from blue to red through gray, for values around zero.
+    Coolwarm,
+    Grayscale,
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl Colormap {
+    /// The gradient stops in sRGB.
+    fn stops(self) -> &'static [Vec3] {
+        match self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Plasma => &PLASMA,
+            Colormap::Coolwarm => &COOLWARM,
+            Colormap::Grayscale => &GRAYSCALE,
+        }
+    }
+
+    /// The linear RGBA color at `t`, clamped to the ends of the gradient.
+    pub fn sample(self, t: f32) -> Vec4 {
+        let stops = self.stops();
+        let position = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) * (stops.len() - 1) as f32 };
+        let index = (position as usize).min(stops.len() - 2);
+        let color = stops[index].lerp(stops[index + 1], position - index as f32);
+        Vec3::from_array(color.to_array().map(srgb_to_linear)).extend(1.0)
+    }
+
+    /// Colors every vertex by `value` of its position, spreading `range` across the gradient.
+    /// Without a range the lowest value gets the start of the gradient and the highest the end.
+    pub fn color_vertices(self, data: &mut MeshData, range: Option<Range<f32>>, value: impl Fn(Vec3) -> f32) {
+        let values = data.positions.iter().map(|&position| value(position)).collect::<Vec<_>>();
+        let range = range.unwrap_or_else(|| {
+            let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+            let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            min..max
+        });
+        let span = range.end - range.start;
+        data.colors = values
+            .iter()
+            .map(|value| self.sample(if span.abs() > f32::EPSILON { (value - range.start) / span } else { 0.5 }))
+            .collect();
+    }
+}
@@ -0,0 +1,472 @@
+use std::collections::{HashMap, HashSet};
+use glam::{Mat4, Vec2, Vec3};
+use crate::{builder::MeshBuilder, mesh_data::MeshData, model::{Mesh, ModelVertex}, optimize, simplify::weld_points};
+
+/// How far from a plane a point still counts as on it.
+const PLANE_EPSILON: f32 = 1e-5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct CsgVertex {
+    position: Vec3,
+    normal: Vec3,
+    tex_coords: Vec2,
+}
+
+impl CsgVertex {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Self {
+            position: self.position.lerp(other.position, t),
+            normal: self.normal.lerp(other.normal, t).normalize_or_zero(),
+            tex_coords: self.tex_coords.lerp(other.tex_coords, t),
+        }
+    }
+
+    fn flip(&mut self) {
+        self.normal = -self.normal;
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Plane {
+    normal: Vec3,
+    distance: f32,
+}
+
+impl Plane {
+    fn from_points(a: Vec3, b: Vec3, c: Vec3) -> Option<Self> {
+        let normal = (b - a).cross(c - a).try_normalize()?;
+        Some(Self { normal, distance: normal.dot(a) })
+    }
+
+    fn flip(&mut self) {
+        self.normal = -self.normal;
+        self.distance = -self.distance;
+    }
+}
+
+/// A convex polygon, counter-clockwise seen from the front of its plane.
+#[derive(Clone, Debug)]
+struct Polygon {
+    vertices: Vec<CsgVertex>,
+    plane: Plane,
+}
+
+impl Polygon {
+    fn new(vertices: Vec<CsgVertex>) -> Option<Self> {
+        let plane = Plane::from_points(vertices[0].position, vertices[1].position, vertices[2].position)?;
+        Some(Self { vertices, plane })
+    }
+
+    fn flip(&mut self) {
+        self.vertices.reverse();
+        self.vertices.iter_mut().for_each(CsgVertex::flip);
+        self.plane.flip();
+    }
+}
+
+const COPLANAR: u8 = 0;
+const FRONT: u8 = 1;
+const BACK: u8 = 2;
+const SPANNING: u8 = 3;
+
+/// Sorts `polygon` by which side of `plane` it is on, cutting it in two if it spans the plane.
+/// Polygons in the plane go with the side their own plane faces.
+fn split_polygon(
+    plane: Plane,
+    polygon: Polygon,
+    coplanar_front: &mut Vec<Polygon>,
+    coplanar_back: &mut Vec<Polygon>,
+    front: &mut Vec<Polygon>,
+    back: &mut Vec<Polygon>,
+) {
+    let sides = polygon
+        .vertices
+        .iter()
+        .map(|vertex| {
+            let t = plane.normal.dot(vertex.position) - plane.distance;
+            if t < -PLANE_EPSILON {
+                BACK
+            } else if t > PLANE_EPSILON {
+                FRONT
+            } else {
+                COPLANAR
+            }
+        })
+        .collect::<Vec<_>>();
+
+    match sides.iter().fold(COPLANAR, |all, &side| all | side) {
+        COPLANAR if plane.normal.dot(polygon.plane.normal) > 0.0 => coplanar_front.push(polygon),
+        COPLANAR => coplanar_back.push(polygon),
+        FRONT => front.push(polygon),
+        BACK => back.push(polygon),
+        _ => {
+            let mut front_vertices = vec![];
+            let mut back_vertices = vec![];
+            let count = polygon.vertices.len();
+            for i in 0..count {
+                let j = (i + 1) % count;
+                let (vertex, next) = (polygon.vertices[i], polygon.vertices[j]);
+                if sides[i] != BACK {
+                    front_vertices.push(vertex);
+                }
+                if sides[i] != FRONT {
+                    back_vertices.push(vertex);
+                }
+                if sides[i] | sides[j] == SPANNING {
+                    let t = (plane.distance - plane.normal.dot(vertex.position))
+                        / plane.normal.dot(next.position - vertex.position);
+                    let split = vertex.lerp(next, t);
+                    front_vertices.push(split);
+                    back_vertices.push(split);
+                }
+            }
+            // The pieces keep the plane of the whole, even when rounding makes them slivers.
+            for (vertices, list) in [(front_vertices, front), (back_vertices, back)] {
+                if vertices.len() >= 3 {
+                    list.push(Polygon { vertices, plane: polygon.plane });
+                }
+            }
+        }
+    }
+}
+
+/// A BSP tree of polygons, split by the plane of the first polygon put in it.
+#[derive(Clone, Debug, Default)]
+struct Node {
+    plane: Option<Plane>,
+    front: Option<Box<Node>>,
+    back: Option<Box<Node>>,
+    polygons: Vec<Polygon>,
+}
+
+impl Node {
+    fn new(polygons: Vec<Polygon>) -> Self {
+        let mut node = Self::default();
+        node.build(polygons);
+        node
+    }
+
+    /// Turns solid space into empty space and back.
+    fn invert(&mut self) {
+        self.polygons.iter_mut().for_each(Polygon::flip);
+        if let Some(plane) = &mut self.plane {
+            plane.flip();
+        }
+        if let Some(front) = &mut self.front {
+            front.invert();
+        }
+        if let Some(back) = &mut self.back {
+            back.invert();
+        }
+        std::mem::swap(&mut self.front, &mut self.back);
+    }
+
+    /// Removes the parts of `polygons` inside this tree.
+    fn clip_polygons(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        let Some(plane) = self.plane else {
+            return polygons;
+        };
+        let (mut front, mut back) = (vec![], vec![]);
+        for polygon in polygons {
+            let (mut coplanar_front, mut coplanar_back) = (vec![], vec![]);
+            split_polygon(plane, polygon, &mut coplanar_front, &mut coplanar_back, &mut front, &mut back);
+            front.append(&mut coplanar_front);
+            back.append(&mut coplanar_back);
+        }
+        let mut front = match &self.front {
+            Some(node) => node.clip_polygons(front),
+            None => front,
+        };
+        // Nothing behind a leaf plane is outside the solid.
+        if let Some(node) = &self.back {
+            front.append(&mut node.clip_polygons(back));
+        }
+        front
+    }
+
+    /// Removes the parts of the polygons of this tree inside `other`.
+    fn clip_to(&mut self, other: &Node) {
+        self.polygons = other.clip_polygons(std::mem::take(&mut self.polygons));
+        if let Some(front) = &mut self.front {
+            front.clip_to(other);
+        }
+        if let Some(back) = &mut self.back {
+            back.clip_to(other);
+        }
+    }
+
+    fn all_polygons(&self) -> Vec<Polygon> {
+        let mut polygons = self.polygons.clone();
+        for child in [&self.front, &self.back].into_iter().flatten() {
+            polygons.append(&mut child.all_polygons());
+        }
+        polygons
+    }
+
+    fn build(&mut self, polygons: Vec<Polygon>) {
+        if polygons.is_empty() {
+            return;
+        }
+        let plane = *self.plane.get_or_insert(polygons[0].plane);
+        let (mut front, mut back) = (vec![], vec![]);
+        for polygon in polygons {
+            let (mut coplanar_front, mut coplanar_back) = (vec![], vec![]);
+            split_polygon(plane, polygon, &mut coplanar_front, &mut coplanar_back, &mut front, &mut back);
+            self.polygons.append(&mut coplanar_front);
+            self.polygons.append(&mut coplanar_back);
+        }
+        for (child, polygons) in [(&mut self.front, front), (&mut self.back, back)] {
+            if !polygons.is_empty() {
+                child.get_or_insert_with(Default::default).build(polygons);
+            }
+        }
+    }
+}
+
+/// Splits the polygons at the corners of their neighbours that lie along their edges, which
+/// cutting one face but not the face beside it leaves behind. Returns whether each polygon got
+/// new vertices, which lie in line with their neighbours.
+fn close_t_junctions(polygons: &mut [Polygon]) -> Vec<bool> {
+    let positions = polygons.iter().flat_map(|polygon| polygon.vertices.iter().map(|vertex| vertex.position)).collect::<Vec<_>>();
+    let (point_of_vertex, points) = weld_points(&positions, PLANE_EPSILON);
+    let mut corners = vec![];
+    let mut first = 0;
+    for polygon in polygons.iter() {
+        corners.push(point_of_vertex[first..first + polygon.vertices.len()].to_vec());
+        first += polygon.vertices.len();
+    }
+    let edges = |corners: &[u32]| (0..corners.len()).map(|i| (corners[i], corners[(i + 1) % corners.len()])).collect::<Vec<_>>();
+    let mut counts = HashMap::new();
+    for edge in corners.iter().flat_map(|corners| edges(corners)) {
+        *counts.entry(edge).or_insert(0) += 1;
+    }
+    // A closed surface runs along every edge as often one way as the other.
+    let open = counts
+        .iter()
+        .filter(|&(&(a, b), &count)| counts.get(&(b, a)).copied().unwrap_or(0) != count)
+        .map(|(&edge, _)| edge)
+        .collect::<HashSet<_>>();
+    let candidates = open.iter().flat_map(|&(a, b)| [a, b]).collect::<HashSet<_>>();
+
+    polygons
+        .iter_mut()
+        .zip(corners)
+        .map(|(polygon, corners)| {
+            let mut vertices = vec![];
+            for (i, (a, b)) in edges(&corners).into_iter().enumerate() {
+                let (vertex, next) = (polygon.vertices[i], polygon.vertices[(i + 1) % corners.len()]);
+                vertices.push(vertex);
+                if !open.contains(&(a, b)) {
+                    continue;
+                }
+                let edge = next.position - vertex.position;
+                let mut on_edge = candidates
+                    .iter()
+                    .filter(|&&point| point != a && point != b)
+                    .map(|&point| (edge.dot(points[point as usize] - vertex.position) / edge.length_squared(), point))
+                    .filter(|&(t, point)| t > 0.0 && t < 1.0 && (vertex.position + edge * t).distance(points[point as usize]) <= PLANE_EPSILON)
+                    .collect::<Vec<_>>();
+                on_edge.sort_by(|x, y| x.0.total_cmp(&y.0));
+                vertices.extend(on_edge.into_iter().map(|(t, point)| CsgVertex { position: points[point as usize], ..vertex.lerp(next, t) }));
+            }
+            let split = vertices.len() > polygon.vertices.len();
+            polygon.vertices = vertices;
+            split
+        })
+        .collect()
+}
+
+/// A closed mesh to combine with boolean operations. The results carry normals and UVs
+/// interpolated across the cuts.
+#[derive(Clone, Debug, Default)]
+pub struct Solid {
+    polygons: Vec<Polygon>,
+}
+
+impl Solid {
+    pub fn from_data(data: &MeshData) -> Self {
+        let vertex = |i: u32| {
+            let i = i as usize;
+            CsgVertex { position: data.positions[i], normal: data.normals[i], tex_coords: data.tex_coords[i] }
+        };
+        let polygons = data
+            .indices
+            .chunks_exact(3)
+            .filter_map(|t| Polygon::new(vec![vertex(t[0]), vertex(t[1]), vertex(t[2])]))
+            .collect();
+        Self { polygons }
+    }
+
+    pub fn transformed(mut self, transform: Mat4) -> Self {
+        let normal_matrix = transform.inverse().transpose();
+        let mirrored = transform.determinant() < 0.0;
+        for polygon in &mut self.polygons {
+            for vertex in &mut polygon.vertices {
+                vertex.position = transform.transform_point3(vertex.position);
+                vertex.normal = normal_matrix.transform_vector3(vertex.normal).normalize_or_zero();
+            }
+            // Mirroring turns the winding inside out.
+            if mirrored {
+                polygon.vertices.reverse();
+            }
+            let [a, b, c] = [0, 1, 2].map(|i| polygon.vertices[i].position);
+            polygon.plane = Plane::from_points(a, b, c).unwrap_or(polygon.plane);
+        }
+        self
+    }
+
+    /// Everything inside either solid.
+    pub fn union(&self, other: &Solid) -> Solid {
+        let mut a = Node::new(self.polygons.clone());
+        let mut b = Node::new(other.polygons.clone());
+        a.clip_to(&b);
+        b.clip_to(&a);
+        b.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.build(b.all_polygons());
+        Solid { polygons: a.all_polygons() }
+    }
+
+    /// Everything inside this solid but not `other`.
+    pub fn difference(&self, other: &Solid) -> Solid {
+        let mut a = Node::new(self.polygons.clone());
+        let mut b = Node::new(other.polygons.clone());
+        a.invert();
+        a.clip_to(&b);
+        b.clip_to(&a);
+        b.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.build(b.all_polygons());
+        a.invert();
+        Solid { polygons: a.all_polygons() }
+    }
+
+    /// Everything inside both solids.
+    pub fn intersection(&self, other: &Solid) -> Solid {
+        let mut a = Node::new(self.polygons.clone());
+        let mut b = Node::new(other.polygons.clone());
+        a.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.clip_to(&b);
+        b.clip_to(&a);
+        a.build(b.all_polygons());
+        a.invert();
+        Solid { polygons: a.all_polygons() }
+    }
+
+    /// Everything outside the solid, with the faces turned inward.
+    pub fn inverse(&self) -> Solid {
+        let mut polygons = self.polygons.clone();
+        polygons.iter_mut().for_each(Polygon::flip);
+        Solid { polygons }
+    }
+
+    /// Fans the polygons into triangles and welds the vertices they share. Polygons split to
+    /// meet their neighbours are fanned around their center, since corners in line with their
+    /// neighbours would make flat triangles.
+    pub fn to_data(&self) -> MeshData {
+        let mut polygons = self.polygons.clone();
+        let split = close_t_junctions(&mut polygons);
+        let (mut positions, mut normals, mut tex_coords, mut indices) = (vec![], vec![], vec![], vec![]);
+        for (polygon, split) in polygons.iter().zip(split) {
+            let first = positions.len() as u32;
+            for vertex in &polygon.vertices {
+                positions.push(vertex.position);
+                normals.push(vertex.normal);
+                tex_coords.push(vertex.tex_coords);
+            }
+            let count = polygon.vertices.len() as u32;
+            if split {
+                // The attributes vary linearly across a polygon, so the average is the center's.
+                let center = first + count;
+                positions.push(polygon.vertices.iter().map(|vertex| vertex.position).sum::<Vec3>() / count as f32);
+                normals.push(polygon.vertices.iter().map(|vertex| vertex.normal).sum::<Vec3>().normalize_or_zero());
+                tex_coords.push(polygon.vertices.iter().map(|vertex| vertex.tex_coords).sum::<Vec2>() / count as f32);
+                for i in 0..count {
+                    indices.extend([center, first + i, first + (i + 1) % count]);
+                }
+            } else {
+                for i in 1..count - 1 {
+                    indices.extend([first, first + i, first + i + 1]);
+                }
+            }
+        }
+        let mut data = MeshBuilder::<ModelVertex>::new("CSG", positions)
+            .normals(normals)
+            .tex_coords(tex_coords)
+            .indices(indices)
+            .build_data();
+        optimize::weld_vertices(&mut data);
+        data
+    }
+
+    pub fn mesh(&self, material: usize, device: &wgpu::Device) -> Mesh {
+        MeshBuilder::<ModelVertex>::from_data("CSG", self.to_data())
+            .material(material)
+            .build(device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+    use crate::{bounds::Aabb, shapes::{cube_data, icosphere_data}, validation::validate};
+    use super::*;
+
+    /// Closed, consistently wound and otherwise clean.
+    fn assert_closed(data: &MeshData) {
+        let report = validate(data);
+        assert_eq!(report.boundary_edges, 0, "{report}");
+        assert!(!report.has_errors(), "{report}");
+    }
+
+    /// Two 2x2x2 cubes overlapping in a 1 x 1.5 x 1.75 box.
+    fn cubes() -> (Solid, Solid) {
+        let a = Solid::from_data(&cube_data(Vec3::splat(2.0)));
+        let b = Solid::from_data(&cube_data(Vec3::splat(2.0))).transformed(Mat4::from_translation(vec3(1.0, 0.5, 0.25)));
+        (a, b)
+    }
+
+    #[test]
+    fn overlapping_cubes() {
+        let (a, b) = cubes();
+        for (solid, expected) in [(a.union(&b), 13.375), (a.difference(&b), 5.375), (a.intersection(&b), 2.625)] {
+            let data = solid.to_data();
+            assert_closed(&data);
+            assert!((data.volume() - expected).abs() < 1e-4, "volume {} instead of {expected}", data.volume());
+        }
+    }
+
+    #[test]
+    fn results_stay_inside_their_solids() {
+        let (a, b) = cubes();
+        let a_box = Aabb::new(Vec3::splat(-1.0), Vec3::splat(1.0));
+        let b_box = Aabb::new(vec3(0.0, -0.5, -0.75), vec3(2.0, 1.5, 1.25));
+        let grown = |aabb: Aabb| Aabb::new(aabb.min - PLANE_EPSILON, aabb.max + PLANE_EPSILON);
+        let shrunk = |aabb: Aabb| Aabb::new(aabb.min + PLANE_EPSILON, aabb.max - PLANE_EPSILON);
+
+        let intersection = a.intersection(&b).to_data();
+        assert!(intersection.positions.iter().all(|&p| grown(a_box).contains(p) && grown(b_box).contains(p)));
+        let difference = a.difference(&b).to_data();
+        assert!(difference.positions.iter().all(|&p| grown(a_box).contains(p) && !shrunk(b_box).contains(p)));
+        let union = a.union(&b).to_data();
+        assert!(union.positions.iter().all(|&p| grown(a_box).contains(p) || grown(b_box).contains(p)));
+    }
+
+    #[test]
+    fn cube_minus_sphere_is_closed() {
+        let cube = Solid::from_data(&cube_data(Vec3::splat(2.0)));
+        let sphere_data = icosphere_data(1.2, 2);
+        let sphere = Solid::from_data(&sphere_data);
+        let difference = cube.difference(&sphere).to_data();
+        let intersection = cube.intersection(&sphere).to_data();
+        assert_closed(&difference);
+        assert_closed(&intersection);
+        // The two halves make up the cube between them.
+        assert!((difference.volume() + intersection.volume() - 8.0).abs() < 1e-3);
+        assert!(intersection.volume() < sphere_data.volume());
+    }
+}
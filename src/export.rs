@@ -0,0 +1,380 @@
+use std::{collections::HashMap, fmt::Write as _, fs, io::{BufWriter, Write}, path::Path};
+use anyhow::{anyhow, Context};
+use glam::Vec3;
+use crate::{mesh_data::MeshData, model::{MatModel, Mesh, ModelMaterial}};
+
+/// A mesh to write out, with its material counted in the materials given alongside.
+#[derive(Copy, Clone, Debug)]
+pub struct ExportMesh<'a> {
+    pub name: &'a str,
+    pub data: &'a MeshData,
+    pub material: Option<usize>,
+}
+
+impl<'a> ExportMesh<'a> {
+    /// Needs the data kept with [`crate::builder::MeshBuilder::retain_data`].
+    pub fn from_mesh(mesh: &'a Mesh) -> anyhow::Result<Self> {
+        let data = mesh.data.as_ref().ok_or_else(|| anyhow!("{} was built without retaining its data", mesh.name))?;
+        Ok(Self { name: &mesh.name, data, material: Some(mesh.material) })
+    }
+}
+
+fn model_meshes(model: &MatModel) -> anyhow::Result<Vec<ExportMesh<'_>>> {
+    model.meshes.iter().map(ExportMesh::from_mesh).collect()
+}
+
+/// OBJ and MTL names end at the first space.
+fn obj_name(name: &str) -> String {
+    let name = name.split_whitespace().collect::<Vec<_>>().join("_");
+    if name.is_empty() { "unnamed".to_owned() } else { name }
+}
+
+/// Copies the textures of the materials next to `path` and returns the file names to refer to
+/// them by, as the diffuse, normal, metallic-roughness, occlusion and emissive texture of each
+/// material.
+fn copy_textures(path: &Path, materials: &[ModelMaterial]) -> anyhow::Result<Vec<[Option<String>; 5]>> {
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let sources = materials
+        .iter()
+        .map(|material| {
+            [
+                &material.diffuse_texture,
+                &material.normal_texture,
+                &material.metallic_roughness_texture,
+                &material.occlusion_texture,
+                &material.emissive_texture,
+            ]
+            .map(|texture| texture.path.as_deref())
+        })
+        .collect::<Vec<_>>();
+    let names = copy_files(directory, sources.iter().flatten().flatten().copied())?;
+    Ok(sources.iter().map(|textures| textures.map(|source| source.map(|source| names[source].clone()))).collect())
+}
+
+/// Copies the files into `directory` and returns the name each got there. Files already in
+/// the directory stay as they are, and files from different folders with the same name get
+/// a number added so that none overwrites another.
+fn copy_files<'a>(directory: &Path, sources: impl Iterator<Item = &'a Path>) -> anyhow::Result<HashMap<&'a Path, String>> {
+    let mut files = vec![];
+    for source in sources {
+        let file = fs::canonicalize(source).with_context(|| format!("copying {}", source.display()))?;
+        let name = file.file_name().ok_or_else(|| anyhow!("{} is not a file", source.display()))?.to_string_lossy().into_owned();
+        files.push((source, file, name));
+    }
+
+    // Names go to files rather than paths, as the same file may be reached by different paths.
+    let mut names = HashMap::new();
+    for (_, file, name) in &files {
+        if fs::canonicalize(directory.join(name)).is_ok_and(|destination| destination == *file) {
+            names.insert(file.clone(), name.clone());
+        }
+    }
+    for (_, file, name) in &files {
+        if names.contains_key(file) {
+            continue;
+        }
+        let (stem, extension) = name.rsplit_once('.').map_or((name.as_str(), String::new()), |(stem, extension)| (stem, format!(".{extension}")));
+        let mut unique = name.clone();
+        for number in 1.. {
+            if names.values().all(|taken| *taken != unique) {
+                break;
+            }
+            unique = format!("{stem}_{number}{extension}");
+        }
+        fs::copy(file, directory.join(&unique)).with_context(|| format!("copying {}", file.display()))?;
+        names.insert(file.clone(), unique);
+    }
+    Ok(files.into_iter().map(|(source, file, _)| (source, names[&file].clone())).collect())
+}
+
+/// Writes the meshes to an OBJ file and their materials to an MTL file beside it, copying the
+/// textures along. OBJ has nowhere to keep tangents, so they are left out.
+pub fn write_obj(path: impl AsRef<Path>, meshes: &[ExportMesh], materials: &[ModelMaterial]) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let mut obj = String::new();
+    if !materials.is_empty() {
+        let mtl_path = path.with_extension("mtl");
+        let textures = copy_textures(path, materials)?;
+        let mut mtl = String::new();
+        // The packed metallic-roughness and the occlusion textures have no place in MTL.
+        for (material, [diffuse, normal, _, _, emissive]) in materials.iter().zip(textures) {
+            let factors = &material.factors;
+            let [r, g, b, alpha] = factors.base_color.to_array();
+            writeln!(mtl, "newmtl {}\nKd {r} {g} {b}\nd {alpha}", obj_name(&material.name))?;
+            writeln!(mtl, "Pr {}\nPm {}", factors.roughness, factors.metallic)?;
+            let [r, g, b] = factors.emissive.to_array();
+            writeln!(mtl, "Ke {r} {g} {b}")?;
+            if let Some(diffuse) = diffuse {
+                writeln!(mtl, "map_Kd {diffuse}")?;
+            }
+            if let Some(normal) = normal {
+                writeln!(mtl, "map_Bump {normal}")?;
+            }
+            if let Some(emissive) = emissive {
+                writeln!(mtl, "map_Ke {emissive}")?;
+            }
+            writeln!(mtl)?;
+        }
+        fs::write(&mtl_path, mtl).with_context(|| format!("writing {}", mtl_path.display()))?;
+        writeln!(obj, "mtllib {}", mtl_path.file_name().unwrap().to_string_lossy())?;
+    }
+
+    let mut offset = 1;
+    for mesh in meshes {
+        let data = mesh.data;
+        writeln!(obj, "o {}", obj_name(mesh.name))?;
+        for p in &data.positions {
+            writeln!(obj, "v {} {} {}", p.x, p.y, p.z)?;
+        }
+        // OBJ counts v up from the bottom of the image.
+        for t in &data.tex_coords {
+            writeln!(obj, "vt {} {}", t.x, 1.0 - t.y)?;
+        }
+        for n in &data.normals {
+            writeln!(obj, "vn {} {} {}", n.x, n.y, n.z)?;
+        }
+        if let Some(material) = mesh.material.and_then(|material| materials.get(material)) {
+            writeln!(obj, "usemtl {}", obj_name(&material.name))?;
+        }
+        for t in data.indices.chunks_exact(3) {
+            let [a, b, c] = [t[0], t[1], t[2]].map(|i| i + offset);
+            writeln!(obj, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?;
+        }
+        offset += data.num_vertices() as u32;
+    }
+
+    let mut file = BufWriter::new(fs::File::create(path).with_context(|| format!("creating {}", path.display()))?);
+    file.write_all(obj.as_bytes())?;
+    Ok(())
+}
+
+pub fn write_model_obj(path: impl AsRef<Path>, model: &MatModel) -> anyhow::Result<()> {
+    write_obj(path, &model_meshes(model)?, &model.materials)
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+const GLTF_FLOAT: u32 = 5126;
+const GLTF_UNSIGNED_INT: u32 = 5125;
+const GLTF_ARRAY_BUFFER: u32 = 34962;
+const GLTF_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// The binary buffer of a glTF file with the views and accessors into it.
+#[derive(Default)]
+struct GltfBuffer {
+    bytes: Vec<u8>,
+    views: Vec<String>,
+    accessors: Vec<String>,
+}
+
+impl GltfBuffer {
+    /// Adds one tightly packed view with one accessor over it and returns the accessor.
+    fn push<T: bytemuck::Pod>(&mut self, values: &[T], kind: &str, component_type: u32, target: u32, bounds: Option<(Vec3, Vec3)>) -> usize {
+        let offset = self.bytes.len();
+        self.bytes.extend_from_slice(bytemuck::cast_slice(values));
+        let length = self.bytes.len() - offset;
+        self.bytes.resize(self.bytes.len().next_multiple_of(4), 0);
+        self.views.push(format!(r#"{{"buffer":0,"byteOffset":{offset},"byteLength":{length},"target":{target}}}"#));
+
+        let bounds = bounds.map_or(String::new(), |(min, max)| {
+            format!(r#","min":[{},{},{}],"max":[{},{},{}]"#, min.x, min.y, min.z, max.x, max.y, max.z)
+        });
+        self.accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":{component_type},"count":{},"type":"{kind}"{bounds}}}"#,
+            self.views.len() - 1,
+            values.len(),
+        ));
+        self.accessors.len() - 1
+    }
+}
+
+/// Writes the meshes to a binary glTF file with their normals, UVs, tangents and any vertex
+/// colors. Materials point at their textures by file name, and the textures are copied
+/// beside the file.
+pub fn write_glb(path: impl AsRef<Path>, meshes: &[ExportMesh], materials: &[ModelMaterial]) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let mut buffer = GltfBuffer::default();
+    let mut gltf_meshes = vec![];
+    // glTF has no room for empty buffer views, so meshes without triangles are left out.
+    for mesh in meshes.iter().filter(|mesh| mesh.data.num_triangles() > 0) {
+        let data = mesh.data;
+        let bounds = data.aabb().map(|aabb| (aabb.min, aabb.max));
+        let mut attributes = format!(r#""POSITION":{}"#, buffer.push(&data.positions, "VEC3", GLTF_FLOAT, GLTF_ARRAY_BUFFER, bounds));
+        if !data.normals.is_empty() {
+            let normals = buffer.push(&data.normals, "VEC3", GLTF_FLOAT, GLTF_ARRAY_BUFFER, None);
+            write!(attributes, r#","NORMAL":{normals}"#)?;
+        }
+        if !data.tex_coords.is_empty() {
+            let tex_coords = buffer.push(&data.tex_coords, "VEC2", GLTF_FLOAT, GLTF_ARRAY_BUFFER, None);
+            write!(attributes, r#","TEXCOORD_0":{tex_coords}"#)?;
+        }
+        // Meshes built without tangents have zeros, which glTF does not allow.
+        if !data.tangents.is_empty() && data.tangents.iter().all(|tangent| tangent.w != 0.0) {
+            let tangents = buffer.push(&data.tangents, "VEC4", GLTF_FLOAT, GLTF_ARRAY_BUFFER, None);
+            write!(attributes, r#","TANGENT":{tangents}"#)?;
+        }
+        if !data.colors.is_empty() {
+            let colors = buffer.push(&data.colors, "VEC4", GLTF_FLOAT, GLTF_ARRAY_BUFFER, None);
+            write!(attributes, r#","COLOR_0":{colors}"#)?;
+        }
+        let indices = buffer.push(&data.indices, "SCALAR", GLTF_UNSIGNED_INT, GLTF_ELEMENT_ARRAY_BUFFER, None);
+        let material = mesh
+            .material
+            .filter(|&material| material < materials.len())
+            .map_or(String::new(), |material| format!(r#","material":{material}"#));
+        gltf_meshes.push(format!(
+            r#"{{"name":{},"primitives":[{{"attributes":{{{attributes}}},"indices":{indices}{material}}}]}}"#,
+            json_string(mesh.name),
+        ));
+    }
+
+    let mut images = vec![];
+    let mut gltf_materials = vec![];
+    for (material, file_names) in materials.iter().zip(copy_textures(path, materials)?) {
+        let [diffuse, normal, metallic_roughness, occlusion, emissive] = file_names.map(|file_name| {
+            file_name.map(|file_name| {
+                images.push(format!(r#"{{"uri":{}}}"#, json_string(&file_name)));
+                images.len() - 1
+            })
+        });
+        let factors = &material.factors;
+        let texture = |key: &str, index: Option<usize>, extra: String| {
+            index.map_or(String::new(), |index| format!(r#","{key}":{{"index":{index}{extra}}}"#))
+        };
+        let pbr = format!(
+            r#""baseColorFactor":{:?},"metallicFactor":{},"roughnessFactor":{}{}{}"#,
+            factors.base_color.to_array(),
+            factors.metallic,
+            factors.roughness,
+            texture("baseColorTexture", diffuse, String::new()),
+            texture("metallicRoughnessTexture", metallic_roughness, String::new()),
+        );
+        let alpha = if factors.alpha_cutoff > 0.0 {
+            format!(r#","alphaMode":"MASK","alphaCutoff":{}"#, factors.alpha_cutoff)
+        } else {
+            String::new()
+        };
+        gltf_materials.push(format!(
+            r#"{{"name":{},"pbrMetallicRoughness":{{{pbr}}},"emissiveFactor":{:?}{}{}{}{alpha}}}"#,
+            json_string(&material.name),
+            factors.emissive.to_array(),
+            texture("normalTexture", normal, format!(r#","scale":{}"#, factors.normal_scale)),
+            texture("occlusionTexture", occlusion, format!(r#","strength":{}"#, factors.occlusion_strength)),
+            texture("emissiveTexture", emissive, String::new()),
+        ));
+    }
+    // Every image gets a texture of the same number with the default sampler.
+    let textures = (0..images.len()).map(|i| format!(r#"{{"source":{i}}}"#)).collect::<Vec<_>>();
+    let nodes = (0..gltf_meshes.len()).map(|i| format!(r#"{{"mesh":{i}}}"#)).collect::<Vec<_>>();
+    let scene_nodes = (0..gltf_meshes.len()).map(|i| i.to_string()).collect::<Vec<_>>();
+
+    let mut json = r#"{"asset":{"version":"2.0","generator":"graphics"}"#.to_owned();
+    // glTF does not allow empty arrays, so a file without meshes has no scene either.
+    if !gltf_meshes.is_empty() {
+        write!(
+            json,
+            r#","scene":0,"scenes":[{{"nodes":[{}]}}],"nodes":[{}],"meshes":[{}],"accessors":[{}],"bufferViews":[{}],"buffers":[{{"byteLength":{}}}]"#,
+            scene_nodes.join(","),
+            nodes.join(","),
+            gltf_meshes.join(","),
+            buffer.accessors.join(","),
+            buffer.views.join(","),
+            buffer.bytes.len(),
+        )?;
+    }
+    if !gltf_materials.is_empty() {
+        write!(json, r#","materials":[{}]"#, gltf_materials.join(","))?;
+    }
+    if !images.is_empty() {
+        write!(json, r#","images":[{}],"textures":[{}]"#, images.join(","), textures.join(","))?;
+    }
+    json.push('}');
+    let mut json = json.into_bytes();
+    json.resize(json.len().next_multiple_of(4), b' ');
+
+    // The header and the JSON and binary chunks, each chunk with its length and type first.
+    // Without a buffer there is no binary chunk.
+    let binary_length = if buffer.bytes.is_empty() { 0 } else { 8 + buffer.bytes.len() };
+    let length = 12 + 8 + json.len() + binary_length;
+    let mut file = BufWriter::new(fs::File::create(path).with_context(|| format!("creating {}", path.display()))?);
+    for word in [0x4654_6c67, 2, length as u32, json.len() as u32, 0x4e4f_534a] {
+        file.write_all(&u32::to_le_bytes(word))?;
+    }
+    file.write_all(&json)?;
+    if !buffer.bytes.is_empty() {
+        for word in [buffer.bytes.len() as u32, 0x004e_4942] {
+            file.write_all(&u32::to_le_bytes(word))?;
+        }
+        file.write_all(&buffer.bytes)?;
+    }
+    Ok(())
+}
+
+pub fn write_model_glb(path: impl AsRef<Path>, model: &MatModel) -> anyhow::Result<()> {
+    write_glb(path, &model_meshes(model)?, &model.materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::shapes::circle_data;
+
+    fn scratch_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("graphics-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn glb_leaves_out_empty_meshes() {
+        let directory = scratch_directory("empty-meshes");
+        let (circle, empty) = (circle_data(8, 1.0), MeshData::default());
+        let meshes = [
+            ExportMesh { name: "empty", data: &empty, material: None },
+            ExportMesh { name: "circle", data: &circle, material: None },
+        ];
+        write_glb(directory.join("meshes.glb"), &meshes, &[]).unwrap();
+        let gltf::Gltf { document, blob } = gltf::Gltf::open(directory.join("meshes.glb")).unwrap();
+        assert_eq!(document.meshes().map(|mesh| mesh.name().unwrap().to_owned()).collect::<Vec<_>>(), ["circle"]);
+        assert!(document.views().all(|view| view.length() > 0));
+        assert_eq!(document.buffers().next().unwrap().length(), blob.unwrap().len());
+
+        write_glb(directory.join("nothing.glb"), &meshes[..1], &[]).unwrap();
+        let gltf::Gltf { document, blob } = gltf::Gltf::open(directory.join("nothing.glb")).unwrap();
+        assert_eq!((document.meshes().count(), document.buffers().count()), (0, 0));
+        assert!(blob.is_none());
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn textures_with_the_same_name_get_their_own_copies() {
+        let directory = scratch_directory("texture-names");
+        for (folder, contents) in [("a", "a"), ("b", "b"), ("out", "out")] {
+            fs::create_dir_all(directory.join(folder)).unwrap();
+            fs::write(directory.join(folder).join("color.png"), contents).unwrap();
+        }
+        let [a, b, out] = ["a", "b", "out"].map(|folder| directory.join(folder).join("color.png"));
+        let names = copy_files(&directory.join("out"), [a.as_path(), b.as_path(), out.as_path(), a.as_path()].into_iter()).unwrap();
+        assert_eq!([&a, &b, &out].map(|source| names[source.as_path()].as_str()), ["color_1.png", "color_2.png", "color.png"]);
+        for (name, contents) in [("color.png", "out"), ("color_1.png", "a"), ("color_2.png", "b")] {
+            assert_eq!(fs::read_to_string(directory.join("out").join(name)).unwrap(), contents);
+        }
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,9 +1,8 @@
-use glam::{vec3, Quat, Vec3};
-use graphics::{self, camera, model::{DrawModel, ModelVertex, Vertex, ModelInstance, ModelInstanceRaw}, texture::Texture, window::create_render_pipeline, App};
+use std::f32::consts::TAU;
+use glam::{uvec2, vec2, vec3, Quat, Vec3};
+use graphics::{self, builder::MeshBuilder, camera, colormap::Colormap, model::{ColorVertex, DrawModel, MaterialFactors, MaterialTextures, Mesh, ModelInstance, ModelInstanceRaw, ModelMaterial, Vertex}, shaders, shapes, texture::Texture, window::create_render_pipeline, App};
 use wgpu::{util::DeviceExt, Queue, RenderPass};
 
-const NUM_INSTANCES_PER_ROW: u32 = 10;
-
 fn main() {
     env_logger::init();
     let game = Game::new();
@@ -12,10 +11,10 @@ fn main() {
 
 struct GameState {
     render_pipeline: wgpu::RenderPipeline,
-    camera: camera::Camera,
-    camera_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
-    instances: Vec<ModelInstance>,
+    /// Drawn with the instance at the same index.
+    plots: Vec<Mesh>,
+    material: ModelMaterial,
     instance_buffer: wgpu::Buffer,
 }
 
@@ -25,13 +24,6 @@ struct Game {
 
 impl Game {
     fn new() -> Self {
-        let light_uniform = LightUniform {
-            position: vec3(2.0, 2.0, 2.0),
-            _padding: 0,
-            color: vec3(1.0, 1.0, 1.0),
-            _padding2: 0,
-        };
-
         Self {
             state: None,
         }
@@ -44,57 +36,47 @@ impl Game {
             panic!("GameState does not exist")
         }
     }
-
-    fn state_mut(&mut self) -> &mut GameState {
-        if let Some(state) = &mut self.state {
-            state
-        } else {
-            panic!("GameState does not exist")
-        }
-    }
-
-    fn update_camera_buffer(&mut self, queue: &Queue) {
-        queue.write_buffer(&self.state().camera_buffer, 0, bytemuck::cast_slice(&[self.state().camera.uniform]));
-    }
 }
 
 impl App for Game {
     fn update(
         &mut self,
-        queue: &Queue,
+        _queue: &Queue,
     ) {
         
     }
 
     fn window_event(
         &mut self,
-        event: &winit::event::WindowEvent,
-        queue: &Queue,
+        _event: &winit::event::WindowEvent,
+        _queue: &Queue,
     ) -> bool {
         false
     }
 
     fn device_event(
         &mut self,
-        event: &winit::event::DeviceEvent,
-        queue: &Queue,
+        _event: &winit::event::DeviceEvent,
+        _queue: &Queue,
     ) {
         
     }
 
-    fn render(
-        &mut self,
-        render_pass: &mut RenderPass,
+    fn render<'a>(
+        &'a mut self,
+        render_pass: &mut RenderPass<'a>,
     ) {
-        unsafe {
-            render_pass.set_vertex_buffer(1, std::mem::transmute(self.state().instance_buffer.slice(..)));
-
-            render_pass.set_pipeline(to_static(&self.state().render_pipeline));
-            // render_pass.draw_model_instanced(
-            //     to_static(&self.state().obj_model),
-            //     0..self.state().instances.len() as u32,
-            //     to_static_array(&[&self.state().camera_bind_group]),
-            // );
+        let state = self.state();
+        render_pass.set_vertex_buffer(1, state.instance_buffer.slice(..));
+
+        render_pass.set_pipeline(&state.render_pipeline);
+        for (index, plot) in state.plots.iter().enumerate() {
+            render_pass.draw_mesh_instanced(
+                plot,
+                Some(&state.material),
+                index as u32..index as u32 + 1,
+                &[&state.camera_bind_group],
+            );
         }
     }
     
@@ -105,46 +87,8 @@ impl App for Game {
         config: &wgpu::SurfaceConfiguration,
     ) {
 
-        let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 3,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-            label: Some("texture_bind_group_layout"),
-        });
-
         let mut camera = camera::Camera::new(
-            (0.0, 1.0, 2.0).into(),
+            (0.0, 4.0, 7.0).into(),
             (0.0, 0.0, 0.0).into(),
             Vec3::Y,
             config.width as f32 / config.height as f32,
@@ -188,48 +132,51 @@ impl App for Game {
             label: Some("camera_bind_group"),
         });
 
+        let material_bind_group_layout = ModelMaterial::bind_group_layout(device);
+        let textures = MaterialTextures::plain(device, queue, "Plot").unwrap();
+        let material = ModelMaterial::with_textures(device, "Plot", textures, MaterialFactors::default(), &material_bind_group_layout);
+
         let render_pipeline_layout = device.create_pipeline_layout(
             &wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
                 bind_group_layouts: &[
-                    &texture_bind_group_layout,
+                    &material_bind_group_layout,
                     &camera_bind_group_layout,
                 ],
                 push_constant_ranges: &[],
             }
         );
-        let render_pipeline = {
-            let shader = wgpu::ShaderModuleDescriptor {
-                label: Some("Normal Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("lit.wgsl").into()),
-            };
-            create_render_pipeline(
-                &device,
-                &render_pipeline_layout,
-                config.format,
-                Some(Texture::DEPTH_FORMAT),
-                &[ModelVertex::desc(), ModelInstanceRaw::desc()],
-                shader,
-            )
-        };
-
-        const SPACE_BETWEEN: f32 = 3.0;
-        let instances = (0..NUM_INSTANCES_PER_ROW).flat_map(|z| {
-            (0..NUM_INSTANCES_PER_ROW).map(move |x| {
-                let x = SPACE_BETWEEN * (x as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
-                let z = SPACE_BETWEEN * (z as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
-
-                let position = vec3(x, 0.0, z);
+        let render_pipeline = create_render_pipeline(
+            device,
+            &render_pipeline_layout,
+            config.format,
+            Some(Texture::DEPTH_FORMAT),
+            &[ColorVertex::desc(), ModelInstanceRaw::desc()],
+            shaders::plot(),
+        );
 
-                let rotation = if position == Vec3::ZERO {
-                    Quat::from_axis_angle(Vec3::Z, 0.0)
-                } else {
-                    Quat::from_axis_angle(position.normalize(), 45.0_f32.to_radians())
-                };
+        let ripple = |x: f32, y: f32| (x * x + y * y).sqrt().sin() * 0.3;
+        let ripple_gradient = |x: f32, y: f32| {
+            let radius = (x * x + y * y).sqrt().max(f32::EPSILON);
+            vec2(x, y) * radius.cos() * 0.3 / radius
+        };
+        let plot = shapes::function_plot_data(&ripple, Some(&ripple_gradient), -6.0..6.0, -6.0..6.0, uvec2(96, 96), Some(Colormap::Viridis));
 
-                ModelInstance::new(position, rotation)
-            })
-        }).collect::<Vec<_>>();
+        let torus = |u: f32, v: f32| {
+            let radius = 1.0 + 0.35 * v.cos();
+            vec3(radius * u.cos(), 0.35 * v.sin(), -radius * u.sin())
+        };
+        let mut torus_data = shapes::parametric_surface_data(&torus, None, 0.0..TAU, 0.0..TAU, uvec2(64, 24));
+        Colormap::Plasma.color_vertices(&mut torus_data, None, |position| position.x);
+
+        let plots = [("Ripple", plot), ("Torus", torus_data)]
+            .into_iter()
+            .map(|(name, data)| MeshBuilder::<ColorVertex>::from_data(name, data).build(device))
+            .collect::<Vec<_>>();
+        let instances = [
+            ModelInstance::new(vec3(0.0, -0.5, 0.0), Quat::IDENTITY),
+            ModelInstance::new(vec3(0.0, 1.0, 0.0), Quat::from_axis_angle(Vec3::X, 0.5)),
+        ];
 
         let instance_data = instances.iter().map(ModelInstance::to_raw).collect::<Vec<_>>();
         let instance_buffer = device.create_buffer_init(
@@ -242,30 +189,10 @@ impl App for Game {
 
         self.state = Some(GameState {
             render_pipeline,
-            camera,
-            camera_buffer,
             camera_bind_group,
-            instances,
+            plots,
+            material,
             instance_buffer,
         });
     }
 }
-
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct LightUniform {
-    position: Vec3,
-    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
-    _padding: u32,
-    color: Vec3,
-    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
-    _padding2: u32,
-}
-
-unsafe fn to_static<T>(src: &T) -> &'static T {
-    std::mem::transmute(src)
-}
-
-unsafe fn to_static_array<T>(src: &[&T]) -> &'static [&'static T] {
-    std::mem::transmute(src)
-}
\ No newline at end of file
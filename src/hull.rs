@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use glam::Vec3;
+use crate::{builder::MeshBuilder, mesh_data::MeshData, model::{Mesh, ModelVertex}, normals::NormalMode};
+
+/// How far outside a face, relative to the size of the point set, a point has to be to count.
+const HULL_EPSILON: f32 = 1e-6;
+
+/// How far outside a face a point can be and still count as on it.
+fn tolerance(points: &[Vec3]) -> f32 {
+    let scale = points.iter().fold(0.0f32, |scale, p| scale.max(p.abs().max_element()));
+    scale * HULL_EPSILON * 3.0
+}
+
+struct Face {
+    corners: [u32; 3],
+    normal: Vec3,
+    distance: f32,
+    /// Points in front of this face and of no face made before it.
+    outside: Vec<u32>,
+    alive: bool,
+}
+
+impl Face {
+    fn new(points: &[Vec3], corners: [u32; 3]) -> Self {
+        let [a, b, c] = corners.map(|i| points[i as usize]);
+        let normal = (b - a).cross(c - a).normalize_or_zero();
+        Self { corners, normal, distance: normal.dot(a), outside: vec![], alive: true }
+    }
+
+    fn height(&self, point: Vec3) -> f32 {
+        self.normal.dot(point) - self.distance
+    }
+
+    fn edges(&self) -> [(u32, u32); 3] {
+        let [a, b, c] = self.corners;
+        [(a, b), (b, c), (c, a)]
+    }
+}
+
+/// The smallest convex shape around a set of points, as counter-clockwise triangles facing out.
+#[derive(Clone, Debug, Default)]
+pub struct ConvexHull {
+    pub positions: Vec<Vec3>,
+    pub indices: Vec<u32>,
+}
+
+impl ConvexHull {
+    /// Builds the hull with quickhull. Returns `None` for fewer than four points or points that
+    /// all lie in one plane, which have no volume to wrap.
+    pub fn from_points(points: &[Vec3]) -> Option<Self> {
+        let epsilon = tolerance(points);
+        let farthest = |candidates: &mut dyn Iterator<Item = usize>, distance: &dyn Fn(Vec3) -> f32| {
+            candidates.max_by(|&a, &b| distance(points[a]).total_cmp(&distance(points[b])))
+        };
+
+        // The starting tetrahedron spans the extremes along an axis and the points farthest
+        // from its line and from its plane.
+        let extremes = (0..3).flat_map(|axis| {
+            let min = (0..points.len()).min_by(|&a, &b| points[a][axis].total_cmp(&points[b][axis]));
+            let max = (0..points.len()).max_by(|&a, &b| points[a][axis].total_cmp(&points[b][axis]));
+            [min, max]
+        });
+        let extremes = extremes.flatten().collect::<Vec<_>>();
+        let (a, b) = extremes
+            .iter()
+            .flat_map(|&a| extremes.iter().map(move |&b| (a, b)))
+            .max_by(|&(a, b), &(c, d)| points[a].distance_squared(points[b]).total_cmp(&points[c].distance_squared(points[d])))?;
+        let (pa, pb) = (points[a], points[b]);
+        let line = (pb - pa).try_normalize()?;
+        let c = farthest(&mut (0..points.len()), &|p| (p - pa).reject_from_normalized(line).length_squared())?;
+        let plane_normal = line.cross(points[c] - pa).try_normalize()?;
+        let d = farthest(&mut (0..points.len()), &|p| plane_normal.dot(p - pa).abs())?;
+        if plane_normal.dot(points[d] - pa).abs() <= epsilon {
+            return None;
+        }
+
+        let [a, b, c, d] = [a, b, c, d].map(|i| i as u32);
+        let center = [a, b, c, d].iter().map(|&i| points[i as usize]).sum::<Vec3>() / 4.0;
+        let mut faces = [[a, b, c], [a, b, d], [b, c, d], [c, a, d]]
+            .into_iter()
+            .map(|[a, b, c]| {
+                let face = Face::new(points, [a, b, c]);
+                if face.height(center) > 0.0 { Face::new(points, [a, c, b]) } else { face }
+            })
+            .collect::<Vec<_>>();
+        let mut edges = HashMap::new();
+        for (f, face) in faces.iter().enumerate() {
+            for edge in face.edges() {
+                edges.insert(edge, f);
+            }
+        }
+        let assign = |faces: &mut [Face], candidates: &[usize], point: u32| {
+            let position = points[point as usize];
+            if let Some(&f) = candidates.iter().find(|&&f| faces[f].height(position) > epsilon) {
+                faces[f].outside.push(point);
+            }
+        };
+        let all = (0..faces.len()).collect::<Vec<_>>();
+        for point in 0..points.len() as u32 {
+            if ![a, b, c, d].contains(&point) {
+                assign(&mut faces, &all, point);
+            }
+        }
+
+        let mut pending = (0..faces.len()).collect::<Vec<_>>();
+        while let Some(start) = pending.pop() {
+            if !faces[start].alive || faces[start].outside.is_empty() {
+                continue;
+            }
+            let eye = *faces[start]
+                .outside
+                .iter()
+                .max_by(|&&p, &&q| faces[start].height(points[p as usize]).total_cmp(&faces[start].height(points[q as usize])))
+                .unwrap();
+            let eye_position = points[eye as usize];
+
+            // Flood out from the face to every face the eye can see.
+            let mut visible = vec![start];
+            faces[start].alive = false;
+            let mut i = 0;
+            while i < visible.len() {
+                for (from, to) in faces[visible[i]].edges() {
+                    let neighbour = edges[&(to, from)];
+                    if faces[neighbour].alive && faces[neighbour].height(eye_position) > epsilon {
+                        faces[neighbour].alive = false;
+                        visible.push(neighbour);
+                    }
+                }
+                i += 1;
+            }
+
+            // The edges between seen and unseen faces make a ring around the eye to build on.
+            let horizon = visible
+                .iter()
+                .flat_map(|&f| faces[f].edges())
+                .filter(|&(from, to)| faces[edges[&(to, from)]].alive)
+                .collect::<Vec<_>>();
+            let orphans = visible.iter().flat_map(|&f| std::mem::take(&mut faces[f].outside)).collect::<Vec<_>>();
+            for &f in &visible {
+                for edge in faces[f].edges() {
+                    edges.remove(&edge);
+                }
+            }
+            let first = faces.len();
+            for (from, to) in horizon {
+                let face = Face::new(points, [from, to, eye]);
+                for edge in face.edges() {
+                    edges.insert(edge, faces.len());
+                }
+                faces.push(face);
+            }
+            let new_faces = (first..faces.len()).collect::<Vec<_>>();
+            for point in orphans.into_iter().filter(|&point| point != eye) {
+                assign(&mut faces, &new_faces, point);
+            }
+            pending.extend(new_faces);
+        }
+
+        // Only the points on the hull are kept.
+        let mut remap = HashMap::new();
+        let mut hull = Self::default();
+        for face in faces.iter().filter(|face| face.alive) {
+            for corner in face.corners {
+                let index = *remap.entry(corner).or_insert_with(|| {
+                    hull.positions.push(points[corner as usize]);
+                    hull.positions.len() as u32 - 1
+                });
+                hull.indices.push(index);
+            }
+        }
+        Some(hull)
+    }
+
+    pub fn from_data(data: &MeshData) -> Option<Self> {
+        Self::from_points(&data.positions)
+    }
+
+    /// Needs the data kept with [`MeshBuilder::retain_data`].
+    pub fn from_mesh(mesh: &Mesh) -> Option<Self> {
+        mesh.data.as_ref().and_then(Self::from_data)
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.indices
+            .chunks_exact(3)
+            .map(|t| {
+                let [a, b, c] = [t[0], t[1], t[2]].map(|i| self.positions[i as usize]);
+                a.dot(b.cross(c)) / 6.0
+            })
+            .sum()
+    }
+
+    /// Points on the hull count as inside, with the same tolerance the hull was built with.
+    pub fn contains(&self, point: Vec3) -> bool {
+        let epsilon = tolerance(&self.positions);
+        self.indices.chunks_exact(3).all(|t| {
+            let [a, b, c] = [t[0], t[1], t[2]].map(|i| self.positions[i as usize]);
+            (b - a).cross(c - a).normalize_or_zero().dot(point - a) <= epsilon
+        })
+    }
+
+    /// Flat shaded, since a hull has no smooth surface to follow.
+    pub fn to_data(&self) -> MeshData {
+        MeshBuilder::<ModelVertex>::new("Convex Hull", self.positions.clone())
+            .indices(self.indices.clone())
+            .normal_mode(NormalMode::Flat)
+            .build_data()
+    }
+
+    pub fn mesh(&self, material: usize, device: &wgpu::Device) -> Mesh {
+        MeshBuilder::<ModelVertex>::from_data("Convex Hull", self.to_data())
+            .material(material)
+            .build(device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use glam::vec3;
+    use super::*;
+
+    /// Points spread evenly over a sphere.
+    fn sphere_points(count: usize) -> Vec<Vec3> {
+        let golden_angle = std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+        (0..count)
+            .map(|i| {
+                let y = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+                let radius = (1.0 - y * y).sqrt();
+                let angle = golden_angle * i as f32;
+                vec3(angle.cos() * radius, y, angle.sin() * radius)
+            })
+            .collect()
+    }
+
+    fn assert_closed(hull: &ConvexHull) {
+        let mut edges = HashMap::new();
+        for t in hull.indices.chunks_exact(3) {
+            for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+                *edges.entry((a, b)).or_insert(0) += 1;
+            }
+        }
+        for (&(a, b), &count) in &edges {
+            assert_eq!(count, 1, "edge {a}-{b} is used {count} times");
+            assert_eq!(edges.get(&(b, a)), Some(&1), "edge {a}-{b} has no twin");
+        }
+    }
+
+    #[test]
+    fn cube_hull() {
+        let corners = (0..8).map(|i| vec3((i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2 & 1) as f32) * 2.0 - 1.0);
+        let inside = (0..50).map(|i| Vec3::splat(((i * 37 % 100) as f32 / 100.0 - 0.5) * 1.5));
+        let hull = ConvexHull::from_points(&corners.clone().chain(inside).collect::<Vec<_>>()).unwrap();
+
+        assert_eq!(hull.positions.len(), 8);
+        assert_closed(&hull);
+        assert!((hull.volume() - 8.0).abs() < 1e-5);
+        assert!(corners.into_iter().all(|corner| hull.contains(corner)));
+        assert!(hull.contains(Vec3::ZERO));
+        assert!(hull.contains(vec3(1.0, 0.25, -0.5)));
+        assert!(!hull.contains(vec3(1.001, 0.0, 0.0)));
+        assert!(!hull.contains(vec3(2.0, 2.0, 2.0)));
+    }
+
+    #[test]
+    fn sphere_hull_contains_its_points() {
+        let points = sphere_points(500).into_iter().map(|p| p * 3.0 + vec3(10.0, -5.0, 2.0)).collect::<Vec<_>>();
+        let hull = ConvexHull::from_points(&points).unwrap();
+
+        assert_eq!(hull.positions.len(), points.len());
+        assert_closed(&hull);
+        let sphere = 4.0 / 3.0 * std::f32::consts::PI * 27.0;
+        assert!(hull.volume() < sphere && hull.volume() > sphere * 0.98, "volume {}", hull.volume());
+        assert!(points.iter().all(|&point| hull.contains(point)));
+        assert!(!hull.contains(vec3(13.1, -5.0, 2.0)));
+    }
+
+    #[test]
+    fn flat_points_have_no_hull() {
+        let points = [vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0), vec3(1.0, 0.0, 1.0)];
+        assert!(ConvexHull::from_points(&points).is_none());
+    }
+}
@@ -7,6 +7,30 @@ pub mod camera;
 pub mod model;
 pub mod resources;
 pub mod shapes;
+pub mod normals;
+pub mod tangents;
+pub mod bounds;
+pub mod builder;
+pub mod mesh_data;
+pub mod triangulate;
+pub mod terrain;
+pub mod colormap;
+pub mod voxel;
+pub mod subdivision;
+pub mod simplify;
+pub mod optimize;
+pub mod validation;
+pub mod export;
+pub mod csg;
+pub mod hull;
+pub mod uv;
+pub mod skin;
+pub mod morph;
+pub mod light;
+pub mod animation;
+pub mod spline;
+pub mod lines;
+pub mod shaders;
 
 pub trait App {
     fn update(
@@ -26,9 +50,9 @@ pub trait App {
         queue: &Queue,
     );
 
-    fn render(
-        &mut self,
-        render_pass: &mut RenderPass,
+    fn render<'a>(
+        &'a mut self,
+        render_pass: &mut RenderPass<'a>,
     );
 
     fn setup(
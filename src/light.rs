@@ -0,0 +1,29 @@
+use glam::Vec3;
+
+/// The point light of the lit shaders, laid out for a uniform buffer.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightUniform {
+    pub position: Vec3,
+    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
+    _padding: u32,
+    pub color: Vec3,
+    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
+    _padding2: u32,
+    /// The light reaching every surface from all around, standing in for the light bouncing
+    /// around the scene.
+    pub ambient: Vec3,
+    _padding3: u32,
+}
+
+impl LightUniform {
+    /// The ambient light starts at a tenth of the color.
+    pub fn new(position: Vec3, color: Vec3) -> Self {
+        Self { position, _padding: 0, color, _padding2: 0, ambient: color * 0.1, _padding3: 0 }
+    }
+
+    pub fn ambient(mut self, ambient: Vec3) -> Self {
+        self.ambient = ambient;
+        self
+    }
+}
@@ -0,0 +1,130 @@
+use glam::{Vec3, Vec4};
+use wgpu::util::DeviceExt;
+use crate::model::Vertex;
+use crate::spline::Spline;
+
+/// A line end point for `lines.wgsl`, at the same shader locations as a [`crate::model::ColorVertex`].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    pub position: Vec3,
+    // The color is 16 byte aligned, so we need to use a padding field here
+    _padding: u32,
+    /// Linear RGBA.
+    pub color: Vec4,
+}
+
+impl LineVertex {
+    pub fn new(position: Vec3, color: Vec4) -> Self {
+        Self { position, _padding: 0, color }
+    }
+}
+
+impl Vertex for LineVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+/// Debug lines collected on the CPU, as a line list to draw with a pipeline from
+/// [`crate::window::create_line_pipeline`].
+#[derive(Clone, Debug, Default)]
+pub struct DebugLines {
+    pub vertices: Vec<LineVertex>,
+}
+
+impl DebugLines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    pub fn line(&mut self, start: Vec3, end: Vec3, color: Vec4) {
+        self.vertices.push(LineVertex::new(start, color));
+        self.vertices.push(LineVertex::new(end, color));
+    }
+
+    /// Connects the points in order.
+    pub fn polyline(&mut self, points: &[Vec3], color: Vec4) {
+        for pair in points.windows(2) {
+            self.line(pair[0], pair[1], color);
+        }
+    }
+
+    /// The curve as `segments` straight pieces of equal length.
+    pub fn spline(&mut self, spline: &Spline, segments: usize, color: Vec4) {
+        self.polyline(&spline.points(segments + 1), color);
+    }
+
+    /// The rotation minimizing frames at `count` points along the curve, with the tangent in
+    /// red, the normal in green and the binormal in blue.
+    pub fn frames(&mut self, spline: &Spline, count: usize, size: f32) {
+        for frame in spline.frames(count) {
+            self.line(frame.position, frame.position + frame.tangent * size, Vec4::new(1.0, 0.0, 0.0, 1.0));
+            self.line(frame.position, frame.position + frame.normal * size, Vec4::new(0.0, 1.0, 0.0, 1.0));
+            self.line(frame.position, frame.position + frame.binormal * size, Vec4::new(0.0, 0.0, 1.0, 1.0));
+        }
+    }
+}
+
+/// Debug lines on the GPU. The buffer grows when more lines are written than fit.
+pub struct LineBuffer {
+    pub buffer: wgpu::Buffer,
+    capacity: usize,
+    num_vertices: u32,
+}
+
+impl LineBuffer {
+    pub fn new(device: &wgpu::Device, lines: &DebugLines) -> Self {
+        let (buffer, capacity) = Self::create_buffer(device, &lines.vertices);
+        Self { buffer, capacity, num_vertices: lines.vertices.len() as u32 }
+    }
+
+    fn create_buffer(device: &wgpu::Device, vertices: &[LineVertex]) -> (wgpu::Buffer, usize) {
+        // Buffers can not be empty, so a buffer for no lines still holds one vertex.
+        let zero = [LineVertex::new(Vec3::ZERO, Vec4::ZERO)];
+        let contents = if vertices.is_empty() { &zero } else { vertices };
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Line Vertex Buffer"),
+            contents: bytemuck::cast_slice(contents),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        (buffer, contents.len())
+    }
+
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, lines: &DebugLines) {
+        if lines.vertices.len() > self.capacity {
+            (self.buffer, self.capacity) = Self::create_buffer(device, &lines.vertices);
+        } else {
+            queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&lines.vertices));
+        }
+        self.num_vertices = lines.vertices.len() as u32;
+    }
+
+    /// Draws with the line pipeline and camera bind group already set.
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.num_vertices > 0 {
+            render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+            render_pass.draw(0..self.num_vertices, 0..1);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+// The declarations every lit shader shares: the material, the camera, the light and the
+// instance. Each shader adds its own vertex and fragment stages. See shaders.rs.
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+@group(0) @binding(2)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(3)
+var s_normal: sampler;
+@group(0) @binding(4)
+var t_metallic_roughness: texture_2d<f32>;
+@group(0) @binding(5)
+var s_metallic_roughness: sampler;
+@group(0) @binding(6)
+var t_occlusion: texture_2d<f32>;
+@group(0) @binding(7)
+var s_occlusion: sampler;
+@group(0) @binding(8)
+var t_emissive: texture_2d<f32>;
+@group(0) @binding(9)
+var s_emissive: sampler;
+
+struct MaterialFactors {
+    base_color: vec4<f32>,
+    emissive: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    occlusion_strength: f32,
+    normal_scale: f32,
+    alpha_cutoff: f32,
+}
+@group(0) @binding(10)
+var<uniform> material: MaterialFactors;
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+// The ambient light stands in for the light bouncing around the scene.
+struct Light {
+    position: vec3<f32>,
+    color: vec3<f32>,
+    ambient: vec3<f32>,
+}
+@group(2) @binding(0)
+var<uniform> light: Light;
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+
+    @location(9) normal_matrix_0: vec3<f32>,
+    @location(10) normal_matrix_1: vec3<f32>,
+    @location(11) normal_matrix_2: vec3<f32>,
+};
+
+fn instance_model_matrix(instance: InstanceInput) -> mat4x4<f32> {
+    return mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+}
+
+fn instance_normal_matrix(instance: InstanceInput) -> mat3x3<f32> {
+    return mat3x3<f32>(
+        instance.normal_matrix_0,
+        instance.normal_matrix_1,
+        instance.normal_matrix_2,
+    );
+}
@@ -0,0 +1,44 @@
+// The vertex shader for meshes with morph targets, completed by lit_common.wgsl and
+// blinn_phong.wgsl. It reads the ModelVertex part of a vertex, so it draws SkinnedVertex meshes
+// too, without the joints.
+
+struct MorphInfo {
+    num_vertices: u32,
+    num_targets: u32,
+}
+@group(3) @binding(0)
+var<uniform> morph: MorphInfo;
+// A position, normal and tangent offset per vertex, one target after another.
+@group(3) @binding(1)
+var<storage, read> morph_deltas: array<vec4<f32>>;
+@group(3) @binding(2)
+var<storage, read> morph_weights: array<f32>;
+
+struct VertexInput {
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) texture_coordinates: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    // The weighted offsets of every target are added before the instance transform.
+    var position = model.position;
+    var normal = model.normal;
+    var tangent = model.tangent.xyz;
+    for (var i = 0u; i < morph.num_targets; i += 1u) {
+        let weight = morph_weights[i];
+        if weight != 0.0 {
+            let delta = (i * morph.num_vertices + model.vertex_index) * 3u;
+            position += morph_deltas[delta].xyz * weight;
+            normal += morph_deltas[delta + 1u].xyz * weight;
+            tangent += morph_deltas[delta + 2u].xyz * weight;
+        }
+    }
+    return lit_vertex(position, model.texture_coordinates, normal, vec4<f32>(tangent, model.tangent.w), instance);
+}
@@ -0,0 +1,41 @@
+// The vertex shader for SkinnedVertex meshes, completed by lit_common.wgsl and
+// blinn_phong.wgsl.
+
+// Each joint's current transform times its inverse bind matrix.
+@group(3) @binding(0)
+var<storage, read> joints: array<mat4x4<f32>>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) texture_coordinates: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec4<f32>,
+    @location(12) joints: vec4<u32>,
+    @location(13) weights: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    // Vertices without weights are not skinned and stay where they are.
+    var skin_matrix = mat4x4<f32>(
+        vec4<f32>(1.0, 0.0, 0.0, 0.0),
+        vec4<f32>(0.0, 1.0, 0.0, 0.0),
+        vec4<f32>(0.0, 0.0, 1.0, 0.0),
+        vec4<f32>(0.0, 0.0, 0.0, 1.0),
+    );
+    if dot(model.weights, vec4<f32>(1.0)) > 0.0 {
+        skin_matrix = joints[model.joints.x] * model.weights.x
+            + joints[model.joints.y] * model.weights.y
+            + joints[model.joints.z] * model.weights.z
+            + joints[model.joints.w] * model.weights.w;
+    }
+    // Assumes the joints scale evenly, like the instance normal matrix does.
+    let skin_normal_matrix = mat3x3<f32>(skin_matrix[0].xyz, skin_matrix[1].xyz, skin_matrix[2].xyz);
+
+    let position = (skin_matrix * vec4<f32>(model.position, 1.0)).xyz;
+    let tangent = vec4<f32>(skin_normal_matrix * model.tangent.xyz, model.tangent.w);
+    return lit_vertex(position, model.texture_coordinates, skin_normal_matrix * model.normal, tangent, instance);
+}
@@ -0,0 +1,174 @@
+use glam::{UVec4, Vec2, Vec3, Vec4};
+use crate::{bounds::{Aabb, BoundingSphere, Obb}, model::{FromAttributes, VertexAttributes}, morph::MorphTarget, tangents};
+
+/// A CPU copy of an indexed triangle mesh, with one entry per vertex in every attribute.
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub positions: Vec<Vec3>,
+    pub tex_coords: Vec<Vec2>,
+    pub normals: Vec<Vec3>,
+    pub tangents: Vec<Vec4>,
+    /// Linear RGBA, empty when the mesh has no vertex colors.
+    pub colors: Vec<Vec4>,
+    /// The four joints moving each vertex, empty when the mesh is not skinned.
+    pub joints: Vec<UVec4>,
+    /// How much each of the [`MeshData::joints`] moves the vertex, summing to one.
+    pub weights: Vec<Vec4>,
+    /// Blend shapes with an offset for every vertex.
+    pub morph_targets: Vec<MorphTarget>,
+    pub indices: Vec<u32>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Triangle {
+    pub indices: [u32; 3],
+    pub positions: [Vec3; 3],
+}
+
+impl Triangle {
+    /// Twice the area in the direction of the face normal.
+    pub fn cross(&self) -> Vec3 {
+        let [a, b, c] = self.positions;
+        (b - a).cross(c - a)
+    }
+
+    /// Zero for degenerate triangles.
+    pub fn normal(&self) -> Vec3 {
+        self.cross().normalize_or_zero()
+    }
+
+    pub fn area(&self) -> f32 {
+        self.cross().length() * 0.5
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        let [a, b, c] = self.positions;
+        (a + b + c) / 3.0
+    }
+
+    /// Returns the distance along `direction` and the barycentric coordinates of the hit,
+    /// counting hits on both sides of the triangle.
+    pub fn intersect_ray(&self, origin: Vec3, direction: Vec3) -> Option<(f32, Vec3)> {
+        let [a, b, c] = self.positions;
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+        let inverse = 1.0 / determinant;
+        let t_vec = origin - a;
+        let u = t_vec.dot(p) * inverse;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = t_vec.cross(edge1);
+        let v = direction.dot(q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let distance = edge2.dot(q) * inverse;
+        (distance >= 0.0).then_some((distance, Vec3::new(1.0 - u - v, u, v)))
+    }
+}
+
+impl MeshData {
+    pub fn num_vertices(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn num_triangles(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn triangle(&self, index: usize) -> Triangle {
+        let indices = [self.indices[index * 3], self.indices[index * 3 + 1], self.indices[index * 3 + 2]];
+        Triangle { indices, positions: indices.map(|i| self.positions[i as usize]) }
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = Triangle> + '_ {
+        (0..self.num_triangles()).map(|i| self.triangle(i))
+    }
+
+    pub fn aabb(&self) -> Option<Aabb> {
+        Aabb::from_points(&self.positions)
+    }
+
+    pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        BoundingSphere::from_points(&self.positions)
+    }
+
+    /// The volume enclosed by a closed mesh, negative if its triangles face inward.
+    pub fn volume(&self) -> f32 {
+        self.triangles()
+            .map(|t| {
+                let [a, b, c] = t.positions;
+                a.dot(b.cross(c))
+            })
+            .sum::<f32>()
+            / 6.0
+    }
+
+    pub fn obb(&self) -> Option<Obb> {
+        Obb::from_points(&self.positions)
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        self.triangles().map(|t| t.area()).sum()
+    }
+
+    /// Returns the index of the closest triangle hit by the ray, the distance along `direction`
+    /// and the barycentric coordinates of the hit.
+    pub fn raycast(&self, origin: Vec3, direction: Vec3) -> Option<(usize, f32, Vec3)> {
+        self.triangles()
+            .enumerate()
+            .filter_map(|(i, t)| t.intersect_ray(origin, direction).map(|(distance, barycentric)| (i, distance, barycentric)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Rebuilds every vertex attribute and morph target so vertex `i` is a copy of vertex
+    /// `sources[i]`, leaving the indices to the caller. Empty attributes stay empty.
+    pub fn remap_vertices(&mut self, sources: &[u32]) {
+        fn pick<T: Copy>(values: &[T], sources: &[u32]) -> Vec<T> {
+            if values.is_empty() {
+                vec![]
+            } else {
+                sources.iter().map(|&i| values[i as usize]).collect()
+            }
+        }
+        self.positions = pick(&self.positions, sources);
+        self.tex_coords = pick(&self.tex_coords, sources);
+        self.normals = pick(&self.normals, sources);
+        self.tangents = pick(&self.tangents, sources);
+        self.colors = pick(&self.colors, sources);
+        self.joints = pick(&self.joints, sources);
+        self.weights = pick(&self.weights, sources);
+        self.morph_targets = self.morph_targets.iter().map(|target| target.remap(sources)).collect();
+    }
+
+    /// Replaces the tangents with fresh ones for the current UVs, splitting vertices where
+    /// the tangent frames differ.
+    pub fn compute_tangents(&mut self) {
+        let generated = tangents::generate_tangents(&self.positions, &self.normals, &self.tex_coords, &self.indices);
+        self.remap_vertices(&generated.remap);
+        self.indices = generated.indices;
+        self.tangents = generated.tangents;
+    }
+
+    pub fn vertex(&self, i: usize) -> VertexAttributes {
+        VertexAttributes {
+            position: self.positions[i],
+            tex_coords: self.tex_coords[i],
+            normal: self.normals[i],
+            tangent: self.tangents[i],
+            color: self.colors.get(i).copied().unwrap_or(Vec4::ONE),
+            joints: self.joints.get(i).copied().unwrap_or(UVec4::ZERO),
+            weights: self.weights.get(i).copied().unwrap_or(Vec4::ZERO),
+        }
+    }
+
+    pub fn vertices<V: FromAttributes>(&self) -> Vec<V> {
+        (0..self.num_vertices()).map(|i| V::from_attributes(self.vertex(i))).collect()
+    }
+}
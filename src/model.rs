@@ -1,20 +1,46 @@
 use std::ops::Range;
-use glam::{Mat3, Mat4, Quat, Vec2, Vec3};
+use wgpu::util::DeviceExt;
+use glam::{Mat3, Mat4, Quat, UVec4, Vec2, Vec3, Vec4};
 
-use crate::texture;
+use crate::{bounds::Aabb, mesh_data::MeshData, texture};
 
 pub trait Vertex {
     fn desc() -> wgpu::VertexBufferLayout<'static>;
 }
 
+/// Everything a vertex of a [`MeshData`] can have.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VertexAttributes {
+    pub position: Vec3,
+    pub tex_coords: Vec2,
+    pub normal: Vec3,
+    pub tangent: Vec4,
+    /// White for meshes without vertex colors.
+    pub color: Vec4,
+    pub joints: UVec4,
+    /// Zero for meshes without skinning.
+    pub weights: Vec4,
+}
+
+pub trait FromAttributes {
+    fn from_attributes(attributes: VertexAttributes) -> Self;
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ModelVertex {
     pub position: Vec3,
     pub tex_coords: Vec2,
     pub normal: Vec3,
-    pub tangent: Vec3,
-    pub bitangent: Vec3,
+    /// The bitangent is `cross(normal, tangent.xyz) * tangent.w`.
+    pub tangent: Vec4,
+}
+
+impl FromAttributes for ModelVertex {
+    fn from_attributes(attributes: VertexAttributes) -> Self {
+        let VertexAttributes { position, tex_coords, normal, tangent, .. } = attributes;
+        Self { position, tex_coords, normal, tangent }
+    }
 }
 
 impl Vertex for ModelVertex {
@@ -42,13 +68,64 @@ impl Vertex for ModelVertex {
                 wgpu::VertexAttribute {
                     offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
                     shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+/// A [`ModelVertex`] with a vertex color, for meshes shaded without textures.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ColorVertex {
+    pub position: Vec3,
+    pub tex_coords: Vec2,
+    pub normal: Vec3,
+    pub tangent: Vec4,
+    /// Linear RGBA.
+    pub color: Vec4,
+}
+
+impl FromAttributes for ColorVertex {
+    fn from_attributes(attributes: VertexAttributes) -> Self {
+        let VertexAttributes { position, tex_coords, normal, tangent, color, .. } = attributes;
+        Self { position, tex_coords, normal, tangent, color }
+    }
+}
+
+impl Vertex for ColorVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<ColorVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
                     format: wgpu::VertexFormat::Float32x3,
                 },
                 wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 11]>() as wgpu::BufferAddress,
-                    shader_location: 4,
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 2,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
@@ -64,97 +141,232 @@ pub struct NoMatModel {
 }
 
 pub trait HasMeshes {
-    fn meshes<'a>(
-        &'a self,
-    ) -> &'a Vec<Mesh>;
+    fn meshes(
+        &self,
+    ) -> &Vec<Mesh>;
 }
 pub trait HasMaterials {
-    fn materials<'a>(
-        &'a self,
-    ) -> &'a Vec<impl Material>;
+    fn materials(
+        &self,
+    ) -> &Vec<impl Material>;
 }
 
 impl HasMeshes for MatModel {
-    fn meshes<'a>(
-        &'a self,
-    ) -> &'a Vec<Mesh> {
+    fn meshes(
+        &self,
+    ) -> &Vec<Mesh> {
         &self.meshes
     }
 }
 
 impl HasMeshes for NoMatModel {
-    fn meshes<'a>(
-        &'a self,
-    ) -> &'a Vec<Mesh> {
+    fn meshes(
+        &self,
+    ) -> &Vec<Mesh> {
         &self.meshes
     }
 }
 
 impl HasMaterials for MatModel {
-    fn materials<'a>(
-        &'a self,
-    ) -> &'a Vec<impl Material> {
+    fn materials(
+        &self,
+    ) -> &Vec<impl Material> {
         &self.materials
     }
 }
 
+/// The scalar factors of a metallic-roughness material, multiplied with its textures and laid
+/// out for a uniform buffer.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialFactors {
+    /// Linear RGBA.
+    pub base_color: Vec4,
+    /// Linear RGB light given off regardless of the lighting.
+    pub emissive: Vec3,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// How much of the occlusion texture applies, from none at 0 to all of it at 1.
+    pub occlusion_strength: f32,
+    /// Scales the X and Y of the normal map.
+    pub normal_scale: f32,
+    /// Fragments with a lower alpha are discarded, where zero keeps them all.
+    pub alpha_cutoff: f32,
+}
+
+impl Default for MaterialFactors {
+    /// A white dielectric about as shiny as the Blinn-Phong shaders.
+    fn default() -> Self {
+        Self {
+            base_color: Vec4::ONE,
+            emissive: Vec3::ZERO,
+            metallic: 0.0,
+            roughness: 0.5,
+            occlusion_strength: 1.0,
+            normal_scale: 1.0,
+            alpha_cutoff: 0.0,
+        }
+    }
+}
+
+/// The textures of a [`ModelMaterial`]. The channels follow glTF: roughness in the green and
+/// metalness in the blue channel of `metallic_roughness`, and occlusion in the red channel of
+/// `occlusion`. Both are linear like the normal map, the others sRGB.
+pub struct MaterialTextures {
+    pub diffuse: texture::Texture,
+    pub normal: texture::Texture,
+    pub metallic_roughness: texture::Texture,
+    pub occlusion: texture::Texture,
+    pub emissive: texture::Texture,
+}
+
+impl MaterialTextures {
+    /// Textures that leave the factors as they are: white, with a flat normal map.
+    pub fn plain(device: &wgpu::Device, queue: &wgpu::Queue, name: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            diffuse: texture::Texture::from_color(device, queue, [255; 4], name, false)?,
+            normal: texture::Texture::from_color(device, queue, [128, 128, 255, 255], name, true)?,
+            metallic_roughness: texture::Texture::from_color(device, queue, [255; 4], name, true)?,
+            occlusion: texture::Texture::from_color(device, queue, [255; 4], name, true)?,
+            emissive: texture::Texture::from_color(device, queue, [255; 4], name, false)?,
+        })
+    }
+}
+
+/// A metallic-roughness material. The Blinn-Phong shaders only use the diffuse and normal
+/// textures, `pbr.wgsl` uses everything.
 pub struct ModelMaterial {
     pub name: String,
+    /// The base color.
     pub diffuse_texture: texture::Texture,
     pub normal_texture: texture::Texture,
+    pub metallic_roughness_texture: texture::Texture,
+    pub occlusion_texture: texture::Texture,
+    pub emissive_texture: texture::Texture,
+    pub factors: MaterialFactors,
+    pub factor_buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
 }
 
 impl ModelMaterial {
+    /// A material with just a diffuse and a normal texture and the default factors.
     pub fn new(
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
         name: &str,
         diffuse_texture: texture::Texture,
         normal_texture: texture::Texture,
         layout: &wgpu::BindGroupLayout,
+    ) -> anyhow::Result<Self> {
+        let textures = MaterialTextures {
+            diffuse: diffuse_texture,
+            normal: normal_texture,
+            ..MaterialTextures::plain(device, queue, name)?
+        };
+        Ok(Self::with_textures(device, name, textures, MaterialFactors::default(), layout))
+    }
+
+    pub fn with_textures(
+        device: &wgpu::Device,
+        name: &str,
+        textures: MaterialTextures,
+        factors: MaterialFactors,
+        layout: &wgpu::BindGroupLayout,
     ) -> Self {
+        let factor_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material Factor Buffer"),
+            contents: bytemuck::cast_slice(&[factors]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let MaterialTextures { diffuse, normal, metallic_roughness, occlusion, emissive } = textures;
+        let mut entries = vec![];
+        for (i, texture) in [&diffuse, &normal, &metallic_roughness, &occlusion, &emissive].into_iter().enumerate() {
+            entries.push(wgpu::BindGroupEntry {
+                binding: 2 * i as u32,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: 2 * i as u32 + 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            });
+        }
+        entries.push(wgpu::BindGroupEntry {
+            binding: 10,
+            resource: factor_buffer.as_entire_binding(),
+        });
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::TextureView(&normal_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 3,
-                    resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
-                },
-            ],
+            entries: &entries,
             label: Some(name),
         });
 
         Self {
             name: String::from(name),
-            diffuse_texture,
-            normal_texture,
+            diffuse_texture: diffuse,
+            normal_texture: normal,
+            metallic_roughness_texture: metallic_roughness,
+            occlusion_texture: occlusion,
+            emissive_texture: emissive,
+            factors,
+            factor_buffer,
             bind_group,
         }
     }
+
+    /// The diffuse, normal, metallic-roughness, occlusion and emissive texture with their
+    /// samplers at bindings 0 to 9, and the factors at binding 10.
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let mut entries = vec![];
+        for i in 0..5 {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 2 * i,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            });
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 2 * i + 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            });
+        }
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: 10,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        });
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &entries,
+            label: Some("texture_bind_group_layout"),
+        })
+    }
+
+    pub fn set_factors(&mut self, queue: &wgpu::Queue, factors: MaterialFactors) {
+        self.factors = factors;
+        queue.write_buffer(&self.factor_buffer, 0, bytemuck::cast_slice(&[factors]));
+    }
 }
 
 pub trait Material {
-    fn bind_group<'a>(
-        &'a self,
-    ) -> &'a wgpu::BindGroup;
+    fn bind_group(
+        &self,
+    ) -> &wgpu::BindGroup;
 }
 
 impl Material for ModelMaterial {
-    fn bind_group<'a>(
-        &'a self,
-    ) -> &'a wgpu::BindGroup {
+    fn bind_group(
+        &self,
+    ) -> &wgpu::BindGroup {
         &self.bind_group
     }
 }
@@ -163,8 +375,45 @@ pub struct Mesh {
     pub name: String,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
+    pub index_format: wgpu::IndexFormat,
     pub num_elements: u32,
     pub material: usize,
+    pub bounds: Option<Aabb>,
+    /// Only kept when requested with [`crate::builder::MeshBuilder::retain_data`].
+    pub data: Option<MeshData>,
+}
+
+impl Mesh {
+    /// Uploads vertices of any layout, with 16 bit indices when they are enough.
+    pub fn from_vertices<V: bytemuck::Pod>(name: &str, vertices: &[V], indices: &[u32], material: usize, device: &wgpu::Device) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Vertex Buffer", name)),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let (index_format, index_data) = if vertices.len() <= u16::MAX as usize + 1 {
+            let indices = indices.iter().map(|&i| i as u16).collect::<Vec<_>>();
+            (wgpu::IndexFormat::Uint16, bytemuck::cast_slice(&indices).to_vec())
+        } else {
+            (wgpu::IndexFormat::Uint32, bytemuck::cast_slice(indices).to_vec())
+        };
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Index Buffer", name)),
+            contents: &index_data,
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            name: name.to_owned(),
+            vertex_buffer,
+            index_buffer,
+            index_format,
+            num_elements: indices.len() as u32,
+            material,
+            bounds: None,
+            data: None,
+        }
+    }
 }
 
 pub trait DrawModel<'a> {
@@ -228,16 +477,15 @@ where
         bind_groups: &[&'a wgpu::BindGroup],
     ) {
         self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-        self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        let mut index = if let Some(material) = material {
+        self.set_index_buffer(mesh.index_buffer.slice(..), mesh.index_format);
+        let offset = if let Some(material) = material {
             self.set_bind_group(0, material.bind_group(), &[]);
             1
         } else {
             0
         };
-        for bind_group in bind_groups {
-            self.set_bind_group(index, &bind_group, &[]);
-            index += 1;
+        for (index, bind_group) in bind_groups.iter().enumerate() {
+            self.set_bind_group(offset + index as u32, bind_group, &[]);
         }
         self.draw_indexed(0..mesh.num_elements, 0, instances);
     }
@@ -285,20 +533,29 @@ where
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct ModelInstance {
-    position: Vec3,
-    rotation: Quat,
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
 }
 
 impl ModelInstance {
     pub fn new(position: Vec3, rotation: Quat) -> Self {
-        Self { position, rotation }
+        Self { position, rotation, scale: Vec3::ONE }
+    }
+
+    pub fn with_scale(mut self, scale: Vec3) -> Self {
+        self.scale = scale;
+        self
     }
 
     pub fn to_raw(&self) -> ModelInstanceRaw {
         ModelInstanceRaw {
-            model: Mat4::from_translation(self.position) * Mat4::from_quat(self.rotation),
-            normal: Mat3::from_quat(self.rotation),
+            model: Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.position),
+            // The inverse transpose of the rotation and scale, so normals stay at right angles
+            // to stretched surfaces.
+            normal: Mat3::from_quat(self.rotation) * Mat3::from_diagonal(self.scale.recip()),
             _padding: [0; 3],
         }
     }
@@ -0,0 +1,161 @@
+use glam::{Vec3, Vec4};
+use wgpu::util::DeviceExt;
+use crate::mesh_data::MeshData;
+
+/// One blend shape of a mesh, as offsets added to every vertex. Meshes whose shapes only move
+/// the positions leave the normal and tangent offsets empty.
+#[derive(Clone, Debug, Default)]
+pub struct MorphTarget {
+    pub name: String,
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub tangents: Vec<Vec3>,
+    /// The weight the shape starts out with.
+    pub weight: f32,
+}
+
+impl MorphTarget {
+    /// The offsets of the `sources` vertices, for meshes whose vertices were split or reordered.
+    pub fn remap(&self, sources: &[u32]) -> Self {
+        let pick = |deltas: &[Vec3]| {
+            if deltas.is_empty() {
+                vec![]
+            } else {
+                sources.iter().map(|&i| deltas[i as usize]).collect()
+            }
+        };
+        Self {
+            name: self.name.clone(),
+            positions: pick(&self.positions),
+            normals: pick(&self.normals),
+            tangents: pick(&self.tangents),
+            weight: self.weight,
+        }
+    }
+
+    /// The offsets of one vertex, zero where the target has none.
+    pub fn delta(&self, vertex: usize) -> [Vec3; 3] {
+        [&self.positions, &self.normals, &self.tangents].map(|deltas| deltas.get(vertex).copied().unwrap_or(Vec3::ZERO))
+    }
+}
+
+/// Blends the targets into a copy of the mesh on the CPU with the given weights, for picking
+/// and bounds of the deformed shape.
+pub fn apply_morph_targets(data: &MeshData, weights: &[f32]) -> MeshData {
+    let mut result = data.clone();
+    for (target, &weight) in data.morph_targets.iter().zip(weights).filter(|(_, &weight)| weight != 0.0) {
+        for i in 0..result.num_vertices() {
+            let [position, normal, tangent] = target.delta(i);
+            result.positions[i] += position * weight;
+            result.normals[i] += normal * weight;
+            result.tangents[i] += (tangent * weight).extend(0.0);
+        }
+    }
+    for normal in &mut result.normals {
+        *normal = normal.normalize_or_zero();
+    }
+    for tangent in &mut result.tangents {
+        *tangent = tangent.truncate().normalize_or_zero().extend(tangent.w);
+    }
+    result
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct MorphInfo {
+    num_vertices: u32,
+    num_targets: u32,
+    _padding: [u32; 2],
+}
+
+/// The morph targets of one mesh on the GPU with their current weights, bound for
+/// [`crate::shaders::LIT_MORPH`]. The offsets are stored target by target, with a position,
+/// normal and tangent offset for each vertex.
+pub struct MorphBuffer {
+    pub deltas: wgpu::Buffer,
+    pub weights: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    pub num_targets: usize,
+}
+
+impl MorphBuffer {
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                entry(0, wgpu::BufferBindingType::Uniform),
+                entry(1, wgpu::BufferBindingType::Storage { read_only: true }),
+                entry(2, wgpu::BufferBindingType::Storage { read_only: true }),
+            ],
+            label: Some("morph_bind_group_layout"),
+        })
+    }
+
+    /// Starts out with the weights of the targets.
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, data: &MeshData) -> Self {
+        let targets = &data.morph_targets;
+        let mut deltas = targets
+            .iter()
+            .flat_map(|target| (0..data.num_vertices()).flat_map(|i| target.delta(i).map(|delta| delta.extend(0.0))))
+            .collect::<Vec<_>>();
+        let mut weights = targets.iter().map(|target| target.weight).collect::<Vec<_>>();
+        // Bindings can not be empty, so meshes without targets still get a zero of each.
+        if deltas.is_empty() {
+            deltas.push(Vec4::ZERO);
+        }
+        if weights.is_empty() {
+            weights.push(0.0);
+        }
+
+        let info = MorphInfo { num_vertices: data.num_vertices() as u32, num_targets: targets.len() as u32, _padding: [0; 2] };
+        let info = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Morph Info Buffer"),
+            contents: bytemuck::cast_slice(&[info]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let deltas = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Morph Delta Buffer"),
+            contents: bytemuck::cast_slice(&deltas),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let weights = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Morph Weight Buffer"),
+            contents: bytemuck::cast_slice(&weights),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: info.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: deltas.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: weights.as_entire_binding(),
+                },
+            ],
+            label: Some("morph_bind_group"),
+        });
+        Self { deltas, weights, bind_group, num_targets: targets.len() }
+    }
+
+    /// One weight per target, usually once a frame. Extra weights are ignored.
+    pub fn set_weights(&self, queue: &wgpu::Queue, weights: &[f32]) {
+        let count = weights.len().min(self.num_targets);
+        queue.write_buffer(&self.weights, 0, bytemuck::cast_slice(&weights[..count]));
+    }
+}
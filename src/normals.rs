@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use glam::Vec3;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NormalWeighting {
+    Uniform,
+    Area,
+    Angle,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NormalMode {
+    Flat,
+    /// Faces meeting at more than `crease_angle` (in radians) are not smoothed together,
+    /// which splits their shared vertices.
+    Smooth { crease_angle: f32, weighting: NormalWeighting },
+}
+
+impl Default for NormalMode {
+    fn default() -> Self {
+        Self::Smooth { crease_angle: 60.0_f32.to_radians(), weighting: NormalWeighting::Angle }
+    }
+}
+
+pub struct GeneratedNormals {
+    pub normals: Vec<Vec3>,
+    pub indices: Vec<u32>,
+    /// The source vertex of every generated vertex.
+    pub remap: Vec<u32>,
+}
+
+impl GeneratedNormals {
+    pub fn remap_attribute<T: Copy>(&self, attribute: &[T]) -> Vec<T> {
+        self.remap.iter().map(|&i| attribute[i as usize]).collect()
+    }
+}
+
+fn triangle(indices: &[u32], triangle: usize) -> [usize; 3] {
+    [
+        indices[triangle * 3] as usize,
+        indices[triangle * 3 + 1] as usize,
+        indices[triangle * 3 + 2] as usize,
+    ]
+}
+
+fn corner_weight(positions: &[Vec3], triangle: [usize; 3], corner: usize, area: f32, weighting: NormalWeighting) -> f32 {
+    match weighting {
+        NormalWeighting::Uniform => 1.0,
+        NormalWeighting::Area => area,
+        NormalWeighting::Angle => {
+            let origin = positions[triangle[corner]];
+            let a = positions[triangle[(corner + 1) % 3]] - origin;
+            let b = positions[triangle[(corner + 2) % 3]] - origin;
+            if a.length_squared() == 0.0 || b.length_squared() == 0.0 {
+                0.0
+            } else {
+                a.angle_between(b)
+            }
+        }
+    }
+}
+
+/// Returns the unit normal and area of every triangle. Degenerate triangles get a zero normal.
+pub fn face_normals(positions: &[Vec3], indices: &[u32]) -> Vec<(Vec3, f32)> {
+    (0..indices.len() / 3)
+        .map(|t| {
+            let [a, b, c] = triangle(indices, t);
+            let cross = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+            (cross.normalize_or_zero(), cross.length() * 0.5)
+        })
+        .collect()
+}
+
+/// Numbers the distinct positions and returns the number of every vertex and how many there are.
+pub(crate) fn position_groups(positions: &[Vec3]) -> (Vec<usize>, usize) {
+    let mut groups = HashMap::new();
+    let group_of_vertex = positions
+        .iter()
+        .map(|p| {
+            let next = groups.len();
+            *groups.entry(p.to_array().map(f32::to_bits)).or_insert(next)
+        })
+        .collect();
+    (group_of_vertex, groups.len())
+}
+
+/// Computes one smooth normal per vertex without splitting any vertices.
+/// Vertices sharing a position, like the two sides of a UV seam, get the same normal.
+pub fn vertex_normals(positions: &[Vec3], indices: &[u32], weighting: NormalWeighting) -> Vec<Vec3> {
+    let faces = face_normals(positions, indices);
+    let (group_of_vertex, num_groups) = position_groups(positions);
+    let mut sums = vec![Vec3::ZERO; num_groups];
+    for (t, &(normal, area)) in faces.iter().enumerate() {
+        let tri = triangle(indices, t);
+        for corner in 0..3 {
+            sums[group_of_vertex[tri[corner]]] += normal * corner_weight(positions, tri, corner, area, weighting);
+        }
+    }
+    group_of_vertex
+        .into_iter()
+        .map(|g| sums[g].normalize_or(Vec3::Y))
+        .collect()
+}
+
+/// Computes normals for every triangle corner, splitting vertices wherever corners
+/// that used to share a vertex end up with different normals.
+pub fn generate_normals(positions: &[Vec3], indices: &[u32], mode: NormalMode) -> GeneratedNormals {
+    let faces = face_normals(positions, indices);
+    let (group_of_vertex, num_groups) = position_groups(positions);
+
+    let mut corners_of_group = vec![vec![]; num_groups];
+    for (i, &v) in indices.iter().enumerate() {
+        corners_of_group[group_of_vertex[v as usize]].push(i);
+    }
+
+    let mut generated = GeneratedNormals { normals: vec![], indices: Vec::with_capacity(indices.len()), remap: vec![] };
+    let mut vertex_lookup = HashMap::new();
+    for (i, &v) in indices.iter().enumerate() {
+        let (face_normal, _) = faces[i / 3];
+        let normal = match mode {
+            NormalMode::Flat => face_normal,
+            NormalMode::Smooth { crease_angle, weighting } => {
+                let min_cos = crease_angle.cos();
+                let mut sum = Vec3::ZERO;
+                for &other in &corners_of_group[group_of_vertex[v as usize]] {
+                    let (other_normal, area) = faces[other / 3];
+                    if other_normal.dot(face_normal) >= min_cos {
+                        let tri = triangle(indices, other / 3);
+                        sum += other_normal * corner_weight(positions, tri, other % 3, area, weighting);
+                    }
+                }
+                sum.normalize_or(face_normal)
+            }
+        }.normalize_or(Vec3::Y);
+
+        let key = (v, normal.to_array().map(f32::to_bits));
+        let index = *vertex_lookup.entry(key).or_insert_with(|| {
+            generated.normals.push(normal);
+            generated.remap.push(v);
+            generated.normals.len() as u32 - 1
+        });
+        generated.indices.push(index);
+    }
+    generated
+}
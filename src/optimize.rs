@@ -0,0 +1,303 @@
+use std::{collections::HashMap, fmt};
+use crate::mesh_data::MeshData;
+
+/// The size of the FIFO cache the stats are measured with, close to what GPUs have.
+pub const STATS_CACHE_SIZE: usize = 16;
+
+/// The size of the LRU cache the triangle order is tuned for. Tuning for a larger cache than
+/// the hardware has still does well on the smaller one.
+const OPTIMIZE_CACHE_SIZE: usize = 32;
+
+/// How well an index order reuses transformed vertices.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CacheStats {
+    /// Average cache misses per triangle, from 3 at worst down to about 0.5 for a regular grid.
+    pub acmr: f32,
+    /// Average transforms per vertex, 1 at best.
+    pub atvr: f32,
+}
+
+/// Simulates a FIFO post-transform cache of `cache_size` vertices running over `indices`.
+pub fn cache_stats(indices: &[u32], num_vertices: usize, cache_size: usize) -> CacheStats {
+    let mut cache = std::collections::VecDeque::with_capacity(cache_size);
+    let mut misses = 0;
+    let mut used = vec![false; num_vertices];
+    for &index in indices {
+        used[index as usize] = true;
+        if !cache.contains(&index) {
+            misses += 1;
+            if cache.len() == cache_size {
+                cache.pop_front();
+            }
+            cache.push_back(index);
+        }
+    }
+    let triangles = (indices.len() / 3).max(1);
+    let vertices = used.iter().filter(|&&used| used).count().max(1);
+    CacheStats { acmr: misses as f32 / triangles as f32, atvr: misses as f32 / vertices as f32 }
+}
+
+/// Keeps the vertices at `kept` in that order and points the indices at their new places.
+fn keep_vertices(data: &mut MeshData, kept: &[u32]) {
+    let mut new_index = vec![u32::MAX; data.num_vertices()];
+    for (new, &old) in kept.iter().enumerate() {
+        new_index[old as usize] = new as u32;
+    }
+    data.remap_vertices(kept);
+    for index in &mut data.indices {
+        *index = new_index[*index as usize];
+    }
+}
+
+/// Merges vertices whose attributes are all identical and returns how many were removed.
+/// Unused vertices are dropped along the way.
+pub fn weld_vertices(data: &mut MeshData) -> usize {
+    let before = data.num_vertices();
+    let mut unique = HashMap::new();
+    let mut kept = vec![];
+    let remap = (0..before)
+        .map(|i| {
+            let key = (
+                data.positions[i].to_array().map(f32::to_bits),
+                data.tex_coords[i].to_array().map(f32::to_bits),
+                data.normals[i].to_array().map(f32::to_bits),
+                data.tangents[i].to_array().map(f32::to_bits),
+                data.colors.get(i).map(|color| color.to_array().map(f32::to_bits)),
+                data.joints.get(i).map(|joints| joints.to_array()),
+                data.weights.get(i).map(|weights| weights.to_array().map(f32::to_bits)),
+                data.morph_targets
+                    .iter()
+                    .flat_map(|target| target.delta(i))
+                    .map(|delta| delta.to_array().map(f32::to_bits))
+                    .collect::<Vec<_>>(),
+            );
+            *unique.entry(key).or_insert_with(|| {
+                kept.push(i as u32);
+                i as u32
+            })
+        })
+        .collect::<Vec<_>>();
+    for index in &mut data.indices {
+        *index = remap[*index as usize];
+    }
+    let used = data.indices.iter().fold(vec![false; before], |mut used, &index| {
+        used[index as usize] = true;
+        used
+    });
+    kept.retain(|&i| used[i as usize]);
+    keep_vertices(data, &kept);
+    before - data.num_vertices()
+}
+
+/// Tom Forsyth's score for a vertex at `cache_position` in the cache, or outside it, that
+/// still has `remaining` triangles to draw. Vertices of the last triangle score a little lower
+/// so strips do not keep turning back, and vertices with few triangles left are boosted so
+/// they get finished off.
+fn vertex_score(cache_position: Option<usize>, remaining: u32) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        None => 0.0,
+        Some(position) if position < 3 => 0.75,
+        Some(position) => (1.0 - (position - 3) as f32 / (OPTIMIZE_CACHE_SIZE - 3) as f32).powf(1.5),
+    };
+    cache_score + 2.0 * (remaining as f32).powf(-0.5)
+}
+
+/// Reorders the triangles so neighbouring triangles are drawn close together and their
+/// shared vertices come out of the post-transform cache.
+pub fn optimize_vertex_cache(indices: &mut [u32], num_vertices: usize) {
+    let num_triangles = indices.len() / 3;
+    let mut vertex_triangles = vec![vec![]; num_vertices];
+    for (t, triangle) in indices.chunks(3).enumerate() {
+        for &vertex in triangle {
+            vertex_triangles[vertex as usize].push(t as u32);
+        }
+    }
+    let mut remaining = vertex_triangles.iter().map(|triangles| triangles.len() as u32).collect::<Vec<_>>();
+    let mut scores = (0..num_vertices).map(|v| vertex_score(None, remaining[v])).collect::<Vec<_>>();
+    let triangle_score = |scores: &[f32], t: usize| -> f32 { indices[t * 3..t * 3 + 3].iter().map(|&v| scores[v as usize]).sum() };
+
+    let mut drawn = vec![false; num_triangles];
+    let mut order = Vec::with_capacity(indices.len());
+    let mut cache: Vec<u32> = vec![];
+    let mut next_undrawn = 0;
+    let mut best = None;
+    for _ in 0..num_triangles {
+        // With nothing in the cache to build on, start over at the next triangle in the input.
+        let t = best.unwrap_or_else(|| {
+            while drawn[next_undrawn] {
+                next_undrawn += 1;
+            }
+            next_undrawn
+        });
+        drawn[t] = true;
+        let triangle = [indices[t * 3], indices[t * 3 + 1], indices[t * 3 + 2]];
+        order.extend(triangle);
+
+        for vertex in triangle {
+            remaining[vertex as usize] -= 1;
+        }
+        let mut new_cache = triangle.to_vec();
+        new_cache.extend(cache.iter().copied().filter(|vertex| !triangle.contains(vertex)));
+        for &vertex in new_cache.iter().skip(OPTIMIZE_CACHE_SIZE) {
+            scores[vertex as usize] = vertex_score(None, remaining[vertex as usize]);
+        }
+        new_cache.truncate(OPTIMIZE_CACHE_SIZE);
+        for (position, &vertex) in new_cache.iter().enumerate() {
+            scores[vertex as usize] = vertex_score(Some(position), remaining[vertex as usize]);
+        }
+
+        best = None;
+        let mut best_score = f32::NEG_INFINITY;
+        for &vertex in &new_cache {
+            for &t in &vertex_triangles[vertex as usize] {
+                let t = t as usize;
+                if drawn[t] {
+                    continue;
+                }
+                let score = triangle_score(&scores, t);
+                if score > best_score {
+                    best_score = score;
+                    best = Some(t);
+                }
+            }
+        }
+        cache = new_cache;
+    }
+    indices.copy_from_slice(&order);
+}
+
+/// Reorders the vertices in the order the indices first use them, so the vertex fetches walk
+/// through memory. Unused vertices are dropped.
+pub fn optimize_vertex_fetch(data: &mut MeshData) {
+    let mut seen = vec![false; data.num_vertices()];
+    let mut kept = vec![];
+    for &index in &data.indices {
+        if !seen[index as usize] {
+            seen[index as usize] = true;
+            kept.push(index);
+        }
+    }
+    keep_vertices(data, &kept);
+}
+
+/// What [`optimize`] did to a mesh.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OptimizationReport {
+    pub vertices_before: usize,
+    pub vertices_after: usize,
+    pub before: CacheStats,
+    pub after: CacheStats,
+}
+
+impl fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} -> {} vertices, ACMR {:.3} -> {:.3}, ATVR {:.3} -> {:.3}",
+            self.vertices_before, self.vertices_after, self.before.acmr, self.after.acmr, self.before.atvr, self.after.atvr,
+        )
+    }
+}
+
+/// Welds duplicate vertices, then orders the triangles for the vertex cache and the vertices
+/// for fetching. The mesh looks the same afterwards.
+pub fn optimize(data: &mut MeshData) -> OptimizationReport {
+    let vertices_before = data.num_vertices();
+    let before = cache_stats(&data.indices, data.num_vertices(), STATS_CACHE_SIZE);
+    weld_vertices(data);
+    optimize_vertex_cache(&mut data.indices, data.positions.len());
+    optimize_vertex_fetch(data);
+    OptimizationReport {
+        vertices_before,
+        vertices_after: data.num_vertices(),
+        before,
+        after: cache_stats(&data.indices, data.num_vertices(), STATS_CACHE_SIZE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec2, Vec3, Vec4};
+    use crate::shapes::{cube_data, plane_data};
+    use super::*;
+
+    /// Gives every triangle its own three vertices.
+    fn split(mut data: MeshData) -> MeshData {
+        let sources = std::mem::take(&mut data.indices);
+        data.remap_vertices(&sources);
+        data.indices = (0..sources.len() as u32).collect();
+        data
+    }
+
+    /// The triangles by their corners, each starting at its lowest index so the winding counts
+    /// but not where the triangle starts.
+    fn triangle_set(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles = indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let mut triangle = [triangle[0], triangle[1], triangle[2]];
+                let lowest = (0..3).min_by_key(|&i| triangle[i]).unwrap();
+                triangle.rotate_left(lowest);
+                triangle
+            })
+            .collect::<Vec<_>>();
+        triangles.sort_unstable();
+        triangles
+    }
+
+    /// A grid of triangles in a scrambled order, which is about as bad as it gets for the cache.
+    fn shuffled_grid() -> MeshData {
+        let mut data = plane_data(Vec2::ONE, 32);
+        let mut triangles = data.indices.chunks_exact(3).map(<[u32]>::to_vec).collect::<Vec<_>>();
+        let mut state = 12345u32;
+        for i in (1..triangles.len()).rev() {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            triangles.swap(i, (state >> 8) as usize % (i + 1));
+        }
+        data.indices = triangles.concat();
+        data
+    }
+
+    #[test]
+    fn welding_merges_matching_vertices_only() {
+        // The faces of a cube have their own normals and UVs, so its corners stay apart.
+        let mut data = split(cube_data(Vec3::splat(2.0)));
+        assert_eq!(weld_vertices(&mut data), 36 - 24);
+        assert_eq!(data.num_vertices(), 24);
+
+        let mut data = split(cube_data(Vec3::splat(2.0)));
+        data.normals = data.positions.iter().map(|position| position.normalize()).collect();
+        data.tex_coords.fill(Vec2::ZERO);
+        data.tangents.fill(Vec4::X);
+        let positions = data.triangles().map(|triangle| triangle.positions).collect::<Vec<_>>();
+        assert_eq!(weld_vertices(&mut data), 36 - 8);
+        assert_eq!(data.num_vertices(), 8);
+        assert_eq!(data.triangles().map(|triangle| triangle.positions).collect::<Vec<_>>(), positions);
+    }
+
+    #[test]
+    fn cache_order_keeps_the_triangles() {
+        let mut data = shuffled_grid();
+        let expected = triangle_set(&data.indices);
+        optimize_vertex_cache(&mut data.indices, data.positions.len());
+        assert_eq!(triangle_set(&data.indices), expected);
+    }
+
+    #[test]
+    fn cache_order_does_not_get_worse() {
+        let mut data = shuffled_grid();
+        let before = cache_stats(&data.indices, data.num_vertices(), STATS_CACHE_SIZE);
+        optimize_vertex_cache(&mut data.indices, data.positions.len());
+        let after = cache_stats(&data.indices, data.num_vertices(), STATS_CACHE_SIZE);
+        assert!(after.acmr < before.acmr, "ACMR {} -> {}", before.acmr, after.acmr);
+        assert!(after.acmr < 1.0, "ACMR {}", after.acmr);
+
+        // Running it again on an order that is already good keeps it good.
+        optimize_vertex_cache(&mut data.indices, data.positions.len());
+        let again = cache_stats(&data.indices, data.num_vertices(), STATS_CACHE_SIZE);
+        assert!(again.acmr <= after.acmr + 0.01, "ACMR {} -> {}", after.acmr, again.acmr);
+    }
+}
@@ -0,0 +1,109 @@
+// Metallic-roughness materials lit by a Cook-Torrance GGX BRDF, completed by lit_common.wgsl.
+
+// Vertex shader
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) texture_coordinates: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) texture_coordinates: vec2<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) world_normal: vec3<f32>,
+    @location(3) world_tangent: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = instance_model_matrix(instance);
+    let normal_matrix = instance_normal_matrix(instance);
+
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.texture_coordinates = model.texture_coordinates;
+    out.clip_position = camera.view_proj * world_position;
+    out.world_position = world_position.xyz;
+    out.world_normal = normal_matrix * model.normal;
+    // Tangents lie in the surface, so they move with the model rather than like normals.
+    out.world_tangent = vec4<f32>((model_matrix * vec4<f32>(model.tangent.xyz, 0.0)).xyz, model.tangent.w);
+    return out;
+}
+
+// Fragment shader
+
+const PI: f32 = 3.14159265359;
+
+// The GGX (Trowbridge-Reitz) distribution of microfacet normals.
+fn distribution_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (PI * d * d);
+}
+
+// The height-correlated Smith masking and shadowing term, with the 1 / (4 n.l n.v) of the
+// Cook-Torrance denominator folded in.
+fn visibility_smith_ggx(n_dot_v: f32, n_dot_l: f32, alpha: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    let ggx_v = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - alpha2) + alpha2);
+    let ggx_l = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - alpha2) + alpha2);
+    return 0.5 / max(ggx_v + ggx_l, 1e-5);
+}
+
+fn fresnel_schlick(v_dot_h: f32, f0: vec3<f32>) -> vec3<f32> {
+    // Rounding can put v.h a hair above 1, and pow is undefined for negative bases.
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - v_dot_h, 0.0, 1.0), 5.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let base_color = textureSample(t_diffuse, s_diffuse, in.texture_coordinates) * material.base_color;
+    if material.alpha_cutoff > 0.0 && base_color.a < material.alpha_cutoff {
+        discard;
+    }
+    let metallic_roughness = textureSample(t_metallic_roughness, s_metallic_roughness, in.texture_coordinates);
+    let metallic = clamp(material.metallic * metallic_roughness.b, 0.0, 1.0);
+    // Perfectly smooth surfaces would turn the light into a single bright point.
+    let roughness = clamp(material.roughness * metallic_roughness.g, 0.045, 1.0);
+    let occlusion = 1.0 + material.occlusion_strength * (textureSample(t_occlusion, s_occlusion, in.texture_coordinates).r - 1.0);
+    let emissive = textureSample(t_emissive, s_emissive, in.texture_coordinates).rgb * material.emissive;
+
+    let world_normal = normalize(in.world_normal);
+    let world_tangent = normalize(in.world_tangent.xyz - world_normal * dot(world_normal, in.world_tangent.xyz));
+    let world_bitangent = cross(world_normal, world_tangent) * in.world_tangent.w;
+    var tangent_normal = textureSample(t_normal, s_normal, in.texture_coordinates).xyz * 2.0 - 1.0;
+    tangent_normal = vec3<f32>(tangent_normal.xy * material.normal_scale, tangent_normal.z);
+    let normal = normalize(mat3x3<f32>(world_tangent, world_bitangent, world_normal) * tangent_normal);
+
+    let light_dir = normalize(light.position - in.world_position);
+    let view_dir = normalize(camera.view_pos.xyz - in.world_position);
+    let half_dir = normalize(view_dir + light_dir);
+    let n_dot_l = max(dot(normal, light_dir), 0.0);
+    let n_dot_v = max(dot(normal, view_dir), 1e-4);
+    let n_dot_h = max(dot(normal, half_dir), 0.0);
+    let v_dot_h = max(dot(view_dir, half_dir), 0.0);
+
+    // Dielectrics reflect about 4% head on, metals reflect their base color and diffuse nothing.
+    let f0 = mix(vec3<f32>(0.04), base_color.rgb, metallic);
+    let diffuse_color = base_color.rgb * (1.0 - metallic);
+    let alpha = roughness * roughness;
+    let fresnel = fresnel_schlick(v_dot_h, f0);
+    let specular = distribution_ggx(n_dot_h, alpha) * visibility_smith_ggx(n_dot_v, n_dot_l, alpha) * fresnel;
+    let diffuse = (1.0 - fresnel) * diffuse_color / PI;
+
+    // The light color is scaled by pi so a white light lights a white wall as brightly as in
+    // blinn_phong.wgsl.
+    let direct = (diffuse + specular) * light.color * PI * n_dot_l;
+    // The ambient light stands in for environment lighting, reflected diffusely and by the
+    // base reflectance.
+    let ambient = light.ambient * (diffuse_color + f0) * occlusion;
+
+    return vec4<f32>(ambient + direct + emissive, base_color.a);
+}
@@ -0,0 +1,55 @@
+// Vertex colored plots tinted by the base color of their material, completed by
+// lit_common.wgsl.
+
+// Vertex shader
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(4) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = instance_model_matrix(instance);
+    let normal_matrix = instance_normal_matrix(instance);
+
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.world_position = world_position.xyz;
+    out.world_normal = normal_matrix * model.normal;
+    out.color = model.color;
+
+    return out;
+}
+
+// Fragment shader
+
+// Plots are lit by a fixed light from above and a light at the eye, so every side reads.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = normalize(in.world_normal);
+    let view_dir = normalize(camera.view_pos.xyz - in.world_position);
+    let light_dir = normalize(vec3<f32>(0.3, 1.0, 0.5));
+
+    let ambient_strength = 0.15;
+    let diffuse_strength = max(dot(normal, light_dir), 0.0) * 0.6 + max(dot(normal, view_dir), 0.0) * 0.4;
+    let specular_strength = pow(max(dot(normal, normalize(view_dir + light_dir)), 0.0), 32.0) * 0.2;
+
+    let color = in.color * material.base_color;
+    let result = color.xyz * (ambient_strength + diffuse_strength) + vec3<f32>(specular_strength);
+
+    return vec4<f32>(result, color.a);
+}
@@ -1,22 +1,23 @@
-use std::io::{BufReader, Cursor};
-use glam::{vec2, vec3, Vec2, Vec3};
+use std::{collections::HashMap, io::{BufReader, Cursor}, path::{Path, PathBuf}};
+use anyhow::{anyhow, bail};
+use glam::{vec2, vec3, Mat4, Quat, UVec4, Vec2, Vec3, Vec4};
 use tobj::Model;
-use wgpu::util::DeviceExt;
-use crate::{model::{self, Mesh}, texture};
+use crate::{animation::Keyframe, builder::MeshBuilder, model::{self, Mesh}, morph::MorphTarget, skin::{self, AnimationClip, Channel, ChannelValues, Joint, Keyframes, Skeleton, SkinnedModel, SkinnedVertex, Transform}, subdivision::PolygonMesh, terrain::TerrainBuilder, texture, validation};
 
-pub fn load_string(file_name: &str) -> anyhow::Result<String> {
-    let path = std::path::Path::new(env!("OUT_DIR"))
+/// Where a file from `res/` ends up next to the build.
+pub fn resource_path(file_name: &str) -> PathBuf {
+    std::path::Path::new(env!("OUT_DIR"))
         .join("res")
-        .join(file_name);
-    let txt = std::fs::read_to_string(path)?;
+        .join(file_name)
+}
+
+pub fn load_string(file_name: &str) -> anyhow::Result<String> {
+    let txt = std::fs::read_to_string(resource_path(file_name))?;
     Ok(txt)
 }
 
 pub fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
-    let path = std::path::Path::new(env!("OUT_DIR"))
-        .join("res")
-        .join(file_name);
-    let data = std::fs::read(path)?;
+    let data = std::fs::read(resource_path(file_name))?;
     Ok(data)
 }
 
@@ -27,11 +28,21 @@ pub fn load_texture(
     queue: &wgpu::Queue,
 ) -> anyhow::Result<texture::Texture> {
     let data = load_binary(file_name)?;
-    texture::Texture::from_bytes(device, queue, &data, file_name, is_normal_map)
+    let texture = texture::Texture::from_bytes(device, queue, &data, file_name, is_normal_map)?;
+    Ok(texture::Texture { path: Some(resource_path(file_name)), ..texture })
+}
+
+pub fn load_heightmap(file_name: &str, size: Vec2) -> anyhow::Result<TerrainBuilder> {
+    let data = load_binary(file_name)?;
+    let image = image::load_from_memory(&data)?;
+    Ok(TerrainBuilder::from_image(&image, size))
 }
 
+/// Loads an OBJ file and its materials. `retain_data` keeps a CPU copy of every mesh for tools
+/// like [`crate::uv::project_mesh_uvs`] and [`crate::simplify::LodChain::from_mesh`].
 pub fn load_model(
     file_name: &str,
+    retain_data: bool,
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     layout: &wgpu::BindGroupLayout,
@@ -56,114 +67,485 @@ pub fn load_model(
 
     let mut materials = Vec::new();
     for m in obj_materials? {
-        let diffuse_texture = load_texture(&m.diffuse_texture.unwrap(), false, device, queue)?;
-        let normal_texture = load_texture(&m.normal_texture.unwrap(), true, device, queue)?;
+        materials.push(obj_material(&m, device, queue, layout)?);
+    }
+
+    let meshes = get_meshes(models, retain_data, device, file_name);
+
+    let model = model::MatModel { meshes, materials };
+    warn_about_problems(file_name, &model);
+    Ok(model)
+}
 
-        materials.push(model::ModelMaterial::new(
-            device,
-            &m.name,
-            diffuse_texture,
-            normal_texture,
-            layout,
-        ));
+/// Reads the usual MTL colors and textures along with the PBR extension that Blender and
+/// Substance write: `Pr`, `Pm` and `Ke` with their `map_` textures, and `norm` for the normal
+/// map.
+fn obj_material(
+    m: &tobj::Material,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+) -> anyhow::Result<model::ModelMaterial> {
+    let param = |key: &str| m.unknown_param.get(key);
+    let numbers = |key: &str| -> Vec<f32> {
+        param(key).map_or(vec![], |value| value.split_whitespace().filter_map(|number| number.parse().ok()).collect())
+    };
+    let mut textures = model::MaterialTextures::plain(device, queue, &m.name)?;
+    let mut factors = model::MaterialFactors::default();
+
+    match &m.diffuse_texture {
+        Some(file) => textures.diffuse = load_texture(file, false, device, queue)?,
+        None => {
+            let color = Vec3::from(m.diffuse.unwrap_or([1.0; 3])).extend(1.0);
+            textures.diffuse = color_texture(color, &m.name, device, queue)?;
+        }
+    }
+    factors.base_color.w = m.dissolve.unwrap_or(1.0);
+    if let Some(file) = m.normal_texture.as_ref().or(param("norm")) {
+        textures.normal = load_texture(file, true, device, queue)?;
     }
 
-    let meshes = get_meshes(models, device, file_name);
+    let (metallic_map, roughness_map) = (param("map_Pm"), param("map_Pr"));
+    factors.metallic = numbers("Pm").first().copied().unwrap_or(if metallic_map.is_some() { 1.0 } else { 0.0 });
+    // Files from before the extension still say how shiny they are with the Blinn-Phong
+    // exponent, which maps onto roughness roughly like this.
+    factors.roughness = match (numbers("Pr").first(), roughness_map, m.shininess) {
+        (Some(&roughness), _, _) => roughness,
+        (None, Some(_), _) => 1.0,
+        (None, None, Some(shininess)) => (2.0 / (shininess.max(0.0) + 2.0)).powf(0.25),
+        (None, None, None) => factors.roughness,
+    };
+    if metallic_map.is_some() || roughness_map.is_some() {
+        textures.metallic_roughness = metallic_roughness_texture(metallic_map, roughness_map, &m.name, device, queue)?;
+    }
 
-    Ok(model::MatModel { meshes, materials })
+    if let [r, g, b] = numbers("Ke")[..] {
+        factors.emissive = vec3(r, g, b);
+    }
+    if let Some(file) = param("map_Ke") {
+        textures.emissive = load_texture(file, false, device, queue)?;
+        if numbers("Ke").is_empty() {
+            factors.emissive = Vec3::ONE;
+        }
+    }
+    Ok(model::ModelMaterial::with_textures(device, &m.name, textures, factors, layout))
 }
 
-fn get_meshes(models: Vec<Model>, device: &wgpu::Device, file_name: &str) -> Vec<Mesh> {
-    models
-        .into_iter()
-        .map(|m| {
-            let mut vertices = (0..m.mesh.positions.len() / 3)
-                .map(|i| model::ModelVertex {
-                    position: vec3(
-                        m.mesh.positions[i * 3],
-                        m.mesh.positions[i * 3 + 1],
-                        m.mesh.positions[i * 3 + 2],
-                    ),
-                    tex_coords: vec2(m.mesh.texcoords[i * 2], 1.0 - m.mesh.texcoords[i * 2 + 1]),
-                    normal: vec3(
-                        m.mesh.normals[i * 3],
-                        m.mesh.normals[i * 3 + 1],
-                        m.mesh.normals[i * 3 + 2],
-                    ),
-                    tangent: Vec3::ZERO,
-                    bitangent: Vec3::ZERO,
+/// Packs separate grayscale metalness and roughness maps into one texture the way glTF does,
+/// scaling the metalness to the size of the roughness if they differ.
+fn metallic_roughness_texture(
+    metallic: Option<&String>,
+    roughness: Option<&String>,
+    name: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> anyhow::Result<texture::Texture> {
+    let load = |file: Option<&String>| -> anyhow::Result<Option<image::GrayImage>> {
+        file.map(|file| Ok(image::load_from_memory(&load_binary(file)?)?.to_luma8())).transpose()
+    };
+    let (metallic, roughness) = (load(metallic)?, load(roughness)?);
+    let Some((width, height)) = roughness.as_ref().or(metallic.as_ref()).map(|image| image.dimensions()) else {
+        return texture::Texture::from_color(device, queue, [255; 4], name, true);
+    };
+    let fit = |image: Option<image::GrayImage>| {
+        image.map(|image| {
+            if image.dimensions() == (width, height) {
+                image
+            } else {
+                image::imageops::resize(&image, width, height, image::imageops::FilterType::Triangle)
+            }
+        })
+    };
+    let (metallic, roughness) = (fit(metallic), fit(roughness));
+    let channel = |image: &Option<image::GrayImage>, x, y| image.as_ref().map_or(255, |image| image.get_pixel(x, y)[0]);
+    let packed = image::RgbaImage::from_fn(width, height, |x, y| image::Rgba([255, channel(&roughness, x, y), channel(&metallic, x, y), 255]));
+    texture::Texture::from_image(device, queue, &image::DynamicImage::ImageRgba8(packed), Some(name), true)
+}
+
+/// A single texel of a linear color, for materials without a color texture. The color goes
+/// into the texture rather than the factors so that the Blinn-Phong shaders see it too.
+fn color_texture(color: Vec4, name: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<texture::Texture> {
+    let srgb = |c: f32| {
+        let c = c.clamp(0.0, 1.0);
+        if c <= 0.0031308 { 12.92 * c } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+    };
+    let texel = [srgb(color.x), srgb(color.y), srgb(color.z), color.w.clamp(0.0, 1.0)];
+    texture::Texture::from_color(device, queue, texel.map(|c| (c * 255.0).round() as u8), name, false)
+}
+
+fn warn_about_problems(file_name: &str, model: &model::MatModel) {
+    let report = validation::validate_model(model);
+    if report.missing_materials > 0 {
+        log::warn!("{file_name}: {} meshes use missing materials", report.missing_materials);
+    }
+    for (mesh, report) in model.meshes.iter().zip(&report.meshes) {
+        if let Some(report) = report {
+            warn_about_mesh(file_name, &mesh.name, report);
+        }
+    }
+}
+
+fn warn_about_mesh(file_name: &str, name: &str, report: &validation::MeshReport) {
+    if report.has_errors() {
+        log::warn!("{file_name}: {name} has problems\n{report}");
+    }
+}
+
+/// Loads a glTF or binary glTF file with the first skin in it and every animation of that
+/// skin's joints. Meshes without the skin are placed where their nodes put them and left
+/// still. Morph targets are loaded with the default weights of their meshes, and animations of
+/// the weights are skipped with a warning. Buffers and images must be in the file or in files
+/// beside it. `retain_data` keeps a CPU copy of every mesh, which
+/// [`crate::morph::MorphBuffer::new`] and [`crate::morph::apply_morph_targets`] need.
+pub fn load_skinned_model(
+    file_name: &str,
+    retain_data: bool,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+) -> anyhow::Result<SkinnedModel> {
+    let gltf::Gltf { document, mut blob } = gltf::Gltf::from_slice(&load_binary(file_name)?)?;
+    let beside = |uri: &str| -> anyhow::Result<String> {
+        if uri.starts_with("data:") {
+            bail!("{file_name}: embedded data URIs are not supported");
+        }
+        Ok(Path::new(file_name).with_file_name(uri).to_string_lossy().into_owned())
+    };
+    let buffers = document
+        .buffers()
+        .map(|buffer| match buffer.source() {
+            gltf::buffer::Source::Bin => blob.take().ok_or_else(|| anyhow!("{file_name}: missing binary chunk")),
+            gltf::buffer::Source::Uri(uri) => load_binary(&beside(uri)?),
+        })
+        .zip(document.buffers())
+        .map(|(data, buffer)| match data {
+            Ok(data) if data.len() < buffer.length() => {
+                bail!("{file_name}: buffer {} has {} of its {} bytes", buffer.index(), data.len(), buffer.length())
+            }
+            data => data,
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let buffer_data = |buffer: gltf::Buffer| buffers.get(buffer.index()).map(Vec::as_slice);
+
+    let mut materials = Vec::new();
+    for material in document.materials() {
+        let name = material.name().unwrap_or(file_name);
+        let load_image = |image: gltf::Image, is_normal_map: bool| -> anyhow::Result<texture::Texture> {
+            match image.source() {
+                gltf::image::Source::View { view, .. } => {
+                    let bytes = buffers
+                        .get(view.buffer().index())
+                        .and_then(|buffer| buffer.get(view.offset()..view.offset() + view.length()))
+                        .ok_or_else(|| anyhow!("{file_name}: the image of {name} lies outside its buffer"))?;
+                    texture::Texture::from_bytes(device, queue, bytes, name, is_normal_map)
+                }
+                gltf::image::Source::Uri { uri, .. } => load_texture(&beside(uri)?, is_normal_map, device, queue),
+            }
+        };
+        let pbr = material.pbr_metallic_roughness();
+        let mut textures = model::MaterialTextures::plain(device, queue, name)?;
+        let mut factors = model::MaterialFactors {
+            base_color: Vec4::from(pbr.base_color_factor()),
+            emissive: Vec3::from(material.emissive_factor()),
+            metallic: pbr.metallic_factor(),
+            roughness: pbr.roughness_factor(),
+            alpha_cutoff: match material.alpha_mode() {
+                gltf::material::AlphaMode::Mask => material.alpha_cutoff().unwrap_or(0.5),
+                _ => 0.0,
+            },
+            ..Default::default()
+        };
+        match pbr.base_color_texture() {
+            Some(info) => textures.diffuse = load_image(info.texture().source(), false)?,
+            None => {
+                textures.diffuse = color_texture(factors.base_color, name, device, queue)?;
+                factors.base_color = Vec4::ONE;
+            }
+        }
+        if let Some(info) = pbr.metallic_roughness_texture() {
+            textures.metallic_roughness = load_image(info.texture().source(), true)?;
+        }
+        if let Some(normal) = material.normal_texture() {
+            textures.normal = load_image(normal.texture().source(), true)?;
+            factors.normal_scale = normal.scale();
+        }
+        if let Some(occlusion) = material.occlusion_texture() {
+            textures.occlusion = load_image(occlusion.texture().source(), true)?;
+            factors.occlusion_strength = occlusion.strength();
+        }
+        if let Some(info) = material.emissive_texture() {
+            textures.emissive = load_image(info.texture().source(), false)?;
+        }
+        materials.push(model::ModelMaterial::with_textures(device, name, textures, factors, layout));
+    }
+
+    // Every node's transform in the scene, following the parents up.
+    let mut parents = HashMap::new();
+    for node in document.nodes() {
+        for child in node.children() {
+            parents.insert(child.index(), node.index());
+        }
+    }
+    let nodes = document.nodes().collect::<Vec<_>>();
+    let local = |node: usize| Mat4::from_cols_array_2d(&nodes[node].transform().matrix());
+    let global = |mut node: usize| {
+        let mut matrix = local(node);
+        while let Some(&parent) = parents.get(&node) {
+            matrix = local(parent) * matrix;
+            node = parent;
+        }
+        matrix
+    };
+
+    let skin = document.skins().next();
+    if document.skins().count() > 1 {
+        log::warn!("{file_name}: only the first of {} skins is used", document.skins().count());
+    }
+    let joint_nodes = skin.as_ref().map_or(vec![], |skin| skin.joints().map(|joint| joint.index()).collect());
+    let joint_of_node = joint_nodes.iter().enumerate().map(|(joint, &node)| (node, joint)).collect::<HashMap<_, _>>();
+    let skeleton = match &skin {
+        Some(skin) => {
+            let inverse_bind_matrices = match skin.reader(buffer_data).read_inverse_bind_matrices() {
+                Some(matrices) => matrices.map(|matrix| Mat4::from_cols_array_2d(&matrix)).collect(),
+                None => vec![Mat4::IDENTITY; joint_nodes.len()],
+            };
+            let joints = joint_nodes
+                .iter()
+                .map(|&node| Joint {
+                    name: nodes[node].name().map_or_else(|| format!("joint {node}"), str::to_owned),
+                    parent: parents.get(&node).and_then(|parent| joint_of_node.get(parent).copied()),
+                    rest: Transform::from_matrix(local(node)),
                 })
                 .collect::<Vec<_>>();
+            // The joints are assumed to share whatever hangs above the topmost one.
+            let root = joints
+                .iter()
+                .zip(&joint_nodes)
+                .find(|(joint, _)| joint.parent.is_none())
+                .and_then(|(_, node)| parents.get(node))
+                .map_or(Mat4::IDENTITY, |&parent| global(parent));
+            Skeleton::new(joints, inverse_bind_matrices, root)
+        }
+        None => Skeleton::default(),
+    };
 
-            let indices = &m.mesh.indices;
-            let mut triangles_included = vec![0; vertices.len()];
-
-            for c in indices.chunks(3) {
-                let v0 = vertices[c[0] as usize];
-                let v1 = vertices[c[1] as usize];
-                let v2 = vertices[c[2] as usize];
-    
-                let pos0: Vec3 = v0.position.into();
-                let pos1: Vec3 = v1.position.into();
-                let pos2: Vec3 = v2.position.into();
-    
-                let uv0: Vec2 = v0.tex_coords.into();
-                let uv1: Vec2 = v1.tex_coords.into();
-                let uv2: Vec2 = v2.tex_coords.into();
-
-                let delta_pos1 = pos1 - pos0;
-                let delta_pos2 = pos2 - pos0;
-
-                let delta_uv1 = uv1 - uv0;
-                let delta_uv2 = uv2 - uv0;
-
-                let r = 1.0 / (delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x);
-                let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
-                let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * -r;
-
-                vertices[c[0] as usize].tangent =
-                    (tangent + Vec3::from(vertices[c[0] as usize].tangent)).into();
-                vertices[c[1] as usize].tangent =
-                    (tangent + Vec3::from(vertices[c[1] as usize].tangent)).into();
-                vertices[c[2] as usize].tangent =
-                    (tangent + Vec3::from(vertices[c[2] as usize].tangent)).into();
-                vertices[c[0] as usize].bitangent =
-                    (bitangent + Vec3::from(vertices[c[0] as usize].bitangent)).into();
-                vertices[c[1] as usize].bitangent =
-                    (bitangent + Vec3::from(vertices[c[1] as usize].bitangent)).into();
-                vertices[c[2] as usize].bitangent =
-                    (bitangent + Vec3::from(vertices[c[2] as usize].bitangent)).into();
-
-                triangles_included[c[0] as usize] += 1;
-                triangles_included[c[1] as usize] += 1;
-                triangles_included[c[2] as usize] += 1;
+    let mut meshes = Vec::new();
+    for node in document.nodes() {
+        let Some(mesh) = node.mesh() else {
+            continue;
+        };
+        let skinned = skin.is_some() && node.skin().is_some_and(|skin| skin.index() == 0);
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                log::warn!("{file_name}: skipping a primitive of {} that is not made of triangles", mesh.name().unwrap_or("a mesh"));
+                continue;
+            }
+            let reader = primitive.reader(buffer_data);
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+            let mut positions = positions.map(Vec3::from).collect::<Vec<_>>();
+            let mut normals = reader.read_normals().map(|normals| normals.map(Vec3::from).collect::<Vec<_>>());
+            let mut morph_targets = reader
+                .read_morph_targets()
+                .enumerate()
+                .map(|(i, (positions, normals, tangents))| MorphTarget {
+                    name: format!("target {i}"),
+                    positions: positions.map_or(vec![], |deltas| deltas.map(Vec3::from).collect()),
+                    normals: normals.map_or(vec![], |deltas| deltas.map(Vec3::from).collect()),
+                    tangents: tangents.map_or(vec![], |deltas| deltas.map(Vec3::from).collect()),
+                    weight: mesh.weights().and_then(|weights| weights.get(i)).copied().unwrap_or(0.0),
+                })
+                .collect::<Vec<_>>();
+            // Skinned meshes ignore the transform of their node, the joints place them instead.
+            if !skinned {
+                let transform = global(node.index());
+                let normal_matrix = transform.inverse().transpose();
+                positions.iter_mut().for_each(|p| *p = transform.transform_point3(*p));
+                if let Some(normals) = &mut normals {
+                    normals.iter_mut().for_each(|n| *n = normal_matrix.transform_vector3(*n).normalize_or_zero());
+                }
+                for target in &mut morph_targets {
+                    target.positions.iter_mut().for_each(|p| *p = transform.transform_vector3(*p));
+                    target.normals.iter_mut().for_each(|n| *n = normal_matrix.transform_vector3(*n));
+                    target.tangents.iter_mut().for_each(|t| *t = transform.transform_vector3(*t));
+                }
             }
+            let material = primitive.material().index().unwrap_or(materials.len());
+            let mut builder = MeshBuilder::<SkinnedVertex>::new(mesh.name().unwrap_or(file_name), positions)
+                .material(material)
+                .morph_targets(morph_targets)
+                .optimize(true)
+                .retain_data(retain_data);
+            if let Some(indices) = reader.read_indices() {
+                builder = builder.indices(indices.into_u32().collect());
+            }
+            if let Some(tex_coords) = reader.read_tex_coords(0) {
+                builder = builder.tex_coords(tex_coords.into_f32().map(Vec2::from).collect());
+            }
+            if let Some(normals) = normals {
+                builder = builder.normals(normals);
+                // Meshes moved into place get fresh tangents to go with their moved normals.
+                if let (Some(tangents), true) = (reader.read_tangents(), skinned) {
+                    builder = builder.tangents(tangents.map(Vec4::from).collect());
+                }
+            }
+            if let (true, Some(joints), Some(weights)) = (skinned, reader.read_joints(0), reader.read_weights(0)) {
+                builder = builder.skin(
+                    joints.into_u16().map(|joints| UVec4::from_array(joints.map(u32::from))).collect(),
+                    weights.into_f32().map(Vec4::from).collect(),
+                );
+            }
+            meshes.push(builder.build(device));
+        }
+    }
+    // Meshes without a material share a plain white one.
+    if meshes.iter().any(|mesh| mesh.material == materials.len()) {
+        let textures = model::MaterialTextures::plain(device, queue, "default")?;
+        materials.push(model::ModelMaterial::with_textures(device, "default", textures, model::MaterialFactors::default(), layout));
+    }
+
+    let mut clips = Vec::new();
+    for animation in document.animations() {
+        let mut channels = Vec::new();
+        let mut weight_channels = 0;
+        for channel in animation.channels() {
+            if channel.target().property() == gltf::animation::Property::MorphTargetWeights {
+                weight_channels += 1;
+                continue;
+            }
+            let Some(&joint) = joint_of_node.get(&channel.target().node().index()) else {
+                continue;
+            };
+            let reader = channel.reader(buffer_data);
+            let (Some(times), Some(outputs)) = (reader.read_inputs(), reader.read_outputs()) else {
+                continue;
+            };
+            let times = times.collect::<Vec<_>>();
+            let interpolation = match channel.sampler().interpolation() {
+                gltf::animation::Interpolation::Step => skin::Interpolation::Step,
+                gltf::animation::Interpolation::Linear => skin::Interpolation::Linear,
+                gltf::animation::Interpolation::CubicSpline => skin::Interpolation::CubicSpline,
+            };
+            let values = match outputs {
+                gltf::animation::util::ReadOutputs::Translations(values) => {
+                    ChannelValues::Translation(keyframes(file_name, times, values.map(Vec3::from).collect(), interpolation)?)
+                }
+                gltf::animation::util::ReadOutputs::Rotations(values) => {
+                    ChannelValues::Rotation(keyframes(file_name, times, values.into_f32().map(Quat::from_array).collect(), interpolation)?)
+                }
+                gltf::animation::util::ReadOutputs::Scales(values) => {
+                    ChannelValues::Scale(keyframes(file_name, times, values.map(Vec3::from).collect(), interpolation)?)
+                }
+                // Counted above.
+                gltf::animation::util::ReadOutputs::MorphTargetWeights(_) => continue,
+            };
+            channels.push(Channel { joint, values });
+        }
+        if weight_channels > 0 {
+            let name = animation.name().unwrap_or(file_name);
+            log::warn!("{file_name}: skipping {weight_channels} morph target weight channels of {name}, set the weights with MorphBuffer::set_weights instead");
+        }
+        if !channels.is_empty() {
+            clips.push(AnimationClip::new(animation.name().unwrap_or(file_name), channels));
+        }
+    }
+
+    let model = model::MatModel { meshes, materials };
+    warn_about_problems(file_name, &model);
+    Ok(SkinnedModel { model, skeleton, clips })
+}
 
-            for (i, n) in triangles_included.into_iter().enumerate() {
-                let denom = 1.0 / n as f32;
-                let v = &mut vertices[i];
-                v.tangent = (Vec3::from(v.tangent) * denom).into();
-                v.bitangent = (Vec3::from(v.bitangent) * denom).into();
+/// Keyframes of an animation channel, checking that the sampler has a value, or three for cubic
+/// splines, for every time.
+fn keyframes<T: Keyframe>(file_name: &str, times: Vec<f32>, values: Vec<T>, interpolation: skin::Interpolation) -> anyhow::Result<Keyframes<T>> {
+    let expected = times.len() * interpolation.values_per_key();
+    if values.len() != expected {
+        bail!("{file_name}: an animation sampler has {} values for {} {interpolation:?} keyframes instead of {expected}", values.len(), times.len());
+    }
+    Ok(Keyframes::new(times, values, interpolation))
+}
+
+/// Loads the meshes of an OBJ file with their faces left as polygons, to use as subdivision cages.
+pub fn load_polygon_meshes(file_name: &str) -> anyhow::Result<Vec<PolygonMesh>> {
+    let obj_text = load_string(file_name)?;
+    let (models, _) = tobj::load_obj_buf(
+        &mut BufReader::new(Cursor::new(obj_text)),
+        &tobj::LoadOptions {
+            single_index: true,
+            ..Default::default()
+        },
+        // Only the shape is kept, so the materials are not even loaded.
+        |_| Ok(Default::default()),
+    )?;
+
+    Ok(models
+        .into_iter()
+        .map(|m| {
+            // Files with nothing but triangles leave the arities out.
+            let arities = if m.mesh.face_arities.is_empty() {
+                vec![3; m.mesh.indices.len() / 3]
+            } else {
+                m.mesh.face_arities
+            };
+            let mut indices = m.mesh.indices.into_iter();
+            PolygonMesh {
+                positions: m.mesh.positions.chunks(3).map(|p| vec3(p[0], p[1], p[2])).collect(),
+                tex_coords: m.mesh.texcoords.chunks(2).map(|t| vec2(t[0], 1.0 - t[1])).collect(),
+                faces: arities.iter().map(|&arity| indices.by_ref().take(arity as usize).collect()).collect(),
             }
+        })
+        .collect())
+}
 
-            let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{:?} Vertex Buffer", file_name)),
-                contents: bytemuck::cast_slice(&vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
-            let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{:?} Index Buffer", file_name)),
-                contents: bytemuck::cast_slice(&m.mesh.indices),
-                usage: wgpu::BufferUsages::INDEX,
-            });
-
-            model::Mesh {
-                name: file_name.to_string(),
-                vertex_buffer,
-                index_buffer,
-                num_elements: m.mesh.indices.len() as u32,
-                material: m.mesh.material_id.unwrap_or(0),
+/// Checks every mesh before uploading it, so problems are reported whether or not the data is
+/// kept.
+fn get_meshes(models: Vec<Model>, retain_data: bool, device: &wgpu::Device, file_name: &str) -> Vec<Mesh> {
+    models
+        .into_iter()
+        .map(|m| {
+            let positions = m.mesh.positions
+                .chunks(3)
+                .map(|p| vec3(p[0], p[1], p[2]))
+                .collect::<Vec<_>>();
+            let mut builder = MeshBuilder::<model::ModelVertex>::new(file_name, positions)
+                .indices(m.mesh.indices)
+                .optimize(true);
+            if !m.mesh.texcoords.is_empty() {
+                builder = builder.tex_coords(
+                    m.mesh.texcoords
+                        .chunks(2)
+                        .map(|t| vec2(t[0], 1.0 - t[1]))
+                        .collect(),
+                );
+            }
+            if !m.mesh.normals.is_empty() {
+                builder = builder.normals(
+                    m.mesh.normals
+                        .chunks(3)
+                        .map(|n| vec3(n[0], n[1], n[2]))
+                        .collect(),
+                );
             }
+            let data = builder.build_data();
+            warn_about_mesh(file_name, &m.name, &validation::validate(&data));
+            MeshBuilder::<model::ModelVertex>::from_data(file_name, data)
+                .material(m.mesh.material_id.unwrap_or(0))
+                .retain_data(retain_data)
+                .build(device)
         })
         .collect::<Vec<_>>()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyframes_with_missing_values_are_an_error() {
+        let times = vec![0.0, 1.0];
+        assert!(keyframes("test.gltf", times.clone(), vec![Vec3::ZERO, Vec3::ONE], skin::Interpolation::CubicSpline).is_err());
+        assert!(keyframes("test.gltf", times.clone(), vec![Vec3::ZERO], skin::Interpolation::Linear).is_err());
+        assert!(keyframes("test.gltf", times, vec![Vec3::ZERO, Vec3::ONE], skin::Interpolation::Linear).is_ok());
+    }
+}
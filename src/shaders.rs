@@ -0,0 +1,93 @@
+//! The lit shaders, put together from the declarations in `lit_common.wgsl` and the stages of
+//! each shader. The Blinn-Phong shaders share `blinn_phong.wgsl` and add a vertex shader for
+//! each kind of vertex. Pass them to [`crate::window::create_render_pipeline`] with the matching
+//! [`crate::model::Vertex::desc`].
+
+/// For [`crate::model::ModelVertex`] meshes.
+pub const LIT: &str = concat!(include_str!("lit_common.wgsl"), include_str!("blinn_phong.wgsl"), include_str!("lit.wgsl"));
+
+/// For [`crate::skin::SkinnedVertex`] meshes, with a [`crate::skin::JointBuffer`] in group 3.
+pub const LIT_SKINNED: &str = concat!(include_str!("lit_common.wgsl"), include_str!("blinn_phong.wgsl"), include_str!("lit_skinned.wgsl"));
+
+/// For meshes with a [`crate::morph::MorphBuffer`] in group 3, either
+/// [`crate::model::ModelVertex`] or the [`crate::skin::SkinnedVertex`] meshes of
+/// [`crate::resources::load_skinned_model`]. The joints are left out, so meshes that are both
+/// skinned and morphed need [`crate::morph::apply_morph_targets`] and [`LIT_SKINNED`] instead.
+pub const LIT_MORPH: &str = concat!(include_str!("lit_common.wgsl"), include_str!("blinn_phong.wgsl"), include_str!("lit_morph.wgsl"));
+
+/// For [`crate::model::ModelVertex`] meshes with metallic-roughness materials, lit by a
+/// Cook-Torrance GGX BRDF.
+pub const PBR: &str = concat!(include_str!("lit_common.wgsl"), include_str!("pbr.wgsl"));
+
+/// For [`crate::voxel::VoxelVertex`] meshes, with their material holding the texture atlas.
+pub const VOXEL: &str = concat!(include_str!("lit_common.wgsl"), include_str!("voxel.wgsl"));
+
+/// For [`crate::model::ColorVertex`] meshes such as function plots, lit from above and from the
+/// eye rather than by the light so that every side reads.
+pub const PLOT: &str = concat!(include_str!("lit_common.wgsl"), include_str!("plot.wgsl"));
+
+pub fn lit() -> wgpu::ShaderModuleDescriptor<'static> {
+    wgpu::ShaderModuleDescriptor {
+        label: Some("Lit Shader"),
+        source: wgpu::ShaderSource::Wgsl(LIT.into()),
+    }
+}
+
+pub fn lit_skinned() -> wgpu::ShaderModuleDescriptor<'static> {
+    wgpu::ShaderModuleDescriptor {
+        label: Some("Lit Skinned Shader"),
+        source: wgpu::ShaderSource::Wgsl(LIT_SKINNED.into()),
+    }
+}
+
+pub fn lit_morph() -> wgpu::ShaderModuleDescriptor<'static> {
+    wgpu::ShaderModuleDescriptor {
+        label: Some("Lit Morph Shader"),
+        source: wgpu::ShaderSource::Wgsl(LIT_MORPH.into()),
+    }
+}
+
+pub fn pbr() -> wgpu::ShaderModuleDescriptor<'static> {
+    wgpu::ShaderModuleDescriptor {
+        label: Some("PBR Shader"),
+        source: wgpu::ShaderSource::Wgsl(PBR.into()),
+    }
+}
+
+pub fn voxel() -> wgpu::ShaderModuleDescriptor<'static> {
+    wgpu::ShaderModuleDescriptor {
+        label: Some("Voxel Shader"),
+        source: wgpu::ShaderSource::Wgsl(VOXEL.into()),
+    }
+}
+
+pub fn plot() -> wgpu::ShaderModuleDescriptor<'static> {
+    wgpu::ShaderModuleDescriptor {
+        label: Some("Plot Shader"),
+        source: wgpu::ShaderSource::Wgsl(PLOT.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(name: &str, source: &str) {
+        let module = naga::front::wgsl::parse_str(source).unwrap_or_else(|error| panic!("{name}: {}", error.emit_to_string(source)));
+        naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+            .validate(&module)
+            .unwrap_or_else(|error| panic!("{name}: {error:?}"));
+    }
+
+    #[test]
+    fn shaders_compile() {
+        validate("lit", LIT);
+        validate("lit_skinned", LIT_SKINNED);
+        validate("lit_morph", LIT_MORPH);
+        validate("pbr", PBR);
+        validate("light", include_str!("light.wgsl"));
+        validate("lines", include_str!("lines.wgsl"));
+        validate("plot", PLOT);
+        validate("voxel", VOXEL);
+    }
+}
@@ -0,0 +1,239 @@
+use std::{collections::HashMap, sync::OnceLock};
+use glam::{uvec3, UVec3, Vec2, Vec3};
+use crate::{bounds::Aabb, mesh_data::MeshData, model::Mesh, normals::{self, NormalWeighting}};
+use super::mesh;
+
+/// How close to a grid point vertices may get, as a part of the cube edge. Surfaces passing
+/// right by a grid point would otherwise leave slivers with no area around it.
+const EDGE_MARGIN: f32 = 0.02;
+
+/// A scalar field to extract a surface from.
+pub enum ScalarField<'a> {
+    /// A function of the position, sampled at the corners of a grid with this many cubes
+    /// along each axis.
+    Function(&'a dyn Fn(Vec3) -> f32, UVec3),
+    /// Values at the points of a grid with these dimensions, with x changing fastest and z
+    /// slowest. The grid is stretched across the bounds.
+    Grid(&'a [f32], UVec3),
+}
+
+/// Corner `i` of a cube is offset by bit 0 of `i` along x, bit 1 along y and bit 2 along z.
+fn corner_offset(corner: u8) -> UVec3 {
+    uvec3(corner as u32 & 1, (corner as u32 >> 1) & 1, (corner as u32 >> 2) & 1)
+}
+
+/// The corners at the ends of the 12 cube edges.
+fn cube_edges() -> Vec<(u8, u8)> {
+    [1, 2, 4]
+        .into_iter()
+        .flat_map(|bit| (0..8).filter(move |corner| corner & bit == 0).map(move |corner| (corner, corner | bit)))
+        .collect()
+}
+
+/// The triangles of every combination of inside corners as triples of cube edges. Instead of
+/// the usual hand written table, the contour of the inside corners is traced on each face of
+/// the cube and the segments are linked into loops. Faces with two diagonal inside corners
+/// always keep them apart, so neighbouring cubes agree on the faces they share and the surface
+/// has no holes.
+fn case_table() -> &'static [Vec<[u8; 3]>] {
+    static TABLE: OnceLock<Vec<Vec<[u8; 3]>>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let edges = cube_edges();
+        let edge_between = |a: u8, b: u8| edges.iter().position(|&edge| edge == (a.min(b), a.max(b))).unwrap() as u8;
+        // The corners of every face, counter-clockwise when seen from outside the cube.
+        let faces = (0..3).flat_map(|axis| {
+            [0, 1].map(|side| {
+                let (u, v) = (1 << ((axis + 1) % 3), 1 << ((axis + 2) % 3));
+                let base = side << axis;
+                let mut corners = [base, base | u, base | u | v, base | v];
+                if side == 0 {
+                    corners.reverse();
+                }
+                corners
+            })
+        }).collect::<Vec<_>>();
+
+        (0..=255u8)
+            .map(|case| {
+                let inside = |corner: u8| case & (1 << corner) != 0;
+                // Each segment runs from where the contour leaves the inside corners to where it
+                // came in, which keeps the inside on its left when seen from outside.
+                let mut next = HashMap::new();
+                for face in &faces {
+                    let crossings = (0..4)
+                        .filter(|&i| inside(face[i]) != inside(face[(i + 1) % 4]))
+                        .map(|i| (edge_between(face[i], face[(i + 1) % 4]), inside(face[i])))
+                        .collect::<Vec<_>>();
+                    for (i, &(edge, leaving)) in crossings.iter().enumerate() {
+                        if leaving {
+                            let (entering, _) = crossings[(i + crossings.len() - 1) % crossings.len()];
+                            next.insert(edge, entering);
+                        }
+                    }
+                }
+
+                let mut triangles = vec![];
+                while let Some(&start) = next.keys().min() {
+                    let mut polygon = vec![start];
+                    let mut edge = next.remove(&start).unwrap();
+                    while edge != start {
+                        polygon.push(edge);
+                        edge = next.remove(&edge).unwrap();
+                    }
+                    // A diagonal lying in a face of the cube could be made by the neighbouring
+                    // cube too, so the fan starts where none of its diagonals do.
+                    let on_same_face = |a: u8, b: u8| {
+                        let ((a0, a1), (b0, b1)) = (edges[a as usize], edges[b as usize]);
+                        !(a0 ^ a1) & !(b0 ^ b1) & !(a0 ^ b0) & 7 != 0
+                    };
+                    let count = polygon.len();
+                    let apex = (0..count)
+                        .find(|&k| (2..count - 1).all(|j| !on_same_face(polygon[k], polygon[(k + j) % count])))
+                        .unwrap_or(0);
+                    polygon.rotate_left(apex);
+                    for i in 1..count - 1 {
+                        triangles.push([polygon[0], polygon[i + 1], polygon[i]]);
+                    }
+                }
+                triangles
+            })
+            .collect()
+    })
+}
+
+/// Extracts the surface where the field equals `iso_level` with marching cubes. Values above
+/// the iso level are inside, like a density, so negate signed distances. Vertices on the same
+/// cube edge are shared between cubes and the normals follow the gradient of the field.
+pub fn isosurface_data(field: ScalarField, bounds: Aabb, iso_level: f32) -> MeshData {
+    let (values, dimensions) = match field {
+        ScalarField::Function(function, resolution) => {
+            let dimensions = resolution.max(UVec3::ONE) + 1;
+            let step = bounds.size() / (dimensions - 1).as_vec3();
+            let values = (0..dimensions.z)
+                .flat_map(|z| (0..dimensions.y).flat_map(move |y| (0..dimensions.x).map(move |x| uvec3(x, y, z))))
+                .map(|point| function(bounds.min + point.as_vec3() * step))
+                .collect::<Vec<_>>();
+            (values, dimensions)
+        }
+        ScalarField::Grid(values, dimensions) => (values.to_vec(), dimensions),
+    };
+    if dimensions.cmplt(UVec3::splat(2)).any() || values.len() < (dimensions.x * dimensions.y * dimensions.z) as usize {
+        return MeshData::default();
+    }
+
+    let step = bounds.size() / (dimensions - 1).as_vec3();
+    let index = |point: UVec3| (point.x + dimensions.x * (point.y + dimensions.y * point.z)) as usize;
+    let value = |point: UVec3| values[index(point.min(dimensions - 1))];
+    let gradient = |point: UVec3| {
+        let axis = |axis: UVec3, step: f32| {
+            let (low, high) = (point.saturating_sub(axis), (point + axis).min(dimensions - 1));
+            (value(high) - value(low)) / ((high - low).max_element().max(1) as f32 * step)
+        };
+        Vec3::new(axis(UVec3::X, step.x), axis(UVec3::Y, step.y), axis(UVec3::Z, step.z))
+    };
+
+    let edges = cube_edges();
+    let table = case_table();
+    let mut data = MeshData::default();
+    let mut vertices = HashMap::new();
+    for z in 0..dimensions.z - 1 {
+        for y in 0..dimensions.y - 1 {
+            for x in 0..dimensions.x - 1 {
+                let cube = uvec3(x, y, z);
+                let case = (0..8).fold(0, |case, corner| {
+                    case | (((value(cube + corner_offset(corner)) > iso_level) as usize) << corner)
+                });
+                for triangle in &table[case] {
+                    let corners = triangle.map(|edge| {
+                        let (a, b) = edges[edge as usize];
+                        let (a, b) = (cube + corner_offset(a), cube + corner_offset(b));
+                        *vertices.entry((index(a), index(b))).or_insert_with(|| {
+                            let (value_a, value_b) = (value(a), value(b));
+                            let t = if value_a == value_b { 0.5 } else { ((iso_level - value_a) / (value_b - value_a)).clamp(EDGE_MARGIN, 1.0 - EDGE_MARGIN) };
+                            let position = bounds.min + a.as_vec3().lerp(b.as_vec3(), t) * step;
+                            data.positions.push(position);
+                            data.normals.push(-gradient(a).lerp(gradient(b), t));
+                            data.positions.len() as u32 - 1
+                        })
+                    });
+                    data.indices.extend(corners);
+                }
+            }
+        }
+    }
+
+    // Flat spots in the field have no gradient, so those vertices fall back to the faces around them.
+    let face_normals = normals::vertex_normals(&data.positions, &data.indices, NormalWeighting::Area);
+    for (normal, face_normal) in data.normals.iter_mut().zip(face_normals) {
+        *normal = normal.normalize_or(face_normal);
+    }
+    // There are no texture coordinates, so the tangents only need to be perpendicular to the normals.
+    data.tex_coords = vec![Vec2::ZERO; data.positions.len()];
+    data.tangents = data
+        .normals
+        .iter()
+        .map(|normal| {
+            let axis = if normal.x.abs() < 0.9 { Vec3::X } else { Vec3::Z };
+            axis.reject_from(*normal).normalize_or(normal.any_orthonormal_vector()).extend(1.0)
+        })
+        .collect();
+    data
+}
+
+pub fn isosurface(field: ScalarField, bounds: Aabb, iso_level: f32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Isosurface", isosurface_data(field, bounds, iso_level), material, device)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use crate::validation::validate;
+    use super::*;
+
+    /// Checks the surface is closed, consistently wound and faces away from the inside.
+    fn assert_closed(data: &MeshData, name: &str) {
+        let report = validate(data);
+        assert_eq!(report.boundary_edges, 0, "{name}: {report}");
+        assert!(!report.has_errors(), "{name}: {report}");
+        assert!(data.volume() > 0.0, "{name} faces inward");
+    }
+
+    #[test]
+    fn sphere_is_closed_and_keeps_its_volume() {
+        let sphere = |position: Vec3| 1.0 - position.length();
+        let bounds = Aabb::new(Vec3::splat(-1.5), Vec3::splat(1.5));
+        let data = isosurface_data(ScalarField::Function(&sphere, UVec3::splat(32)), bounds, 0.0);
+        assert_closed(&data, "sphere");
+        let expected = 4.0 / 3.0 * PI;
+        assert!((data.volume() - expected).abs() < 0.03 * expected, "volume {} instead of {expected}", data.volume());
+    }
+
+    #[test]
+    fn metaballs_are_closed() {
+        let centers = [Vec3::new(-0.6, 0.0, 0.0), Vec3::new(0.6, 0.1, 0.0), Vec3::new(0.0, 0.7, 0.3)];
+        let metaballs = |position: Vec3| centers.iter().map(|center| 0.3 / position.distance_squared(*center).max(1e-6)).sum::<f32>();
+        let bounds = Aabb::new(Vec3::splat(-2.0), Vec3::splat(2.0));
+        let data = isosurface_data(ScalarField::Function(&metaballs, UVec3::splat(40)), bounds, 1.0);
+        assert_closed(&data, "metaballs");
+    }
+
+    #[test]
+    fn every_case_is_closed() {
+        // The middle cube of a 3x3x3 grid of cubes takes each case in turn. The grid points
+        // around it stay outside so the surface closes, and the winding is checked by the
+        // edges and the sign of the volume.
+        let bounds = Aabb::new(Vec3::ZERO, Vec3::splat(3.0));
+        for case in 1..=255u8 {
+            let mut values = vec![-1.0; 64];
+            for corner in (0..8).filter(|corner| case & (1 << corner) != 0) {
+                let point = corner_offset(corner) + 1;
+                values[(point.x + 4 * (point.y + 4 * point.z)) as usize] = 1.0;
+            }
+            let data = isosurface_data(ScalarField::Grid(&values, UVec3::splat(4)), bounds, 0.0);
+            // The normals of a field this coarse are too rough to check the faces against.
+            let report = validate(&data);
+            assert_eq!(report.boundary_edges + report.non_manifold_edges + report.inconsistent_edges, 0, "case {case:#010b}: {report}");
+            assert!(data.volume() > 0.0, "case {case:#010b} faces inward");
+        }
+    }
+}
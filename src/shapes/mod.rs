@@ -0,0 +1,176 @@
+use std::f32::consts::TAU;
+use glam::{vec2, vec3, Vec2, Vec3};
+use crate::{builder::MeshBuilder, mesh_data::MeshData, model::{FromAttributes, Mesh, ModelVertex, Vertex, VertexAttributes}, tangents};
+
+mod isosurface;
+mod parametric;
+mod polygon;
+mod primitives;
+mod sweep;
+
+pub use isosurface::*;
+pub use parametric::*;
+pub use polygon::*;
+pub use primitives::*;
+pub use sweep::*;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SimpleVertex {
+    pub position: Vec3,
+    pub tex_coords: Vec2,
+}
+
+impl FromAttributes for SimpleVertex {
+    fn from_attributes(attributes: VertexAttributes) -> Self {
+        Self { position: attributes.position, tex_coords: attributes.tex_coords }
+    }
+}
+
+impl Vertex for SimpleVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<SimpleVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+            ],
+        }
+    }
+}
+
+/// Accumulates the vertices and triangles of a shape before tangents are generated.
+#[derive(Default)]
+pub(crate) struct Geometry {
+    positions: Vec<Vec3>,
+    tex_coords: Vec<Vec2>,
+    normals: Vec<Vec3>,
+    indices: Vec<u32>,
+}
+
+impl Geometry {
+    pub(crate) fn vertex(&mut self, position: Vec3, tex_coords: Vec2, normal: Vec3) -> u32 {
+        self.positions.push(position);
+        self.tex_coords.push(tex_coords);
+        self.normals.push(normal);
+        self.positions.len() as u32 - 1
+    }
+
+    pub(crate) fn num_vertices(&self) -> u32 {
+        self.positions.len() as u32
+    }
+
+    /// Adds a triangle wound counter-clockwise around its vertex normals, whatever the order
+    /// of the arguments. Degenerate triangles are skipped.
+    pub(crate) fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        let normal = self.normals[a as usize] + self.normals[b as usize] + self.normals[c as usize];
+        self.triangle_facing(a, b, c, normal);
+    }
+
+    /// Like [`Geometry::triangle`], but wound to face `direction` instead.
+    pub(crate) fn triangle_facing(&mut self, a: u32, b: u32, c: u32, direction: Vec3) {
+        let [pa, pb, pc] = [a, b, c].map(|i| self.positions[i as usize]);
+        let cross = (pb - pa).cross(pc - pa);
+        // Relative to the edges, so small shapes lose only their truly flat triangles.
+        if cross.length_squared() <= f32::EPSILON * (pb - pa).length_squared() * (pc - pa).length_squared() {
+            return;
+        }
+        if cross.dot(direction) < 0.0 {
+            self.indices.extend([a, c, b]);
+        } else {
+            self.indices.extend([a, b, c]);
+        }
+    }
+
+    pub(crate) fn quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+        self.triangle(a, b, c);
+        self.triangle(a, c, d);
+    }
+
+    /// Adds a `columns` by `rows` grid of quads. `vertex` is called with every grid point
+    /// `(column, row)` and returns its position, texture coordinates and normal.
+    pub(crate) fn grid(&mut self, columns: u32, rows: u32, vertex: impl Fn(u32, u32) -> (Vec3, Vec2, Vec3)) {
+        let first = self.positions.len() as u32;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let (position, tex_coords, normal) = vertex(column, row);
+                self.vertex(position, tex_coords, normal);
+            }
+        }
+        let index = |column: u32, row: u32| first + row * (columns + 1) + column;
+        for row in 0..rows {
+            for column in 0..columns {
+                self.quad(index(column, row), index(column, row + 1), index(column + 1, row + 1), index(column + 1, row));
+            }
+        }
+    }
+
+    pub(crate) fn into_data(self) -> MeshData {
+        let generated = tangents::generate_tangents(&self.positions, &self.normals, &self.tex_coords, &self.indices);
+        MeshData {
+            positions: generated.remap_attribute(&self.positions),
+            tex_coords: generated.remap_attribute(&self.tex_coords),
+            normals: generated.remap_attribute(&self.normals),
+            tangents: generated.tangents,
+            indices: generated.indices,
+            ..Default::default()
+        }
+    }
+}
+
+pub(crate) fn mesh(name: &str, data: MeshData, material: usize, device: &wgpu::Device) -> Mesh {
+    MeshBuilder::<ModelVertex>::from_data(name, data)
+        .material(material)
+        .build(device)
+}
+
+/// The horizontal direction at `angle` around the y axis, starting at +z and turning towards +x.
+fn around(angle: f32) -> Vec3 {
+    vec3(angle.sin(), 0.0, angle.cos())
+}
+
+fn direction(angle: f32) -> Vec2 {
+    vec2(angle.cos(), angle.sin())
+}
+
+/// A disc in the xy plane facing +z.
+pub fn circle_data(num_points: u32, radius: f32) -> MeshData {
+    let mut geometry = Geometry::default();
+    let center = geometry.vertex(Vec3::ZERO, vec2(0.5, 0.5), Vec3::Z);
+    let angle = TAU / num_points as f32;
+    for i in 0..num_points {
+        let vector = direction(angle * i as f32);
+        geometry.vertex(vec3(vector.x, vector.y, 0.0) * radius, vec2(0.5 + 0.5 * vector.x, 0.5 - 0.5 * vector.y), Vec3::Z);
+    }
+    for i in 0..num_points {
+        geometry.triangle(center, center + 1 + i, center + 1 + (i + 1) % num_points);
+    }
+    geometry.into_data()
+}
+
+pub fn circle(num_points: u32, radius: f32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Circle", circle_data(num_points, radius), material, device)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiny_shapes_keep_their_triangles() {
+        // The two rows at the poles lose one of each quad's triangles.
+        let expected = 16 * 8 * 2 - 2 * 16;
+        assert_eq!(uv_sphere_data(1.0, 16, 8).num_triangles(), expected);
+        assert_eq!(uv_sphere_data(0.001, 16, 8).num_triangles(), expected);
+    }
+}
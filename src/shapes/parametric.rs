@@ -0,0 +1,98 @@
+use std::ops::Range;
+use glam::{vec2, vec3, UVec2, Vec2, Vec3};
+use crate::{colormap::Colormap, mesh_data::MeshData, model::Mesh};
+use super::{mesh, Geometry};
+
+/// Samples a surface over a grid with `segments` quads across the `u` and `v` ranges. The
+/// normals come from `normal` when given and from finite differences otherwise, facing along
+/// `∂P/∂u × ∂P/∂v` either way. The texture runs along `u` and up along `v` across the ranges.
+pub fn parametric_surface_data(
+    surface: &dyn Fn(f32, f32) -> Vec3,
+    normal: Option<&dyn Fn(f32, f32) -> Vec3>,
+    u_range: Range<f32>,
+    v_range: Range<f32>,
+    segments: UVec2,
+) -> MeshData {
+    let segments = segments.max(UVec2::ONE);
+    let start = vec2(u_range.start, v_range.start);
+    let step = vec2(u_range.end - u_range.start, v_range.end - v_range.start) / segments.as_vec2();
+    let center = start + step * segments.as_vec2() * 0.5;
+    let delta = step * 0.05;
+    // Zero where one of the derivatives collapses, like at the poles of a sphere.
+    let finite_difference = |point: Vec2| {
+        let du = (surface(point.x + delta.x, point.y) - surface(point.x - delta.x, point.y)) / delta.x;
+        let dv = (surface(point.x, point.y + delta.y) - surface(point.x, point.y - delta.y)) / delta.y;
+        let (du_length, dv_length) = (du.length(), dv.length());
+        if du_length.min(dv_length) <= du_length.max(dv_length) * 1e-3 {
+            return Vec3::ZERO;
+        }
+        du.cross(dv).normalize_or_zero()
+    };
+
+    let mut geometry = Geometry::default();
+    geometry.grid(segments.x, segments.y, |column, row| {
+        let point = start + vec2(column as f32, row as f32) * step;
+        let mut direction = normal.map_or(Vec3::ZERO, |normal| normal(point.x, point.y).normalize_or_zero());
+        if direction == Vec3::ZERO {
+            direction = finite_difference(point);
+        }
+        // Such points have no normal of their own, so one is taken from just inside the domain.
+        if direction == Vec3::ZERO {
+            direction = finite_difference(point + (center - point).signum() * delta);
+        }
+        let tex_coords = vec2(column as f32 / segments.x as f32, 1.0 - row as f32 / segments.y as f32);
+        (surface(point.x, point.y), tex_coords, direction)
+    });
+    geometry.into_data()
+}
+
+pub fn parametric_surface(
+    surface: &dyn Fn(f32, f32) -> Vec3,
+    normal: Option<&dyn Fn(f32, f32) -> Vec3>,
+    u_range: Range<f32>,
+    v_range: Range<f32>,
+    segments: UVec2,
+    material: usize,
+    device: &wgpu::Device,
+) -> Mesh {
+    mesh("Parametric Surface", parametric_surface_data(surface, normal, u_range, v_range, segments), material, device)
+}
+
+/// Plots `z = f(x, y)` over a domain. The renderer is y up, so the plot lies in the xz plane
+/// with the value along +y and the y axis of the domain along -z. `gradient` returns
+/// `(∂f/∂x, ∂f/∂y)` for analytic normals. A colormap colors the vertices from the lowest
+/// value to the highest.
+pub fn function_plot_data(
+    function: &dyn Fn(f32, f32) -> f32,
+    gradient: Option<&dyn Fn(f32, f32) -> Vec2>,
+    x_range: Range<f32>,
+    y_range: Range<f32>,
+    segments: UVec2,
+    colormap: Option<Colormap>,
+) -> MeshData {
+    let surface = |x: f32, y: f32| vec3(x, function(x, y), -y);
+    let normal = |x: f32, y: f32| {
+        let slope = gradient.map_or(Vec2::ZERO, |gradient| gradient(x, y));
+        vec3(-slope.x, 1.0, slope.y)
+    };
+    let normal = gradient.is_some().then_some(&normal as &dyn Fn(f32, f32) -> Vec3);
+    let mut data = parametric_surface_data(&surface, normal, x_range, y_range, segments);
+    if let Some(colormap) = colormap {
+        colormap.color_vertices(&mut data, None, |position| position.y);
+    }
+    data
+}
+
+/// Use [`function_plot_data`] with a colormap and a [`crate::model::ColorVertex`] mesh for
+/// vertex colors.
+pub fn function_plot(
+    function: &dyn Fn(f32, f32) -> f32,
+    gradient: Option<&dyn Fn(f32, f32) -> Vec2>,
+    x_range: Range<f32>,
+    y_range: Range<f32>,
+    segments: UVec2,
+    material: usize,
+    device: &wgpu::Device,
+) -> Mesh {
+    mesh("Function Plot", function_plot_data(function, gradient, x_range, y_range, segments, None), material, device)
+}
@@ -0,0 +1,124 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+use glam::{vec2, vec3, Vec2, Vec3};
+use crate::{bounds::Aabb, mesh_data::MeshData, model::Mesh, triangulate};
+use super::{mesh, Geometry};
+
+/// Maps the xy plane onto the texture so the bounding box of the shape covers it once.
+fn planar_tex_coords(point: Vec2, bounds: Aabb) -> Vec2 {
+    let size = bounds.size().truncate().max(Vec2::splat(f32::EPSILON));
+    vec2((point.x - bounds.min.x) / size.x, (bounds.max.y - point.y) / size.y)
+}
+
+/// Triangulates a polygon with holes placed in 3D by `transform`. The texture is mirrored
+/// horizontally when `mirror` is set, for caps that are seen from behind the xy plane.
+pub(super) fn fill(geometry: &mut Geometry, outer: &[Vec2], holes: &[Vec<Vec2>], normal: Vec3, mirror: bool, transform: impl Fn(Vec2) -> Vec3) {
+    let Some(bounds) = Aabb::from_points(&outer.iter().map(|p| p.extend(0.0)).collect::<Vec<_>>()) else {
+        return;
+    };
+    let first = outer.iter().chain(holes.iter().flatten()).fold(None, |first, &point| {
+        let mut tex_coords = planar_tex_coords(point, bounds);
+        if mirror {
+            tex_coords.x = 1.0 - tex_coords.x;
+        }
+        let index = geometry.vertex(transform(point), tex_coords, normal);
+        first.or(Some(index))
+    });
+    let Some(first) = first else {
+        return;
+    };
+    for triangle in triangulate::triangulate(outer, holes).chunks(3) {
+        geometry.triangle(first + triangle[0], first + triangle[1], first + triangle[2]);
+    }
+}
+
+/// A flat polygon with holes in the xy plane facing +z. The outline must not intersect itself,
+/// but can be concave and wound either way.
+pub fn polygon_data(outer: &[Vec2], holes: &[Vec<Vec2>]) -> MeshData {
+    let mut geometry = Geometry::default();
+    fill(&mut geometry, outer, holes, Vec3::Z, false, |point| point.extend(0.0));
+    geometry.into_data()
+}
+
+pub fn polygon(outer: &[Vec2], holes: &[Vec<Vec2>], material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Polygon", polygon_data(outer, holes), material, device)
+}
+
+pub fn rectangle_data(size: Vec2) -> MeshData {
+    let half = size * 0.5;
+    polygon_data(&[vec2(-half.x, -half.y), vec2(half.x, -half.y), half, vec2(-half.x, half.y)], &[])
+}
+
+pub fn rectangle(size: Vec2, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Rectangle", rectangle_data(size), material, device)
+}
+
+/// The outline of a rectangle centered on the origin with rounded corners, counter-clockwise.
+pub fn rounded_rectangle_outline(size: Vec2, radius: f32, segments: u32) -> Vec<Vec2> {
+    let half = size * 0.5;
+    let radius = radius.clamp(0.0, half.min_element());
+    let inner = half - radius;
+    let corners = [inner, vec2(-inner.x, inner.y), -inner, vec2(inner.x, -inner.y)];
+    corners
+        .iter()
+        .enumerate()
+        .flat_map(|(i, &corner)| {
+            (0..=segments).map(move |k| {
+                let angle = (i as f32 + k as f32 / segments.max(1) as f32) * FRAC_PI_2;
+                corner + vec2(angle.cos(), angle.sin()) * radius
+            })
+        })
+        .collect()
+}
+
+pub fn rounded_rectangle_data(size: Vec2, radius: f32, segments: u32) -> MeshData {
+    polygon_data(&rounded_rectangle_outline(size, radius, segments), &[])
+}
+
+pub fn rounded_rectangle(size: Vec2, radius: f32, segments: u32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Rounded Rectangle", rounded_rectangle_data(size, radius, segments), material, device)
+}
+
+/// The corners of a regular polygon with its first corner on +y, counter-clockwise.
+pub fn regular_polygon_outline(sides: u32, radius: f32) -> Vec<Vec2> {
+    (0..sides.max(3))
+        .map(|i| {
+            let angle = FRAC_PI_2 + i as f32 / sides.max(3) as f32 * TAU;
+            vec2(angle.cos(), angle.sin()) * radius
+        })
+        .collect()
+}
+
+pub fn regular_polygon_data(sides: u32, radius: f32) -> MeshData {
+    polygon_data(&regular_polygon_outline(sides, radius), &[])
+}
+
+pub fn regular_polygon(sides: u32, radius: f32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Regular Polygon", regular_polygon_data(sides, radius), material, device)
+}
+
+/// A band between two radii from `start_angle` to `end_angle`, in radians counter-clockwise from +x.
+/// An `inner_radius` of zero gives a pie slice.
+pub fn arc_data(inner_radius: f32, outer_radius: f32, start_angle: f32, end_angle: f32, segments: u32) -> MeshData {
+    let segments = segments.max(1);
+    let bounds = Aabb::new(Vec3::splat(-outer_radius), Vec3::splat(outer_radius));
+    let mut geometry = Geometry::default();
+    geometry.grid(segments, 1, |column, row| {
+        let angle = start_angle + (end_angle - start_angle) * column as f32 / segments as f32;
+        let radius = if row == 0 { outer_radius } else { inner_radius };
+        let point = vec2(angle.cos(), angle.sin()) * radius;
+        (vec3(point.x, point.y, 0.0), planar_tex_coords(point, bounds), Vec3::Z)
+    });
+    geometry.into_data()
+}
+
+pub fn arc(inner_radius: f32, outer_radius: f32, start_angle: f32, end_angle: f32, segments: u32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Arc", arc_data(inner_radius, outer_radius, start_angle, end_angle, segments), material, device)
+}
+
+pub fn ring_data(inner_radius: f32, outer_radius: f32, segments: u32) -> MeshData {
+    arc_data(inner_radius, outer_radius, 0.0, TAU, segments)
+}
+
+pub fn ring(inner_radius: f32, outer_radius: f32, segments: u32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Ring", ring_data(inner_radius, outer_radius, segments), material, device)
+}
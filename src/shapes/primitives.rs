@@ -0,0 +1,260 @@
+use std::{collections::HashMap, f32::consts::{FRAC_PI_2, FRAC_PI_4, PI, TAU}};
+use glam::{vec2, vec3, Vec2, Vec3};
+use crate::{mesh_data::MeshData, model::Mesh};
+use super::{around, mesh, Geometry};
+
+/// The outward normal, the direction of increasing u and the direction of increasing v
+/// of every box face, so textures are upright when seen from outside.
+const BOX_FACES: [(Vec3, Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::NEG_Z, Vec3::NEG_Y),
+    (Vec3::NEG_X, Vec3::Z, Vec3::NEG_Y),
+    (Vec3::Y, Vec3::X, Vec3::Z),
+    (Vec3::NEG_Y, Vec3::X, Vec3::NEG_Z),
+    (Vec3::Z, Vec3::X, Vec3::NEG_Y),
+    (Vec3::NEG_Z, Vec3::NEG_X, Vec3::NEG_Y),
+];
+
+/// Grid coordinates across a box face of half extent `extent`, with `segments` extra
+/// columns in each rounded edge spaced so the projected vertices are evenly spread.
+fn box_coordinates(extent: f32, radius: f32, segments: u32) -> Vec<f32> {
+    if radius <= 0.0 || segments == 0 {
+        return vec![-extent, extent];
+    }
+    let flat = extent - radius;
+    let offset = |i: u32| radius * (i as f32 / segments as f32 * FRAC_PI_4).tan();
+    (0..=segments)
+        .rev()
+        .map(|i| -flat - offset(i))
+        .chain((0..=segments).map(|i| flat + offset(i)))
+        .collect()
+}
+
+fn rounded_box_geometry(size: Vec3, radius: f32, segments: u32) -> Geometry {
+    let half = size * 0.5;
+    let radius = radius.clamp(0.0, half.min_element());
+    let inner = half - radius;
+    let mut geometry = Geometry::default();
+    for (normal, u, v) in BOX_FACES {
+        let extent_u = u.abs().dot(half);
+        let extent_v = v.abs().dot(half);
+        let coordinates_u = box_coordinates(extent_u, radius, segments);
+        let coordinates_v = box_coordinates(extent_v, radius, segments);
+        geometry.grid(coordinates_u.len() as u32 - 1, coordinates_v.len() as u32 - 1, |column, row| {
+            let (cu, cv) = (coordinates_u[column as usize], coordinates_v[row as usize]);
+            let point = normal * normal.abs().dot(half) + u * cu + v * cv;
+            let core = point.clamp(-inner, inner);
+            let direction = (point - core).normalize_or(normal);
+            let tex_coords = vec2((cu + extent_u) / (2.0 * extent_u), (cv + extent_v) / (2.0 * extent_v));
+            (core + direction * radius, tex_coords, direction)
+        });
+    }
+    geometry
+}
+
+/// A box centered on the origin, with the whole texture on every face.
+pub fn cube_data(size: Vec3) -> MeshData {
+    rounded_box_geometry(size, 0.0, 0).into_data()
+}
+
+pub fn cube(size: Vec3, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Cube", cube_data(size), material, device)
+}
+
+/// A box whose edges and corners are rounded with `radius`, using `segments` quads per rounded edge.
+pub fn rounded_box_data(size: Vec3, radius: f32, segments: u32) -> MeshData {
+    rounded_box_geometry(size, radius, segments.max(1)).into_data()
+}
+
+pub fn rounded_box(size: Vec3, radius: f32, segments: u32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Rounded Box", rounded_box_data(size, radius, segments), material, device)
+}
+
+/// A plane in the xz plane facing +y, split into `subdivisions` quads along each side.
+pub fn plane_data(size: Vec2, subdivisions: u32) -> MeshData {
+    let subdivisions = subdivisions.max(1);
+    let mut geometry = Geometry::default();
+    geometry.grid(subdivisions, subdivisions, |column, row| {
+        let tex_coords = vec2(column as f32, row as f32) / subdivisions as f32;
+        let position = vec3((tex_coords.x - 0.5) * size.x, 0.0, (tex_coords.y - 0.5) * size.y);
+        (position, tex_coords, Vec3::Y)
+    });
+    geometry.into_data()
+}
+
+pub fn plane(size: Vec2, subdivisions: u32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Plane", plane_data(size, subdivisions), material, device)
+}
+
+pub fn uv_sphere_data(radius: f32, segments: u32, rings: u32) -> MeshData {
+    let (segments, rings) = (segments.max(3), rings.max(2));
+    let mut geometry = Geometry::default();
+    geometry.grid(segments, rings, |column, row| {
+        let tex_coords = vec2(column as f32 / segments as f32, row as f32 / rings as f32);
+        let polar = tex_coords.y * PI;
+        let normal = around(tex_coords.x * TAU) * polar.sin() + Vec3::Y * polar.cos();
+        (normal * radius, tex_coords, normal)
+    });
+    geometry.into_data()
+}
+
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("UV Sphere", uv_sphere_data(radius, segments, rings), material, device)
+}
+
+/// A subdivided icosahedron with spherically mapped texture coordinates.
+pub fn icosphere_data(radius: f32, subdivisions: u32) -> MeshData {
+    let t = (1.0 + 5.0_f32.sqrt()) / 2.0;
+    let mut points = vec![
+        vec3(-1.0, t, 0.0), vec3(1.0, t, 0.0), vec3(-1.0, -t, 0.0), vec3(1.0, -t, 0.0),
+        vec3(0.0, -1.0, t), vec3(0.0, 1.0, t), vec3(0.0, -1.0, -t), vec3(0.0, 1.0, -t),
+        vec3(t, 0.0, -1.0), vec3(t, 0.0, 1.0), vec3(-t, 0.0, -1.0), vec3(-t, 0.0, 1.0),
+    ].into_iter().map(Vec3::normalize).collect::<Vec<_>>();
+    let mut faces = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints = HashMap::new();
+        let mut midpoint = |a: usize, b: usize| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                points.push((points[a] + points[b]).normalize());
+                points.len() - 1
+            })
+        };
+        faces = faces
+            .into_iter()
+            .flat_map(|[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let mut geometry = Geometry::default();
+    let mut vertices = HashMap::new();
+    for face in faces {
+        let mut tex_coords = face.map(|i| vec2(points[i].x.atan2(points[i].z) / TAU + 0.5, points[i].y.acos() / PI));
+        // Triangles crossing the seam get their own vertices on the far side of the texture.
+        let max_u = tex_coords.iter().map(|uv| uv.x).fold(0.0, f32::max);
+        for uv in &mut tex_coords {
+            if max_u - uv.x > 0.5 {
+                uv.x += 1.0;
+            }
+        }
+        // The u of a pole is undefined, so it takes the average of the rest of the triangle.
+        for corner in 0..3 {
+            if points[face[corner]].y.abs() > 0.9999 {
+                tex_coords[corner].x = (tex_coords[(corner + 1) % 3].x + tex_coords[(corner + 2) % 3].x) * 0.5;
+            }
+        }
+        let [a, b, c] = [0, 1, 2].map(|corner| {
+            let point = points[face[corner]];
+            let uv = tex_coords[corner];
+            *vertices
+                .entry((face[corner], uv.to_array().map(f32::to_bits)))
+                .or_insert_with(|| geometry.vertex(point * radius, uv, point))
+        });
+        geometry.triangle(a, b, c);
+    }
+    geometry.into_data()
+}
+
+pub fn icosphere(radius: f32, subdivisions: u32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Icosphere", icosphere_data(radius, subdivisions), material, device)
+}
+
+/// A flat disc at height `y` facing up or down along y.
+fn disc(geometry: &mut Geometry, y: f32, radius: f32, segments: u32, normal: Vec3) {
+    let flip = normal.y.signum();
+    let center = geometry.vertex(vec3(0.0, y, 0.0), vec2(0.5, 0.5), normal);
+    for i in 0..segments {
+        let direction = around(i as f32 / segments as f32 * TAU);
+        geometry.vertex(direction * radius + Vec3::Y * y, vec2(0.5 + 0.5 * direction.x, 0.5 + 0.5 * direction.z * flip), normal);
+    }
+    for i in 0..segments {
+        geometry.triangle(center, center + 1 + i, center + 1 + (i + 1) % segments);
+    }
+}
+
+/// A cylinder centered on the origin along y, with caps.
+pub fn cylinder_data(radius: f32, height: f32, segments: u32) -> MeshData {
+    let segments = segments.max(3);
+    let mut geometry = Geometry::default();
+    geometry.grid(segments, 1, |column, row| {
+        let tex_coords = vec2(column as f32 / segments as f32, row as f32);
+        let normal = around(tex_coords.x * TAU);
+        (normal * radius + Vec3::Y * height * (0.5 - tex_coords.y), tex_coords, normal)
+    });
+    disc(&mut geometry, height * 0.5, radius, segments, Vec3::Y);
+    disc(&mut geometry, -height * 0.5, radius, segments, Vec3::NEG_Y);
+    geometry.into_data()
+}
+
+pub fn cylinder(radius: f32, height: f32, segments: u32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Cylinder", cylinder_data(radius, height, segments), material, device)
+}
+
+/// A cone centered on the origin along y with its tip pointing up, with a base cap.
+pub fn cone_data(radius: f32, height: f32, segments: u32) -> MeshData {
+    let segments = segments.max(3);
+    let mut geometry = Geometry::default();
+    geometry.grid(segments, 1, |column, row| {
+        let tex_coords = vec2(column as f32 / segments as f32, row as f32);
+        let direction = around(tex_coords.x * TAU);
+        let normal = (direction * height + Vec3::Y * radius).normalize();
+        let position = direction * radius * tex_coords.y + Vec3::Y * height * (0.5 - tex_coords.y);
+        (position, tex_coords, normal)
+    });
+    disc(&mut geometry, -height * 0.5, radius, segments, Vec3::NEG_Y);
+    geometry.into_data()
+}
+
+pub fn cone(radius: f32, height: f32, segments: u32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Cone", cone_data(radius, height, segments), material, device)
+}
+
+/// A torus around the y axis. `segments` go around the ring and `sides` around the tube.
+pub fn torus_data(major_radius: f32, minor_radius: f32, segments: u32, sides: u32) -> MeshData {
+    let (segments, sides) = (segments.max(3), sides.max(3));
+    let mut geometry = Geometry::default();
+    geometry.grid(segments, sides, |column, row| {
+        let tex_coords = vec2(column as f32 / segments as f32, row as f32 / sides as f32);
+        let outward = around(tex_coords.x * TAU);
+        let tube = tex_coords.y * TAU;
+        let normal = outward * tube.cos() - Vec3::Y * tube.sin();
+        (outward * major_radius + normal * minor_radius, tex_coords, normal)
+    });
+    geometry.into_data()
+}
+
+pub fn torus(major_radius: f32, minor_radius: f32, segments: u32, sides: u32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Torus", torus_data(major_radius, minor_radius, segments, sides), material, device)
+}
+
+/// A capsule along y where `height` is the length of the cylindrical part, so the
+/// total height is `height + 2 * radius`. `rings` is per hemisphere.
+pub fn capsule_data(radius: f32, height: f32, segments: u32, rings: u32) -> MeshData {
+    let (segments, rings) = (segments.max(3), rings.max(1));
+    let length = PI * radius + height;
+    let mut geometry = Geometry::default();
+    geometry.grid(segments, rings * 2 + 1, |column, row| {
+        let (polar, center, distance) = if row <= rings {
+            let polar = row as f32 / rings as f32 * FRAC_PI_2;
+            (polar, height * 0.5, polar * radius)
+        } else {
+            let polar = FRAC_PI_2 + (row - rings - 1) as f32 / rings as f32 * FRAC_PI_2;
+            (polar, -height * 0.5, polar * radius + height)
+        };
+        let u = column as f32 / segments as f32;
+        let normal = around(u * TAU) * polar.sin() + Vec3::Y * polar.cos();
+        (normal * radius + Vec3::Y * center, vec2(u, distance / length), normal)
+    });
+    geometry.into_data()
+}
+
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Capsule", capsule_data(radius, height, segments, rings), material, device)
+}
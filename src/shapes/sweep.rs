@@ -0,0 +1,193 @@
+use std::f32::consts::TAU;
+use glam::{vec2, Vec2, Vec3};
+use crate::{mesh_data::MeshData, model::Mesh, triangulate};
+use super::{around, fill, mesh, Geometry};
+
+/// A point of a profile with the normal on the right hand side of the outline and the
+/// fraction of the outline walked to get there.
+struct ProfileVertex {
+    point: Vec2,
+    normal: Vec2,
+    distance: f32,
+}
+
+/// Walks an outline, splitting every corner sharper than `crease_angle` into two vertices so
+/// both sides keep their own normal. Closed outlines end with a copy of the first point.
+fn profile(points: &[Vec2], closed: bool, crease_angle: f32) -> Vec<ProfileVertex> {
+    let count = points.len();
+    if count < 2 {
+        return vec![];
+    }
+    let edge_normal = |i: usize| {
+        let direction = (points[(i + 1) % count] - points[i]).normalize_or_zero();
+        vec2(direction.y, -direction.x)
+    };
+    let length = |i: usize| points[(i + 1) % count].distance(points[i]);
+    let num_edges = if closed { count } else { count - 1 };
+    let total = (0..num_edges).map(length).sum::<f32>().max(f32::EPSILON);
+
+    let mut vertices = vec![];
+    let mut distance = 0.0;
+    for i in 0..=num_edges {
+        let point = points[i % count];
+        let incoming = (closed || i > 0).then(|| edge_normal((i + count - 1) % count));
+        let outgoing = (closed || i < num_edges).then(|| edge_normal(i % count));
+        let mut push = |normal: Vec2| vertices.push(ProfileVertex { point, normal, distance: distance / total });
+        match (incoming, outgoing) {
+            (Some(incoming), Some(outgoing)) if incoming.angle_between(outgoing).abs() <= crease_angle => {
+                push((incoming + outgoing).normalize_or(outgoing));
+            }
+            (Some(incoming), Some(outgoing)) => {
+                if i > 0 {
+                    push(incoming);
+                }
+                if i < num_edges {
+                    push(outgoing);
+                }
+            }
+            (Some(normal), None) | (None, Some(normal)) => push(normal),
+            (None, None) => {}
+        }
+        if i < num_edges {
+            distance += length(i);
+        }
+    }
+    vertices
+}
+
+/// Winds an outline counter-clockwise, or clockwise for holes, so its profile normals point
+/// out of the solid.
+fn wound(points: &[Vec2], counter_clockwise: bool) -> Vec<Vec2> {
+    let mut points = points.to_vec();
+    if (triangulate::signed_area(&points) > 0.0) != counter_clockwise {
+        points.reverse();
+    }
+    points
+}
+
+/// A polygon with holes in the xy plane extruded along z into a solid centered on the origin,
+/// with caps facing +z and -z. Corners of the sides sharper than `crease_angle` radians are
+/// kept hard.
+pub fn extrude_data(outer: &[Vec2], holes: &[Vec<Vec2>], depth: f32, crease_angle: f32) -> MeshData {
+    let mut geometry = Geometry::default();
+    let rings = std::iter::once(wound(outer, true)).chain(holes.iter().map(|hole| wound(hole, false)));
+    for ring in rings {
+        let profile = profile(&ring, true, crease_angle);
+        if profile.is_empty() {
+            continue;
+        }
+        geometry.grid(profile.len() as u32 - 1, 1, |column, row| {
+            let vertex = &profile[column as usize];
+            let position = vertex.point.extend(depth * (0.5 - row as f32));
+            (position, vec2(vertex.distance, row as f32), vertex.normal.extend(0.0))
+        });
+    }
+    fill(&mut geometry, outer, holes, Vec3::Z, false, |point| point.extend(depth * 0.5));
+    fill(&mut geometry, outer, holes, Vec3::NEG_Z, true, |point| point.extend(-depth * 0.5));
+    geometry.into_data()
+}
+
+pub fn extrude(outer: &[Vec2], holes: &[Vec<Vec2>], depth: f32, crease_angle: f32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Extrusion", extrude_data(outer, holes, depth, crease_angle), material, device)
+}
+
+/// Revolves a profile of (radius, height) points around the y axis. The surface faces to the
+/// right of the profile, so one walked from the bottom to the top faces outwards. Corners
+/// sharper than `crease_angle` radians are kept hard.
+pub fn lathe_data(profile_points: &[Vec2], segments: u32, crease_angle: f32) -> MeshData {
+    let segments = segments.max(3);
+    let profile = profile(profile_points, false, crease_angle);
+    let mut geometry = Geometry::default();
+    if profile.is_empty() {
+        return geometry.into_data();
+    }
+    geometry.grid(segments, profile.len() as u32 - 1, |column, row| {
+        let u = column as f32 / segments as f32;
+        let outward = around(u * TAU);
+        let vertex = &profile[profile.len() - 1 - row as usize];
+        let position = outward * vertex.point.x + Vec3::Y * vertex.point.y;
+        let normal = outward * vertex.normal.x + Vec3::Y * vertex.normal.y;
+        (position, vec2(u, 1.0 - vertex.distance), normal)
+    });
+    geometry.into_data()
+}
+
+pub fn lathe(profile: &[Vec2], segments: u32, crease_angle: f32, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Lathe", lathe_data(profile, segments, crease_angle), material, device)
+}
+
+/// Rotation minimizing frames along a path, found with the double reflection method from
+/// Wang et al., "Computation of Rotation Minimizing Frames". Each frame is the tangent and
+/// the two axes the profile is laid out along.
+pub fn rotation_minimizing_frames(path: &[Vec3]) -> Vec<(Vec3, Vec3, Vec3)> {
+    let count = path.len();
+    if count < 2 {
+        return vec![];
+    }
+    let tangents = (0..count)
+        .map(|i| (path[(i + 1).min(count - 1)] - path[i.saturating_sub(1)]).normalize_or(Vec3::Z))
+        .collect::<Vec<_>>();
+    let mut right = tangents[0].any_orthonormal_vector();
+    let mut frames = vec![(tangents[0], right, tangents[0].cross(right))];
+    for i in 0..count - 1 {
+        let step = path[i + 1] - path[i];
+        let length = step.length_squared();
+        if length > f32::EPSILON * f32::EPSILON {
+            let reflected_right = right - step * (2.0 / length * step.dot(right));
+            let reflected_tangent = tangents[i] - step * (2.0 / length * step.dot(tangents[i]));
+            let difference = tangents[i + 1] - reflected_tangent;
+            let length = difference.length_squared();
+            right = if length > f32::EPSILON * f32::EPSILON {
+                reflected_right - difference * (2.0 / length * difference.dot(reflected_right))
+            } else {
+                reflected_right
+            };
+        }
+        right = right.reject_from(tangents[i + 1]).normalize_or(tangents[i + 1].any_orthonormal_vector());
+        frames.push((tangents[i + 1], right, tangents[i + 1].cross(right)));
+    }
+    frames
+}
+
+/// Sweeps a closed profile along a path, laying it out across the rotation minimizing frames
+/// so the result does not twist. Corners of the profile sharper than `crease_angle` radians
+/// are kept hard, and `caps` closes both ends.
+pub fn sweep_data(profile_points: &[Vec2], path: &[Vec3], crease_angle: f32, caps: bool) -> MeshData {
+    let outline = wound(profile_points, true);
+    let profile = profile(&outline, true, crease_angle);
+    let frames = rotation_minimizing_frames(path);
+    let mut geometry = Geometry::default();
+    if profile.is_empty() || frames.is_empty() {
+        return geometry.into_data();
+    }
+
+    let lengths = path.windows(2).map(|pair| pair[0].distance(pair[1])).collect::<Vec<_>>();
+    let total = lengths.iter().sum::<f32>().max(f32::EPSILON);
+    let distances = std::iter::once(0.0)
+        .chain(lengths.iter().scan(0.0, |distance, length| {
+            *distance += length;
+            Some(*distance / total)
+        }))
+        .collect::<Vec<_>>();
+    let place = |i: usize, point: Vec2| {
+        let (_, right, up) = frames[i];
+        path[i] + right * point.x + up * point.y
+    };
+    geometry.grid(profile.len() as u32 - 1, path.len() as u32 - 1, |column, row| {
+        let vertex = &profile[column as usize];
+        let (_, right, up) = frames[row as usize];
+        let normal = right * vertex.normal.x + up * vertex.normal.y;
+        (place(row as usize, vertex.point), vec2(1.0 - vertex.distance, distances[row as usize]), normal)
+    });
+
+    if caps {
+        let last = path.len() - 1;
+        fill(&mut geometry, &outline, &[], -frames[0].0, true, |point| place(0, point));
+        fill(&mut geometry, &outline, &[], frames[last].0, false, |point| place(last, point));
+    }
+    geometry.into_data()
+}
+
+pub fn sweep(profile: &[Vec2], path: &[Vec3], crease_angle: f32, caps: bool, material: usize, device: &wgpu::Device) -> Mesh {
+    mesh("Sweep", sweep_data(profile, path, crease_angle, caps), material, device)
+}
@@ -0,0 +1,444 @@
+use std::{cmp::Ordering, collections::{BinaryHeap, HashMap, HashSet}, ops::Add};
+use glam::{DVec3, IVec3, Vec3};
+use crate::{bounds::BoundingSphere, builder::MeshBuilder, camera::Camera, mesh_data::MeshData, model::{Mesh, ModelVertex}};
+
+/// Border and seam edges pull harder than faces, so collapses slide along them instead of
+/// eating into them.
+const CONSTRAINT_WEIGHT: f64 = 4.0;
+
+/// Positions closer than this part of the size of the mesh count as one, so seams whose
+/// sides were computed separately still join up.
+const WELD_TOLERANCE: f32 = 1e-5;
+
+/// The furthest a level of a [LodChain] may stray from the original, as a part of its bounding
+/// radius.
+const MAX_LOD_ERROR: f32 = 0.1;
+
+/// The cosine of the furthest a collapse may turn a face.
+const MIN_FACE_COSINE: f32 = 0.25;
+
+/// The sum of squared distances to a set of planes, as the upper triangle of a symmetric 4x4
+/// matrix. Only faces add to the weight, which averages the error over their area.
+#[derive(Copy, Clone, Debug, Default)]
+struct Quadric {
+    coefficients: [f64; 10],
+    weight: f64,
+}
+
+impl Quadric {
+    fn plane(normal: DVec3, point: DVec3, weight: f64, counted: bool) -> Self {
+        let [a, b, c] = normal.to_array();
+        let d = -normal.dot(point);
+        let coefficients = [a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d].map(|value| value * weight);
+        Self { coefficients, weight: if counted { weight } else { 0.0 } }
+    }
+
+    /// The root mean square distance of `point` from the planes.
+    fn error(&self, point: Vec3) -> f64 {
+        let [x, y, z] = point.as_dvec3().to_array();
+        let q = &self.coefficients;
+        let sum = q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+            + q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+            + q[7] * z * z + 2.0 * q[8] * z
+            + q[9];
+        (sum.max(0.0) / self.weight.max(f64::MIN_POSITIVE)).sqrt()
+    }
+}
+
+impl Add for Quadric {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let mut coefficients = self.coefficients;
+        for (value, other) in coefficients.iter_mut().zip(other.coefficients) {
+            *value += other;
+        }
+        Self { coefficients, weight: self.weight + other.weight }
+    }
+}
+
+/// Moving every vertex at the `from` position onto the `to` position, kept in a heap with the
+/// cheapest first.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Collapse {
+    error: f64,
+    from: u32,
+    to: u32,
+}
+
+impl Eq for Collapse {}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.error.total_cmp(&self.error)
+    }
+}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+struct Simplifier {
+    point_of_vertex: Vec<u32>,
+    points: Vec<Vec3>,
+    quadrics: Vec<Quadric>,
+    triangles: Vec<[u32; 3]>,
+    /// The normal each face started with, so collapses can not turn faces over a little at a
+    /// time. Zero for faces that started without area.
+    original_normals: Vec<Vec3>,
+    alive: Vec<bool>,
+    point_triangles: Vec<Vec<usize>>,
+}
+
+impl Simplifier {
+    fn point(&self, vertex: u32) -> u32 {
+        self.point_of_vertex[vertex as usize]
+    }
+
+    fn live_triangles(&self, point: u32) -> impl Iterator<Item = usize> + '_ {
+        self.point_triangles[point as usize].iter().copied().filter(|&t| self.alive[t])
+    }
+
+    fn has_point(&self, triangle: usize, point: u32) -> bool {
+        self.triangles[triangle].iter().any(|&vertex| self.point(vertex) == point)
+    }
+
+    fn neighbours(&self, point: u32) -> HashSet<u32> {
+        self.live_triangles(point)
+            .flat_map(|t| self.triangles[t])
+            .map(|vertex| self.point(vertex))
+            .filter(|&other| other != point)
+            .collect()
+    }
+
+    /// Edges with one face are borders, edges whose faces have different vertices at both ends
+    /// are seams. Faces that only split at one end, like the fans around the poles of a sphere,
+    /// still share the edge.
+    fn is_constrained(&self, a: u32, b: u32) -> bool {
+        let shared = self.live_triangles(a).filter(|&t| self.has_point(t, b)).collect::<Vec<_>>();
+        if shared.len() != 2 {
+            return true;
+        }
+        let vertex_at = |t: usize, point: u32| self.triangles[t].into_iter().find(|&vertex| self.point(vertex) == point);
+        let splits = |point: u32| vertex_at(shared[0], point) != vertex_at(shared[1], point);
+        splits(a) && splits(b)
+    }
+
+    /// Checks that moving `from` onto `to` keeps the surface manifold, flips no faces and keeps
+    /// borders and seams in place. Returns the vertex each vertex at `from` turns into.
+    fn vertex_map(&self, from: u32, to: u32) -> Option<HashMap<u32, u32>> {
+        let shared = self.live_triangles(from).filter(|&t| self.has_point(t, to)).count();
+        // Lone triangles are kept rather than collapsed away.
+        let lone = self.live_triangles(from).count() == shared && self.live_triangles(to).count() == shared;
+        if shared == 0 || lone || self.neighbours(from).intersection(&self.neighbours(to)).count() != shared {
+            return None;
+        }
+        // A point on a border or seam may only slide along it.
+        let constrained = self.neighbours(from).into_iter().any(|other| self.is_constrained(from, other));
+        if constrained && !self.is_constrained(from, to) {
+            return None;
+        }
+
+        let mut map = HashMap::new();
+        for t in self.live_triangles(from) {
+            let corners = self.triangles[t];
+            let vertex = corners.into_iter().find(|&vertex| self.point(vertex) == from).unwrap();
+            let target = corners.into_iter().find(|&vertex| self.point(vertex) == to);
+            match (map.get(&vertex).copied(), target) {
+                (Some(Some(existing)), Some(target)) if existing != target => return None,
+                (None, target) | (Some(None), target) => {
+                    map.insert(vertex, target);
+                }
+                _ => {}
+            }
+        }
+        let map = map
+            .into_iter()
+            .map(|(vertex, target)| target.map(|target| (vertex, target)))
+            .collect::<Option<HashMap<_, _>>>()?;
+
+        let moved = self.points[to as usize];
+        for t in self.live_triangles(from).filter(|&t| !self.has_point(t, to)) {
+            let corners = self.triangles[t].map(|vertex| self.points[self.point(vertex) as usize]);
+            let after = self.triangles[t].map(|vertex| if self.point(vertex) == from { moved } else { self.points[self.point(vertex) as usize] });
+            let normal = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+            let new_normal = (after[1] - after[0]).cross(after[2] - after[0]);
+            // Turning a face too far also rejects slivers that fold upright on the surface.
+            let turned = |normal: Vec3| new_normal.dot(normal) <= MIN_FACE_COSINE * new_normal.length() * normal.length();
+            let original = self.original_normals[t];
+            if turned(normal) || (original != Vec3::ZERO && turned(original)) {
+                return None;
+            }
+        }
+        Some(map)
+    }
+
+    fn error(&self, from: u32, to: u32) -> f64 {
+        (self.quadrics[from as usize] + self.quadrics[to as usize]).error(self.points[to as usize])
+    }
+}
+
+/// Numbers the positions, counting positions closer than `tolerance` as one, and returns the
+/// number of every position along with the first position of each number.
+pub(crate) fn weld_points(positions: &[Vec3], tolerance: f32) -> (Vec<u32>, Vec<Vec3>) {
+    let cell_size = tolerance.max(f32::MIN_POSITIVE);
+    let mut cells: HashMap<IVec3, Vec<u32>> = HashMap::new();
+    let mut points: Vec<Vec3> = vec![];
+    let point_of_vertex = positions
+        .iter()
+        .map(|&position| {
+            let cell = (position / cell_size).floor().as_ivec3();
+            let nearby = (0..27)
+                .map(|i| cell + IVec3::new(i % 3 - 1, i / 3 % 3 - 1, i / 9 - 1))
+                .filter_map(|cell| cells.get(&cell))
+                .flatten()
+                .copied()
+                .find(|&point| points[point as usize].distance(position) <= tolerance);
+            nearby.unwrap_or_else(|| {
+                points.push(position);
+                cells.entry(cell).or_default().push(points.len() as u32 - 1);
+                points.len() as u32 - 1
+            })
+        })
+        .collect();
+    (point_of_vertex, points)
+}
+
+/// Reduces a mesh with quadric error metrics until it has at most `target_triangles` or the
+/// next collapse would move the surface further than `max_error`. Every step moves the vertices
+/// at one position onto a neighbouring position, so the kept vertices keep all their
+/// attributes. Borders and UV seams only shrink along themselves. Returns the simplified mesh
+/// and the largest error of the collapses made, roughly a distance from the original surface.
+pub fn simplify_data(data: &MeshData, target_triangles: usize, max_error: f32) -> (MeshData, f32) {
+    let tolerance = data.aabb().map_or(0.0, |aabb| aabb.size().max_element()) * WELD_TOLERANCE;
+    let (point_of_vertex, points) = weld_points(&data.positions, tolerance);
+    let num_points = points.len();
+    let triangles = data.indices.chunks(3).map(|t| [t[0], t[1], t[2]]).collect::<Vec<_>>();
+    // Faces with two corners at one position, such as at the poles of a sphere, are dropped.
+    let alive = triangles
+        .iter()
+        .map(|t| t.map(|vertex| point_of_vertex[vertex as usize]))
+        .map(|[a, b, c]| a != b && b != c && c != a)
+        .collect();
+    let original_normals = triangles
+        .iter()
+        .map(|t| {
+            let [a, b, c] = t.map(|vertex| data.positions[vertex as usize]);
+            (b - a).cross(c - a).normalize_or_zero()
+        })
+        .collect();
+    let mut simplifier = Simplifier {
+        point_of_vertex,
+        points,
+        original_normals,
+        quadrics: vec![Quadric::default(); num_points],
+        alive,
+        point_triangles: vec![vec![]; num_points],
+        triangles,
+    };
+
+    for t in 0..simplifier.triangles.len() {
+        if !simplifier.alive[t] {
+            continue;
+        }
+        let corners = simplifier.triangles[t].map(|vertex| simplifier.point(vertex));
+        for &point in &corners {
+            simplifier.point_triangles[point as usize].push(t);
+        }
+        let [a, b, c] = corners.map(|point| simplifier.points[point as usize].as_dvec3());
+        let cross = (b - a).cross(c - a);
+        let area = cross.length() * 0.5;
+        if area > 0.0 {
+            let plane = Quadric::plane(cross.normalize(), a, area, true);
+            for point in corners {
+                simplifier.quadrics[point as usize] = simplifier.quadrics[point as usize] + plane;
+            }
+        }
+    }
+    // Planes through the border and seam edges, upright on their faces.
+    for t in 0..simplifier.triangles.len() {
+        if !simplifier.alive[t] {
+            continue;
+        }
+        let corners = simplifier.triangles[t].map(|vertex| simplifier.point(vertex));
+        for i in 0..3 {
+            let (a, b) = (corners[i], corners[(i + 1) % 3]);
+            if simplifier.is_constrained(a, b) {
+                let [pa, pb, pc] = [a, b, corners[(i + 2) % 3]].map(|point| simplifier.points[point as usize].as_dvec3());
+                let edge = pb - pa;
+                let normal = edge.cross((pb - pa).cross(pc - pa)).normalize_or_zero();
+                let plane = Quadric::plane(normal, pa, edge.length_squared() * CONSTRAINT_WEIGHT, false);
+                for point in [a, b] {
+                    simplifier.quadrics[point as usize] = simplifier.quadrics[point as usize] + plane;
+                }
+            }
+        }
+    }
+
+    let mut heap = BinaryHeap::new();
+    let mut removed = vec![false; num_points];
+    for point in 0..num_points as u32 {
+        for other in simplifier.neighbours(point) {
+            heap.push(Collapse { error: simplifier.error(point, other), from: point, to: other });
+        }
+    }
+
+    let mut remaining = simplifier.alive.iter().filter(|&&alive| alive).count();
+    let mut largest_error = 0.0f64;
+    while remaining > target_triangles {
+        let Some(collapse) = heap.pop() else {
+            break;
+        };
+        if removed[collapse.from as usize] || removed[collapse.to as usize] {
+            continue;
+        }
+        // Costs go stale as quadrics merge, so they are checked again when they come up.
+        let error = simplifier.error(collapse.from, collapse.to);
+        if error > collapse.error * (1.0 + 1e-6) + 1e-12 {
+            heap.push(Collapse { error, ..collapse });
+            continue;
+        }
+        if error > max_error as f64 {
+            break;
+        }
+        let Some(map) = simplifier.vertex_map(collapse.from, collapse.to) else {
+            continue;
+        };
+
+        for t in simplifier.point_triangles[collapse.from as usize].clone() {
+            if !simplifier.alive[t] {
+                continue;
+            }
+            if simplifier.has_point(t, collapse.to) {
+                simplifier.alive[t] = false;
+                remaining -= 1;
+            } else {
+                for vertex in &mut simplifier.triangles[t] {
+                    if let Some(&target) = map.get(vertex) {
+                        *vertex = target;
+                    }
+                }
+                simplifier.point_triangles[collapse.to as usize].push(t);
+            }
+        }
+        removed[collapse.from as usize] = true;
+        simplifier.quadrics[collapse.to as usize] = simplifier.quadrics[collapse.to as usize] + simplifier.quadrics[collapse.from as usize];
+        let alive = &simplifier.alive;
+        simplifier.point_triangles[collapse.to as usize].retain(|&t| alive[t]);
+        largest_error = largest_error.max(error);
+
+        for other in simplifier.neighbours(collapse.to) {
+            heap.push(Collapse { error: simplifier.error(collapse.to, other), from: collapse.to, to: other });
+            heap.push(Collapse { error: simplifier.error(other, collapse.to), from: other, to: collapse.to });
+        }
+    }
+
+    let mut remap = HashMap::new();
+    let mut sources = vec![];
+    let mut indices = vec![];
+    for (t, triangle) in simplifier.triangles.iter().enumerate() {
+        if !simplifier.alive[t] {
+            continue;
+        }
+        for &vertex in triangle {
+            indices.push(*remap.entry(vertex).or_insert_with(|| {
+                sources.push(vertex);
+                sources.len() as u32 - 1
+            }));
+        }
+    }
+    let mut result = MeshData { indices, ..data.clone() };
+    result.remap_vertices(&sources);
+    (result, largest_error as f32)
+}
+
+pub fn simplify(data: &MeshData, target_triangles: usize, max_error: f32, material: usize, device: &wgpu::Device) -> Mesh {
+    MeshBuilder::<ModelVertex>::from_data("Simplified", simplify_data(data, target_triangles, max_error).0)
+        .material(material)
+        .build(device)
+}
+
+/// Progressively simpler versions of a mesh, from the original down.
+pub struct LodChain {
+    levels: Vec<Mesh>,
+    /// How far each level strays from the original.
+    errors: Vec<f32>,
+    bounds: BoundingSphere,
+}
+
+impl LodChain {
+    /// Builds `num_levels` levels, each with about `ratio` times the triangles of the one
+    /// before. Levels that no longer shrink without straying too far from the original are left
+    /// out.
+    pub fn new(data: &MeshData, num_levels: usize, ratio: f32, material: usize, device: &wgpu::Device) -> Self {
+        let bounds = data.bounding_sphere().unwrap_or(BoundingSphere::new(Vec3::ZERO, 0.0));
+        let mut levels = vec![MeshBuilder::<ModelVertex>::from_data("LOD 0", data.clone()).material(material).build(device)];
+        let mut errors = vec![0.0];
+        let mut triangles = data.num_triangles();
+        for level in 1..num_levels {
+            let target = (triangles as f32 * ratio.clamp(0.0, 1.0)) as usize;
+            let (simplified, error) = simplify_data(data, target, bounds.radius * MAX_LOD_ERROR);
+            if simplified.num_triangles() >= triangles {
+                break;
+            }
+            triangles = simplified.num_triangles();
+            levels.push(MeshBuilder::<ModelVertex>::from_data(&format!("LOD {level}"), simplified).material(material).build(device));
+            errors.push(error.max(*errors.last().unwrap()));
+        }
+        Self { levels, errors, bounds }
+    }
+
+    /// Uses the retained data of a mesh built with [`MeshBuilder::retain_data`].
+    pub fn from_mesh(mesh: &Mesh, num_levels: usize, ratio: f32, device: &wgpu::Device) -> Option<Self> {
+        mesh.data.as_ref().map(|data| Self::new(data, num_levels, ratio, mesh.material, device))
+    }
+
+    pub fn levels(&self) -> &[Mesh] {
+        &self.levels
+    }
+
+    pub fn level(&self, level: usize) -> &Mesh {
+        &self.levels[level.min(self.levels.len() - 1)]
+    }
+
+    /// The simplest level whose error stays under `max_screen_error` when drawn at `position`,
+    /// measured like [`Camera::screen_size`] as a fraction of the view height.
+    pub fn select_level(&self, camera: &Camera, position: Vec3, max_screen_error: f32) -> usize {
+        let center = self.bounds.center + position;
+        // The error is measured at the point of the bounds closest to the camera.
+        let distance = (camera.eye().distance(center) - self.bounds.radius).max(f32::EPSILON);
+        let closest = camera.eye() + (center - camera.eye()).normalize_or_zero() * distance;
+        (0..self.levels.len())
+            .rev()
+            .find(|&level| camera.screen_size(closest, self.errors[level]) <= max_screen_error)
+            .unwrap_or(0)
+    }
+
+    pub fn select(&self, camera: &Camera, position: Vec3, max_screen_error: f32) -> &Mesh {
+        self.level(self.select_level(camera, position, max_screen_error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shapes::uv_sphere_data;
+    use super::*;
+
+    #[test]
+    fn uv_sphere_keeps_its_shape() {
+        let data = uv_sphere_data(1.0, 64, 32);
+        let original_volume = data.volume();
+        for (target, max_error) in [(258, 0.05), (64, 0.1)] {
+            let (simplified, error) = simplify_data(&data, target, f32::INFINITY);
+            assert_eq!(simplified.num_triangles(), target);
+            assert!(error < max_error, "error {error} at {target} triangles");
+            assert!(simplified.volume() > original_volume * 0.75, "volume {} at {target} triangles", simplified.volume());
+            let aabb = simplified.aabb().unwrap();
+            assert!(aabb.min.cmple(Vec3::splat(-0.9)).all() && aabb.max.cmpge(Vec3::splat(0.9)).all(), "{aabb:?}");
+            // The sphere is centered, so every face should face away from the center.
+            assert!(simplified.triangles().all(|t| t.centroid().dot(t.normal()) > 0.0));
+        }
+    }
+}
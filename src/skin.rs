@@ -0,0 +1,495 @@
+use glam::{Mat4, Quat, UVec4, Vec2, Vec3, Vec4};
+use wgpu::util::DeviceExt;
+use crate::animation::Keyframe;
+use crate::model::{FromAttributes, HasMaterials, HasMeshes, Material, MatModel, Mesh, Vertex, VertexAttributes};
+
+/// A [`crate::model::ModelVertex`] moved by up to four joints, for [`crate::shaders::LIT_SKINNED`].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkinnedVertex {
+    pub position: Vec3,
+    pub tex_coords: Vec2,
+    pub normal: Vec3,
+    pub tangent: Vec4,
+    pub joints: UVec4,
+    /// All zero for vertices that stay where they are.
+    pub weights: Vec4,
+}
+
+impl FromAttributes for SkinnedVertex {
+    fn from_attributes(attributes: VertexAttributes) -> Self {
+        let VertexAttributes { position, tex_coords, normal, tangent, joints, weights, .. } = attributes;
+        Self { position, tex_coords, normal, tangent, joints, weights }
+    }
+}
+
+impl Vertex for SkinnedVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<SkinnedVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                // After the instance attributes, which take 5 to 11.
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Uint32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+/// A translation, rotation and scale, applied in the reverse order.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self { translation: Vec3::ZERO, rotation: Quat::IDENTITY, scale: Vec3::ONE };
+
+    /// Shears are lost.
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        Self { translation, rotation, scale }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+
+    /// `weight` of the way from this transform to `other`.
+    pub fn blend(&self, other: &Transform, weight: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(other.translation, weight),
+            rotation: self.rotation.slerp(other.rotation, weight),
+            scale: self.scale.lerp(other.scale, weight),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Joint {
+    pub name: String,
+    pub parent: Option<usize>,
+    /// Relative to the parent, or to [`Skeleton::root`] for joints without one.
+    pub rest: Transform,
+}
+
+/// A hierarchy of joints with the inverse bind matrices that take the vertices of a skinned
+/// mesh into the space of each joint.
+#[derive(Clone, Debug, Default)]
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+    pub inverse_bind_matrices: Vec<Mat4>,
+    /// Where the joints without a parent hang from.
+    pub root: Mat4,
+    /// Parents come before their children.
+    order: Vec<usize>,
+}
+
+impl Skeleton {
+    pub fn new(joints: Vec<Joint>, inverse_bind_matrices: Vec<Mat4>, root: Mat4) -> Self {
+        let depth = |mut joint: usize| {
+            let mut depth = 0;
+            while let Some(parent) = joints[joint].parent {
+                joint = parent;
+                depth += 1;
+            }
+            depth
+        };
+        let mut order = (0..joints.len()).collect::<Vec<_>>();
+        order.sort_by_cached_key(|&joint| depth(joint));
+        Self { joints, inverse_bind_matrices, root, order }
+    }
+
+    pub fn num_joints(&self) -> usize {
+        self.joints.len()
+    }
+
+    pub fn find_joint(&self, name: &str) -> Option<usize> {
+        self.joints.iter().position(|joint| joint.name == name)
+    }
+
+    pub fn rest_pose(&self) -> Vec<Transform> {
+        self.joints.iter().map(|joint| joint.rest).collect()
+    }
+
+    /// Where each joint of `pose` ends up in model space.
+    pub fn global_matrices(&self, pose: &[Transform]) -> Vec<Mat4> {
+        let mut globals = vec![Mat4::IDENTITY; self.joints.len()];
+        for &joint in &self.order {
+            let parent = self.joints[joint].parent.map_or(self.root, |parent| globals[parent]);
+            globals[joint] = parent * pose[joint].matrix();
+        }
+        globals
+    }
+
+    /// The matrices taking the vertices of the bind pose to `pose`, for [`JointBuffer::update`].
+    pub fn joint_matrices(&self, pose: &[Transform]) -> Vec<Mat4> {
+        self.global_matrices(pose)
+            .into_iter()
+            .zip(&self.inverse_bind_matrices)
+            .map(|(global, inverse_bind)| global * *inverse_bind)
+            .collect()
+    }
+}
+
+/// How values change between two keyframes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Holds each value until the next keyframe.
+    Step,
+    /// Straight lines, and slerp for rotations.
+    #[default]
+    Linear,
+    /// Hermite curves through the values with a tangent on each side of every keyframe.
+    CubicSpline,
+}
+
+impl Interpolation {
+    /// How many values each keyframe has.
+    pub fn values_per_key(self) -> usize {
+        match self {
+            Interpolation::CubicSpline => 3,
+            _ => 1,
+        }
+    }
+}
+
+/// Values at increasing times. Cubic splines keep an in tangent, the value and an out tangent
+/// for every time, as glTF stores them.
+#[derive(Clone, Debug)]
+pub struct Keyframes<T> {
+    pub times: Vec<f32>,
+    pub values: Vec<T>,
+    pub interpolation: Interpolation,
+}
+
+impl<T: Keyframe> Keyframes<T> {
+    /// Panics unless there are [Interpolation::values_per_key] values for every time.
+    pub fn new(times: Vec<f32>, values: Vec<T>, interpolation: Interpolation) -> Self {
+        let per_key = interpolation.values_per_key();
+        assert_eq!(values.len(), times.len() * per_key, "{interpolation:?} keyframes need {per_key} values per time");
+        Self { times, values, interpolation }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.times.last().copied().unwrap_or(0.0)
+    }
+
+    fn value(&self, key: usize) -> T {
+        match self.interpolation {
+            Interpolation::CubicSpline => self.values[key * 3 + 1],
+            _ => self.values[key],
+        }
+    }
+
+    /// Holds the first and last values before and after the keyframes. `None` without any.
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let next = self.times.partition_point(|&key| key <= time);
+        if self.times.is_empty() {
+            return None;
+        } else if next == 0 {
+            return Some(self.value(0));
+        } else if next == self.times.len() {
+            return Some(self.value(next - 1));
+        }
+        let key = next - 1;
+        let duration = self.times[next] - self.times[key];
+        let t = (time - self.times[key]) / duration;
+        Some(match self.interpolation {
+            Interpolation::Step => self.value(key),
+            Interpolation::Linear => self.value(key).interpolate(self.value(next), t),
+            Interpolation::CubicSpline => {
+                let out_tangent = self.values[key * 3 + 2];
+                let in_tangent = self.values[next * 3];
+                self.value(key).hermite(out_tangent, self.value(next), in_tangent, t, duration)
+            }
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ChannelValues {
+    Translation(Keyframes<Vec3>),
+    Rotation(Keyframes<Quat>),
+    Scale(Keyframes<Vec3>),
+}
+
+/// One property of one joint over time.
+#[derive(Clone, Debug)]
+pub struct Channel {
+    pub joint: usize,
+    pub values: ChannelValues,
+}
+
+impl Channel {
+    pub fn duration(&self) -> f32 {
+        match &self.values {
+            ChannelValues::Translation(keyframes) | ChannelValues::Scale(keyframes) => keyframes.duration(),
+            ChannelValues::Rotation(keyframes) => keyframes.duration(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    pub name: String,
+    pub channels: Vec<Channel>,
+    pub duration: f32,
+}
+
+impl AnimationClip {
+    /// Lasts until the last keyframe of any channel.
+    pub fn new(name: &str, channels: Vec<Channel>) -> Self {
+        let duration = channels.iter().map(Channel::duration).fold(0.0, f32::max);
+        Self { name: name.to_owned(), channels, duration }
+    }
+
+    /// Overwrites the animated properties of `pose`, leaving the rest as they are.
+    pub fn apply(&self, time: f32, pose: &mut [Transform]) {
+        for channel in &self.channels {
+            let Some(transform) = pose.get_mut(channel.joint) else {
+                continue;
+            };
+            match &channel.values {
+                ChannelValues::Translation(keyframes) => transform.translation = keyframes.sample(time).unwrap_or(transform.translation),
+                ChannelValues::Rotation(keyframes) => transform.rotation = keyframes.sample(time).unwrap_or(transform.rotation),
+                ChannelValues::Scale(keyframes) => transform.scale = keyframes.sample(time).unwrap_or(transform.scale),
+            }
+        }
+    }
+
+    /// The pose at `time`, with the joints the clip does not move at rest.
+    pub fn sample(&self, skeleton: &Skeleton, time: f32) -> Vec<Transform> {
+        let mut pose = skeleton.rest_pose();
+        self.apply(time, &mut pose);
+        pose
+    }
+}
+
+/// `weight` of the way from pose `a` to pose `b`, joint by joint.
+pub fn blend_poses(a: &[Transform], b: &[Transform], weight: f32) -> Vec<Transform> {
+    a.iter().zip(b).map(|(a, b)| a.blend(b, weight)).collect()
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Playback {
+    clip: usize,
+    time: f32,
+}
+
+/// Plays one clip of a set at a time, cross-fading from the previous one when asked to.
+#[derive(Clone, Debug)]
+pub struct AnimationPlayer {
+    current: Playback,
+    /// The clip faded out of, with how far into the fade and how long it takes.
+    fading: Option<(Playback, f32, f32)>,
+    pub speed: f32,
+    pub looping: bool,
+}
+
+impl AnimationPlayer {
+    pub fn new(clip: usize) -> Self {
+        Self { current: Playback { clip, time: 0.0 }, fading: None, speed: 1.0, looping: true }
+    }
+
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn clip(&self) -> usize {
+        self.current.clip
+    }
+
+    pub fn time(&self) -> f32 {
+        self.current.time
+    }
+
+    /// Switches to `clip` from its start at once.
+    pub fn play(&mut self, clip: usize) {
+        self.current = Playback { clip, time: 0.0 };
+        self.fading = None;
+    }
+
+    /// Starts `clip` and blends over to it from the playing clip over `duration` seconds.
+    pub fn cross_fade(&mut self, clip: usize, duration: f32) {
+        self.fading = Some((self.current, 0.0, duration));
+        self.current = Playback { clip, time: 0.0 };
+    }
+
+    fn wrap(&self, playback: &mut Playback, clips: &[AnimationClip]) {
+        let duration = clips[playback.clip].duration;
+        playback.time = if self.looping && duration > 0.0 {
+            playback.time.rem_euclid(duration)
+        } else {
+            playback.time.clamp(0.0, duration)
+        };
+    }
+
+    pub fn advance(&mut self, seconds: f32, clips: &[AnimationClip]) {
+        let step = seconds * self.speed;
+        let mut current = self.current;
+        current.time += step;
+        self.wrap(&mut current, clips);
+        self.current = current;
+        if let Some((mut previous, elapsed, duration)) = self.fading {
+            previous.time += step;
+            self.wrap(&mut previous, clips);
+            let elapsed = elapsed + seconds;
+            self.fading = (elapsed < duration).then_some((previous, elapsed, duration));
+        }
+    }
+
+    pub fn pose(&self, skeleton: &Skeleton, clips: &[AnimationClip]) -> Vec<Transform> {
+        let pose = clips[self.current.clip].sample(skeleton, self.current.time);
+        match self.fading {
+            Some((previous, elapsed, duration)) => {
+                let previous = clips[previous.clip].sample(skeleton, previous.time);
+                blend_poses(&previous, &pose, elapsed / duration)
+            }
+            None => pose,
+        }
+    }
+}
+
+/// The joint matrices of one posed skeleton on the GPU, bound as a storage buffer for
+/// [`crate::shaders::LIT_SKINNED`].
+pub struct JointBuffer {
+    pub buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    pub num_joints: usize,
+}
+
+impl JointBuffer {
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("joint_bind_group_layout"),
+        })
+    }
+
+    /// Starts out with every joint at its bind pose.
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, num_joints: usize) -> Self {
+        // Bindings can not be empty, so skeletons without joints still get one.
+        let matrices = vec![Mat4::IDENTITY; num_joints.max(1)];
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Joint Buffer"),
+            contents: bytemuck::cast_slice(&matrices),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("joint_bind_group"),
+        });
+        Self { buffer, bind_group, num_joints }
+    }
+
+    /// Takes the matrices of [`Skeleton::joint_matrices`].
+    pub fn update(&self, queue: &wgpu::Queue, matrices: &[Mat4]) {
+        let count = matrices.len().min(self.num_joints);
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&matrices[..count]));
+    }
+}
+
+/// A model whose meshes are made of [`SkinnedVertex`] with the skeleton moving them and the
+/// clips it can play.
+pub struct SkinnedModel {
+    pub model: MatModel,
+    pub skeleton: Skeleton,
+    pub clips: Vec<AnimationClip>,
+}
+
+impl SkinnedModel {
+    pub fn find_clip(&self, name: &str) -> Option<usize> {
+        self.clips.iter().position(|clip| clip.name == name)
+    }
+}
+
+impl HasMeshes for SkinnedModel {
+    fn meshes(
+        &self,
+    ) -> &Vec<Mesh> {
+        &self.model.meshes
+    }
+}
+
+impl HasMaterials for SkinnedModel {
+    fn materials(
+        &self,
+    ) -> &Vec<impl Material> {
+        &self.model.materials
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cubic_spline_keeps_tangents_beside_values() {
+        let keyframes = Keyframes::new(vec![0.0, 1.0], vec![Vec3::ZERO, Vec3::ZERO, Vec3::ZERO, Vec3::ZERO, Vec3::ONE, Vec3::ZERO], Interpolation::CubicSpline);
+        assert_eq!(keyframes.sample(0.5), Some(Vec3::splat(0.5)));
+    }
+
+    #[test]
+    #[should_panic(expected = "values per time")]
+    fn cubic_spline_without_tangents_panics_on_creation() {
+        Keyframes::new(vec![0.0, 1.0], vec![Vec3::ZERO, Vec3::ONE], Interpolation::CubicSpline);
+    }
+}
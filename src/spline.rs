@@ -0,0 +1,221 @@
+use std::f32::consts::FRAC_PI_2;
+use glam::{vec3, Vec3};
+use crate::shapes::rotation_minimizing_frames;
+
+/// Arc length samples per segment, enough to keep the speed along the curve even to a few
+/// parts in a thousand.
+const SAMPLES: usize = 32;
+
+/// A point on a curve with its moving frame: the direction of travel and two axes across it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub position: Vec3,
+    pub tangent: Vec3,
+    pub normal: Vec3,
+    pub binormal: Vec3,
+}
+
+/// A smooth 3D curve, stored as cubic Bézier segments whatever it was made from. Curves are
+/// evaluated at a parameter running from 0 at the start to 1 at the end, spread evenly over
+/// the segments, or at a distance along the curve.
+#[derive(Clone, Debug)]
+pub struct Spline {
+    segments: Vec<[Vec3; 4]>,
+    /// The arc length up to each sample, `SAMPLES` for every segment after the first zero.
+    lengths: Vec<f32>,
+}
+
+impl Spline {
+    /// Through every point, with the tangent at each one along the line between its neighbours.
+    /// Open curves extend their ends straight, closed ones join the last point to the first.
+    pub fn catmull_rom(points: &[Vec3], closed: bool) -> Self {
+        let count = points.len();
+        if count < 2 {
+            return Self::point(points.first().copied().unwrap_or(Vec3::ZERO));
+        }
+        let point = |i: isize| {
+            if closed {
+                points[i.rem_euclid(count as isize) as usize]
+            } else if i < 0 {
+                2.0 * points[0] - points[1]
+            } else if i >= count as isize {
+                2.0 * points[count - 1] - points[count - 2]
+            } else {
+                points[i as usize]
+            }
+        };
+        let num_segments = if closed { count } else { count - 1 } as isize;
+        let segments = (0..num_segments)
+            .map(|i| {
+                let [p0, p1, p2, p3] = [point(i - 1), point(i), point(i + 1), point(i + 2)];
+                [p1, p1 + (p2 - p0) / 6.0, p2 - (p3 - p1) / 6.0, p2]
+            })
+            .collect();
+        Self::from_segments(segments)
+    }
+
+    /// Cubic Bézier segments sharing their end points, so `3n + 1` control points for `n`
+    /// segments. Left over points at the end are ignored.
+    pub fn bezier(controls: &[Vec3]) -> Self {
+        if controls.len() < 4 {
+            return Self::point(controls.first().copied().unwrap_or(Vec3::ZERO));
+        }
+        let segments = (0..(controls.len() - 1) / 3)
+            .map(|i| [controls[3 * i], controls[3 * i + 1], controls[3 * i + 2], controls[3 * i + 3]])
+            .collect();
+        Self::from_segments(segments)
+    }
+
+    /// A uniform cubic B-spline, which is smoother than a Catmull-Rom curve but only passes near
+    /// its points. Open curves repeat their end points so they still start and end on them.
+    pub fn b_spline(points: &[Vec3], closed: bool) -> Self {
+        let count = points.len();
+        if count < 2 {
+            return Self::point(points.first().copied().unwrap_or(Vec3::ZERO));
+        }
+        let padded = if closed {
+            (0..count + 3).map(|i| points[i % count]).collect::<Vec<_>>()
+        } else {
+            let (first, last) = (points[0], points[count - 1]);
+            [first, first].into_iter().chain(points.iter().copied()).chain([last, last]).collect()
+        };
+        let segments = padded
+            .windows(4)
+            .map(|window| {
+                let [p0, p1, p2, p3] = [window[0], window[1], window[2], window[3]];
+                [(p0 + 4.0 * p1 + p2) / 6.0, (2.0 * p1 + p2) / 3.0, (p1 + 2.0 * p2) / 3.0, (p1 + 4.0 * p2 + p3) / 6.0]
+            })
+            .collect();
+        Self::from_segments(segments)
+    }
+
+    /// A circle in the XZ plane, counter-clockwise seen from above and starting on the x axis,
+    /// for turntables.
+    pub fn circle(center: Vec3, radius: f32) -> Self {
+        // The usual four segment approximation, off by less than 0.03% of the radius.
+        let handle = radius * 4.0 / 3.0 * (FRAC_PI_2 / 4.0).tan();
+        let point = |angle: f32| center + radius * vec3(angle.cos(), 0.0, -angle.sin());
+        let tangent = |angle: f32| vec3(-angle.sin(), 0.0, -angle.cos());
+        let segments = (0..4)
+            .map(|i| {
+                let (start, end) = (i as f32 * FRAC_PI_2, (i + 1) as f32 * FRAC_PI_2);
+                [point(start), point(start) + handle * tangent(start), point(end) - handle * tangent(end), point(end)]
+            })
+            .collect();
+        Self::from_segments(segments)
+    }
+
+    fn point(point: Vec3) -> Self {
+        Self::from_segments(vec![[point; 4]])
+    }
+
+    fn from_segments(segments: Vec<[Vec3; 4]>) -> Self {
+        let mut spline = Self { segments, lengths: vec![0.0] };
+        let steps = spline.segments.len() * SAMPLES;
+        let mut previous = spline.position(0.0);
+        let mut length = 0.0;
+        for step in 1..=steps {
+            let position = spline.position(step as f32 / steps as f32);
+            length += position.distance(previous);
+            spline.lengths.push(length);
+            previous = position;
+        }
+        spline
+    }
+
+    /// Each segment as its four Bézier control points.
+    pub fn segments(&self) -> &[[Vec3; 4]] {
+        &self.segments
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.start().distance_squared(self.end()) <= f32::EPSILON
+    }
+
+    pub fn start(&self) -> Vec3 {
+        self.segments[0][0]
+    }
+
+    pub fn end(&self) -> Vec3 {
+        self.segments[self.segments.len() - 1][3]
+    }
+
+    /// The segment and the parameter within it.
+    fn locate(&self, t: f32) -> (&[Vec3; 4], f32) {
+        let count = self.segments.len();
+        let t = t.clamp(0.0, 1.0) * count as f32;
+        let index = (t as usize).min(count - 1);
+        (&self.segments[index], t - index as f32)
+    }
+
+    pub fn position(&self, t: f32) -> Vec3 {
+        let ([b0, b1, b2, b3], t) = self.locate(t);
+        let s = 1.0 - t;
+        s * s * s * *b0 + 3.0 * s * s * t * *b1 + 3.0 * s * t * t * *b2 + t * t * t * *b3
+    }
+
+    /// The rate of change of the position with the parameter, so not normalized.
+    pub fn derivative(&self, t: f32) -> Vec3 {
+        let ([b0, b1, b2, b3], t) = self.locate(t);
+        let s = 1.0 - t;
+        let scale = 3.0 * self.segments.len() as f32;
+        scale * (s * s * (*b1 - *b0) + 2.0 * s * t * (*b2 - *b1) + t * t * (*b3 - *b2))
+    }
+
+    pub fn second_derivative(&self, t: f32) -> Vec3 {
+        let ([b0, b1, b2, b3], t) = self.locate(t);
+        let count = self.segments.len() as f32;
+        6.0 * count * count * ((1.0 - t) * (*b2 - 2.0 * *b1 + *b0) + t * (*b3 - 2.0 * *b2 + *b1))
+    }
+
+    /// The direction of travel, or zero where the curve stands still.
+    pub fn tangent(&self, t: f32) -> Vec3 {
+        self.derivative(t).normalize_or_zero()
+    }
+
+    /// The Frenet frame, with the normal towards the inside of the bend. Along straight parts
+    /// the normal is an arbitrary direction across the curve, so use [`Spline::frames`] for
+    /// anything that should not flip or twist.
+    pub fn frame(&self, t: f32) -> Frame {
+        let first = self.derivative(t);
+        let tangent = first.normalize_or(Vec3::Z);
+        let binormal = first.cross(self.second_derivative(t)).try_normalize().unwrap_or_else(|| tangent.any_orthonormal_vector());
+        Frame { position: self.position(t), tangent, normal: binormal.cross(tangent), binormal }
+    }
+
+    /// The length of the whole curve.
+    pub fn length(&self) -> f32 {
+        self.lengths[self.lengths.len() - 1]
+    }
+
+    /// The parameter at a distance along the curve, clamped to its ends.
+    pub fn parameter_at(&self, distance: f32) -> f32 {
+        let steps = self.lengths.len() - 1;
+        let distance = distance.clamp(0.0, self.length());
+        let next = self.lengths.partition_point(|&length| length < distance).clamp(1, steps);
+        let (before, after) = (self.lengths[next - 1], self.lengths[next]);
+        let within = if after > before { (distance - before) / (after - before) } else { 0.0 };
+        (next as f32 - 1.0 + within) / steps as f32
+    }
+
+    pub fn position_at(&self, distance: f32) -> Vec3 {
+        self.position(self.parameter_at(distance))
+    }
+
+    /// Points evenly spaced along the curve, from start to end.
+    pub fn points(&self, count: usize) -> Vec<Vec3> {
+        let spacing = self.length() / count.saturating_sub(1).max(1) as f32;
+        (0..count).map(|i| self.position_at(i as f32 * spacing)).collect()
+    }
+
+    /// Rotation minimizing frames at points evenly spaced along the curve, which turn with the
+    /// curve but never twist around it.
+    pub fn frames(&self, count: usize) -> Vec<Frame> {
+        let points = self.points(count);
+        rotation_minimizing_frames(&points)
+            .into_iter()
+            .zip(points)
+            .map(|((tangent, normal, binormal), position)| Frame { position, tangent, normal, binormal })
+            .collect()
+    }
+}
@@ -0,0 +1,390 @@
+use std::{collections::{HashMap, HashSet}, ops::{Add, Mul}};
+use glam::{Vec2, Vec3};
+use crate::{builder::MeshBuilder, mesh_data::MeshData, model::{Mesh, ModelVertex}, normals::{self, NormalMode, NormalWeighting}};
+
+/// A mesh of polygons with any number of corners, like the faces of an OBJ file before they
+/// are triangulated.
+#[derive(Clone, Debug, Default)]
+pub struct PolygonMesh {
+    pub positions: Vec<Vec3>,
+    /// Empty when the mesh has no texture coordinates.
+    pub tex_coords: Vec<Vec2>,
+    /// Counter-clockwise loops of vertex indices.
+    pub faces: Vec<Vec<u32>>,
+}
+
+impl PolygonMesh {
+    pub fn from_data(data: &MeshData) -> Self {
+        Self {
+            positions: data.positions.clone(),
+            tex_coords: data.tex_coords.clone(),
+            faces: data.indices.chunks(3).map(<[u32]>::to_vec).collect(),
+        }
+    }
+
+    /// Fans the faces into triangles, splitting quads along their shorter diagonal. Normals are
+    /// smoothed across edges up to `crease_angle` radians.
+    pub fn to_data(&self, crease_angle: f32) -> MeshData {
+        let position = |index: u32| self.positions[index as usize];
+        let indices = self
+            .faces
+            .iter()
+            .flat_map(|face| {
+                let mut face = face.clone();
+                if face.len() == 4 && position(face[1]).distance_squared(position(face[3])) < position(face[0]).distance_squared(position(face[2])) {
+                    face.rotate_left(1);
+                }
+                (1..face.len().saturating_sub(1)).flat_map(move |i| [face[0], face[i], face[i + 1]]).collect::<Vec<_>>()
+            })
+            .collect();
+        let mut builder = MeshBuilder::<ModelVertex>::new("Polygon Mesh", self.positions.clone())
+            .indices(indices)
+            .normal_mode(NormalMode::Smooth { crease_angle, weighting: NormalWeighting::Angle });
+        if !self.tex_coords.is_empty() {
+            builder = builder.tex_coords(self.tex_coords.clone());
+        }
+        builder.build_data()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Scheme {
+    Loop,
+    CatmullClark,
+}
+
+fn edge(a: u32, b: u32) -> (u32, u32) {
+    (a.min(b), a.max(b))
+}
+
+fn face_edges(face: &[u32]) -> impl Iterator<Item = (u32, u32)> + '_ {
+    (0..face.len()).map(|i| (face[i], face[(i + 1) % face.len()]))
+}
+
+/// The new values of one attribute after a step of subdivision, for the old vertices, the
+/// edges and the faces. Only Catmull-Clark makes face points.
+struct Refined<T> {
+    vertices: Vec<T>,
+    edges: HashMap<(u32, u32), T>,
+    faces: Vec<T>,
+}
+
+/// Applies the subdivision rules to one attribute over the connectivity given by `faces`.
+/// Edges with other than two faces and edges for which `crease` returns true are sharp: their
+/// edge points stay on the edge, vertices on two of them follow the crease and vertices on
+/// more stay where they are. Without `smooth` everything is interpolated linearly instead.
+fn refine<T>(values: &[T], faces: &[Vec<u32>], scheme: Scheme, smooth: bool, crease: impl Fn((u32, u32)) -> bool) -> Refined<T>
+where
+    T: Copy + Default + Add<Output = T> + Mul<f32, Output = T>,
+{
+    let mut edge_faces: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+    let mut neighbours = vec![vec![]; values.len()];
+    let mut vertex_faces = vec![vec![]; values.len()];
+    for (index, face) in faces.iter().enumerate() {
+        for (a, b) in face_edges(face) {
+            let faces = edge_faces.entry(edge(a, b)).or_default();
+            if faces.is_empty() {
+                neighbours[a as usize].push(b);
+                neighbours[b as usize].push(a);
+            }
+            faces.push(index);
+            vertex_faces[a as usize].push(index);
+        }
+    }
+    let is_sharp = |key: (u32, u32)| !smooth || edge_faces[&key].len() != 2 || crease(key);
+    let value = |index: u32| values[index as usize];
+    let average = |indices: &mut dyn Iterator<Item = T>| {
+        let (sum, count) = indices.fold((T::default(), 0), |(sum, count), value| (sum + value, count + 1));
+        sum * (1.0 / count.max(1) as f32)
+    };
+
+    let face_points = match scheme {
+        Scheme::Loop => vec![],
+        Scheme::CatmullClark => faces.iter().map(|face| average(&mut face.iter().map(|&i| value(i)))).collect(),
+    };
+
+    let edge_points = edge_faces
+        .iter()
+        .map(|(&key, adjacent)| {
+            let (a, b) = (value(key.0), value(key.1));
+            let point = if is_sharp(key) {
+                (a + b) * 0.5
+            } else {
+                match scheme {
+                    Scheme::Loop => {
+                        let opposite = adjacent.iter().map(|&face| {
+                            let corner = faces[face].iter().find(|&&i| i != key.0 && i != key.1).copied().unwrap_or(key.0);
+                            value(corner)
+                        });
+                        (a + b) * 0.375 + opposite.fold(T::default(), |sum, value| sum + value) * 0.125
+                    }
+                    Scheme::CatmullClark => (a + b + face_points[adjacent[0]] + face_points[adjacent[1]]) * 0.25,
+                }
+            };
+            (key, point)
+        })
+        .collect();
+
+    let vertex_points = (0..values.len())
+        .map(|vertex| {
+            let v = values[vertex];
+            let around = &neighbours[vertex];
+            let sharp = around.iter().filter(|&&other| is_sharp(edge(vertex as u32, other))).collect::<Vec<_>>();
+            let n = around.len() as f32;
+            match sharp.len() {
+                _ if around.is_empty() || !smooth => v,
+                0 | 1 => match scheme {
+                    Scheme::Loop => {
+                        let beta = if around.len() == 3 { 3.0 / 16.0 } else { 3.0 / (8.0 * n) };
+                        v * (1.0 - n * beta) + average(&mut around.iter().map(|&i| value(i))) * (n * beta)
+                    }
+                    Scheme::CatmullClark => {
+                        let face_average = average(&mut vertex_faces[vertex].iter().map(|&face| face_points[face]));
+                        let edge_average = average(&mut around.iter().map(|&i| (v + value(i)) * 0.5));
+                        (face_average + edge_average * 2.0 + v * (n - 3.0)) * (1.0 / n)
+                    }
+                },
+                2 => (v * 6.0 + value(*sharp[0]) + value(*sharp[1])) * 0.125,
+                _ => v,
+            }
+        })
+        .collect();
+
+    Refined { vertices: vertex_points, edges: edge_points, faces: face_points }
+}
+
+/// Newell's normal, which copes with polygons that are not quite flat.
+fn polygon_normal(positions: &[Vec3], face: &[u32]) -> Vec3 {
+    face_edges(face)
+        .map(|(a, b)| positions[a as usize].cross(positions[b as usize]))
+        .fold(Vec3::ZERO, |sum, cross| sum + cross)
+        .normalize_or_zero()
+}
+
+fn subdivide(mesh: &PolygonMesh, levels: u32, crease_angle: f32, scheme: Scheme) -> PolygonMesh {
+    // Vertices that only differ in attributes that get recomputed, like normals, are merged so
+    // only real UV seams split the surface.
+    let has_tex_coords = !mesh.tex_coords.is_empty();
+    let mut lookup = HashMap::new();
+    let mut positions = vec![];
+    let mut tex_coords = vec![];
+    let remap = (0..mesh.positions.len())
+        .map(|i| {
+            let (position, uv) = (mesh.positions[i], mesh.tex_coords.get(i).copied().unwrap_or_default());
+            *lookup.entry((position.to_array().map(f32::to_bits), uv.to_array().map(f32::to_bits))).or_insert_with(|| {
+                positions.push(position);
+                tex_coords.push(uv);
+                positions.len() as u32 - 1
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut faces = mesh
+        .faces
+        .iter()
+        .map(|face| face.iter().map(|&i| remap[i as usize]).collect::<Vec<_>>())
+        .filter(|face| face.len() >= 3 && face.iter().collect::<HashSet<_>>().len() == face.len())
+        .collect::<Vec<_>>();
+
+    // The surface is shaped by points shared across the seams. The texture coordinates are
+    // interpolated linearly over the vertices instead, which keeps the seams where they are
+    // and cannot fold the texture where UV islands meet at odd angles.
+    let (point_of_vertex, num_points) = normals::position_groups(&positions);
+    let mut point_of_vertex = point_of_vertex.into_iter().map(|point| point as u32).collect::<Vec<_>>();
+    let mut points = vec![Vec3::ZERO; num_points];
+    for (vertex, &point) in point_of_vertex.iter().enumerate() {
+        points[point as usize] = positions[vertex];
+    }
+
+    let mut creases = HashSet::new();
+    let mut edge_normals: HashMap<(u32, u32), Vec<Vec3>> = HashMap::new();
+    for face in &faces {
+        let point_face = face.iter().map(|&i| point_of_vertex[i as usize]).collect::<Vec<_>>();
+        let normal = polygon_normal(&points, &point_face);
+        for (a, b) in face_edges(&point_face) {
+            edge_normals.entry(edge(a, b)).or_default().push(normal);
+        }
+    }
+    for (key, normals) in edge_normals {
+        if normals.len() == 2 && normals[0].angle_between(normals[1]) > crease_angle {
+            creases.insert(key);
+        }
+    }
+
+    for _ in 0..levels {
+        let point_faces = faces
+            .iter()
+            .map(|face| face.iter().map(|&i| point_of_vertex[i as usize]).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let point_of = |vertex: u32| point_of_vertex[vertex as usize];
+        let refined_points = refine(&points, &point_faces, scheme, true, |key| creases.contains(&key));
+        let refined_tex_coords = has_tex_coords.then(|| refine(&tex_coords, &faces, scheme, false, |_| true));
+
+        // The old vertices and points keep their numbers, followed by the edges and faces.
+        let mut new_points = refined_points.vertices.clone();
+        let mut point_edges = HashMap::new();
+        let mut new_point_of_vertex = point_of_vertex.clone();
+        let mut new_tex_coords = refined_tex_coords.as_ref().map_or(vec![], |refined| refined.vertices.clone());
+        let mut vertex_edges = HashMap::new();
+        let mut edge_vertex = |a: u32, b: u32, new_points: &mut Vec<Vec3>, new_point_of_vertex: &mut Vec<u32>, new_tex_coords: &mut Vec<Vec2>| {
+            *vertex_edges.entry(edge(a, b)).or_insert_with(|| {
+                let point_edge = edge(point_of(a), point_of(b));
+                let point = *point_edges.entry(point_edge).or_insert_with(|| {
+                    new_points.push(refined_points.edges[&point_edge]);
+                    new_points.len() as u32 - 1
+                });
+                new_point_of_vertex.push(point);
+                if let Some(refined) = &refined_tex_coords {
+                    new_tex_coords.push(refined.edges[&edge(a, b)]);
+                }
+                new_point_of_vertex.len() as u32 - 1
+            })
+        };
+
+        let mut new_faces = vec![];
+        for (index, face) in faces.iter().enumerate() {
+            let count = face.len();
+            let edges = (0..count)
+                .map(|i| edge_vertex(face[i], face[(i + 1) % count], &mut new_points, &mut new_point_of_vertex, &mut new_tex_coords))
+                .collect::<Vec<_>>();
+            match scheme {
+                Scheme::Loop => {
+                    let ([a, b, c], [ab, bc, ca]) = ([face[0], face[1], face[2]], [edges[0], edges[1], edges[2]]);
+                    new_faces.extend([vec![a, ab, ca], vec![ab, b, bc], vec![ca, bc, c], vec![ab, bc, ca]]);
+                }
+                Scheme::CatmullClark => {
+                    new_points.push(refined_points.faces[index]);
+                    new_point_of_vertex.push(new_points.len() as u32 - 1);
+                    if let Some(refined) = &refined_tex_coords {
+                        new_tex_coords.push(refined.faces[index]);
+                    }
+                    let center = new_point_of_vertex.len() as u32 - 1;
+                    new_faces.extend((0..count).map(|i| vec![face[i], edges[i], center, edges[(i + count - 1) % count]]));
+                }
+            }
+        }
+
+        creases = creases
+            .iter()
+            .flat_map(|&(a, b)| {
+                let middle = point_edges[&(a, b)];
+                [edge(a, middle), edge(middle, b)]
+            })
+            .collect();
+        points = new_points;
+        point_of_vertex = new_point_of_vertex;
+        if has_tex_coords {
+            tex_coords = new_tex_coords;
+        }
+        faces = new_faces;
+    }
+
+    PolygonMesh {
+        positions: point_of_vertex.iter().map(|&point| points[point as usize]).collect(),
+        tex_coords: if has_tex_coords { tex_coords } else { vec![] },
+        faces,
+    }
+}
+
+/// Smooths a triangle mesh with `levels` steps of Loop subdivision, each splitting every
+/// triangle into four. Edges between faces meeting at more than `crease_angle` radians stay
+/// sharp, as do the borders of the mesh, and UV seams are kept.
+pub fn loop_subdivide_data(data: &MeshData, levels: u32, crease_angle: f32) -> MeshData {
+    subdivide(&PolygonMesh::from_data(data), levels, crease_angle, Scheme::Loop).to_data(crease_angle)
+}
+
+pub fn loop_subdivide(data: &MeshData, levels: u32, crease_angle: f32, material: usize, device: &wgpu::Device) -> Mesh {
+    MeshBuilder::<ModelVertex>::from_data("Loop Subdivision", loop_subdivide_data(data, levels, crease_angle))
+        .material(material)
+        .build(device)
+}
+
+/// Smooths a mesh of polygons with `levels` steps of Catmull-Clark subdivision, each splitting
+/// every face into quads around its center. Best suited to quad cages, with sharp edges chosen
+/// like in [`loop_subdivide_data`].
+pub fn catmull_clark_data(mesh: &PolygonMesh, levels: u32, crease_angle: f32) -> MeshData {
+    subdivide(mesh, levels, crease_angle, Scheme::CatmullClark).to_data(crease_angle)
+}
+
+pub fn catmull_clark(mesh: &PolygonMesh, levels: u32, crease_angle: f32, material: usize, device: &wgpu::Device) -> Mesh {
+    MeshBuilder::<ModelVertex>::from_data("Catmull-Clark Subdivision", catmull_clark_data(mesh, levels, crease_angle))
+        .material(material)
+        .build(device)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, f32::consts::PI};
+    use glam::vec2;
+    use crate::{shapes::cube_data, validation::validate};
+    use super::*;
+
+    /// A cube of quads from -1 to 1, with corner `i` offset by bit 0 of `i` along x, bit 1
+    /// along y and bit 2 along z.
+    fn quad_cube() -> PolygonMesh {
+        let bit = |i: u32, bit: u32| if i & bit == 0 { -1.0 } else { 1.0 };
+        PolygonMesh {
+            positions: (0..8).map(|i| Vec3::new(bit(i, 1), bit(i, 2), bit(i, 4))).collect(),
+            tex_coords: vec![],
+            faces: vec![vec![0, 2, 3, 1], vec![4, 5, 7, 6], vec![0, 1, 5, 4], vec![2, 6, 7, 3], vec![0, 4, 6, 2], vec![1, 3, 7, 5]],
+        }
+    }
+
+    fn assert_closed_inside_cage(data: &MeshData) {
+        // Without texture coordinates there are no proper tangents, so only the shape is checked.
+        let report = validate(data);
+        let problems = report.boundary_edges + report.non_manifold_edges + report.inconsistent_edges + report.flipped_triangles + report.zero_area_triangles;
+        assert_eq!(problems, 0, "{report}");
+        assert!(data.positions.iter().all(|position| position.abs().max_element() <= 1.0 + 1e-5));
+        assert!(data.volume() > 0.0 && data.volume() <= 8.0 + 1e-4, "volume {}", data.volume());
+    }
+
+    #[test]
+    fn loop_splits_every_triangle_into_four() {
+        let cube = cube_data(Vec3::splat(2.0));
+        for levels in 0..4 {
+            assert_eq!(loop_subdivide_data(&cube, levels, PI).num_triangles(), 12 * 4usize.pow(levels));
+        }
+    }
+
+    #[test]
+    fn closed_cube_stays_closed_inside_its_cage() {
+        assert_closed_inside_cage(&loop_subdivide_data(&cube_data(Vec3::splat(2.0)), 3, PI));
+        assert_closed_inside_cage(&catmull_clark_data(&quad_cube(), 3, PI));
+    }
+
+    #[test]
+    fn creases_stay_on_their_edges() {
+        // Every edge of the cube is sharper than the crease angle, so the cube keeps its shape
+        // and each edge line gets the points splitting it.
+        let levels = 2;
+        let on_edge_lines = |data: &MeshData| {
+            data.positions
+                .iter()
+                .filter(|position| position.abs().to_array().iter().filter(|&&coordinate| (coordinate - 1.0).abs() < 1e-5).count() >= 2)
+                .map(|position| position.to_array().map(f32::to_bits))
+                .collect::<HashSet<_>>()
+                .len()
+        };
+        let expected = 8 + 12 * (2usize.pow(levels) - 1);
+        for data in [loop_subdivide_data(&cube_data(Vec3::splat(2.0)), levels, 0.5), catmull_clark_data(&quad_cube(), levels, 0.5)] {
+            assert!(data.positions.iter().all(|position| (position.abs().max_element() - 1.0).abs() < 1e-5));
+            assert_eq!(on_edge_lines(&data), expected);
+            assert_closed_inside_cage(&data);
+        }
+    }
+
+    #[test]
+    fn seams_keep_both_sides() {
+        // The middle of the edge between the +x and +y faces, where each face has its own UVs.
+        let data = loop_subdivide_data(&cube_data(Vec3::splat(2.0)), 1, 0.5);
+        let tex_coords = data
+            .positions
+            .iter()
+            .zip(&data.tex_coords)
+            .filter(|(position, _)| position.distance(Vec3::new(1.0, 1.0, 0.0)) < 1e-5)
+            .map(|(_, tex_coords)| tex_coords.to_array().map(f32::to_bits))
+            .collect::<HashSet<_>>();
+        let expected = [vec2(0.5, 0.0), vec2(1.0, 0.5)].map(|tex_coords| tex_coords.to_array().map(f32::to_bits));
+        assert_eq!(tex_coords, HashSet::from(expected));
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use glam::{Vec2, Vec3, Vec4};
+
+pub struct GeneratedTangents {
+    /// The tangent in `xyz` and the bitangent sign in `w`, `bitangent = cross(normal, tangent) * w`.
+    pub tangents: Vec<Vec4>,
+    pub indices: Vec<u32>,
+    /// The source vertex of every generated vertex.
+    pub remap: Vec<u32>,
+}
+
+impl GeneratedTangents {
+    pub fn remap_attribute<T: Copy>(&self, attribute: &[T]) -> Vec<T> {
+        self.remap.iter().map(|&i| attribute[i as usize]).collect()
+    }
+}
+
+struct Geometry<'a> {
+    positions: &'a [Vec3],
+    normals: &'a [Vec3],
+    tex_coords: &'a [Vec2],
+    indices: &'a [u32],
+    tangents: Vec<Vec4>,
+}
+
+impl Geometry<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> usize {
+        self.indices[face * 3 + vert] as usize
+    }
+}
+
+impl mikktspace::Geometry for Geometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.positions[self.vertex(face, vert)].to_array()
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.normals[self.vertex(face, vert)].to_array()
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        // Undo the v flip done when loading so the bitangent follows +v like in the baking tools.
+        let tex_coords = self.tex_coords[self.vertex(face, vert)];
+        [tex_coords.x, 1.0 - tex_coords.y]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = Vec4::from_array(tangent);
+    }
+}
+
+fn fallback_tangent(normal: Vec3) -> Vec4 {
+    normal.normalize_or(Vec3::Z).any_orthonormal_vector().extend(1.0)
+}
+
+fn sanitize(tangent: Vec4, normal: Vec3) -> Vec4 {
+    let direction = tangent.truncate();
+    if !tangent.is_finite() || direction.length_squared() < 1e-12 {
+        return fallback_tangent(normal);
+    }
+    let sign = if tangent.w < 0.0 { -1.0 } else { 1.0 };
+    direction.normalize().extend(sign)
+}
+
+/// Generates MikkTSpace tangents, splitting vertices wherever corners that used to share
+/// a vertex end up with different tangents. Texture coordinates are expected to be flipped
+/// vertically like the ones in [`crate::model::ModelVertex`].
+///
+/// Triangles with degenerate texture coordinates never produce NaN or infinite tangents;
+/// vertices that cannot get one from their neighbours get an arbitrary tangent perpendicular to the normal.
+pub fn generate_tangents(positions: &[Vec3], normals: &[Vec3], tex_coords: &[Vec2], indices: &[u32]) -> GeneratedTangents {
+    let mut geometry = Geometry {
+        positions,
+        normals,
+        tex_coords,
+        indices,
+        tangents: vec![Vec4::ZERO; indices.len()],
+    };
+    if !mikktspace::generate_tangents(&mut geometry) {
+        geometry.tangents.fill(Vec4::ZERO);
+    }
+
+    let mut generated = GeneratedTangents { tangents: vec![], indices: Vec::with_capacity(indices.len()), remap: vec![] };
+    let mut vertex_lookup = HashMap::new();
+    for (i, &v) in indices.iter().enumerate() {
+        let tangent = sanitize(geometry.tangents[i], normals[v as usize]);
+        let key = (v, tangent.to_array().map(f32::to_bits));
+        let index = *vertex_lookup.entry(key).or_insert_with(|| {
+            generated.tangents.push(tangent);
+            generated.remap.push(v);
+            generated.tangents.len() as u32 - 1
+        });
+        generated.indices.push(index);
+    }
+    generated
+}
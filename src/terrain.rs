@@ -0,0 +1,433 @@
+use glam::{uvec2, vec2, vec3, UVec2, Vec2, Vec3};
+use crate::{bounds::Aabb, camera::Camera, mesh_data::MeshData, model::Mesh, shapes::{self, Geometry}};
+
+enum HeightSource {
+    Image(image::ImageBuffer<image::Luma<f32>, Vec<f32>>),
+    Function(Box<dyn Fn(f32, f32) -> f32>),
+}
+
+/// Samples a heightmap or a height function into a [`Terrain`].
+pub struct TerrainBuilder {
+    source: HeightSource,
+    size: Vec2,
+    resolution: UVec2,
+    vertical_scale: f32,
+    texture_repeat: Vec2,
+}
+
+impl TerrainBuilder {
+    /// Uses the brightness of the image as the height, from zero for black to the vertical scale
+    /// for white. The top of the image is towards -z, and there is one vertex per pixel unless
+    /// the resolution is changed.
+    pub fn from_image(image: &image::DynamicImage, size: Vec2) -> Self {
+        let image = image.to_luma32f();
+        let resolution = uvec2(image.width(), image.height()).saturating_sub(UVec2::ONE).max(UVec2::ONE);
+        Self {
+            source: HeightSource::Image(image),
+            size,
+            resolution,
+            vertical_scale: 1.0,
+            texture_repeat: Vec2::ONE,
+        }
+    }
+
+    /// Uses `height(x, z)` at every vertex, with x and z relative to the center of the terrain.
+    pub fn from_fn(height: impl Fn(f32, f32) -> f32 + 'static, size: Vec2) -> Self {
+        Self {
+            source: HeightSource::Function(Box::new(height)),
+            size,
+            resolution: UVec2::splat(64),
+            vertical_scale: 1.0,
+            texture_repeat: Vec2::ONE,
+        }
+    }
+
+    /// The number of quads along x and z.
+    pub fn resolution(mut self, columns: u32, rows: u32) -> Self {
+        self.resolution = uvec2(columns, rows).max(UVec2::ONE);
+        self
+    }
+
+    pub fn vertical_scale(mut self, vertical_scale: f32) -> Self {
+        self.vertical_scale = vertical_scale;
+        self
+    }
+
+    /// How many times the texture repeats across the terrain along x and z. Repeating needs a
+    /// texture with [`wgpu::AddressMode::Repeat`], see [`crate::texture::Texture::address_mode`].
+    pub fn texture_repeat(mut self, texture_repeat: Vec2) -> Self {
+        self.texture_repeat = texture_repeat;
+        self
+    }
+
+    pub fn build(self) -> Terrain {
+        let resolution = self.resolution;
+        let mut heights = Vec::with_capacity(((resolution.x + 1) * (resolution.y + 1)) as usize);
+        for row in 0..=resolution.y {
+            for column in 0..=resolution.x {
+                let fraction = vec2(column as f32, row as f32) / resolution.as_vec2();
+                let height = match &self.source {
+                    HeightSource::Image(image) => sample_image(image, fraction),
+                    HeightSource::Function(height) => {
+                        let position = (fraction - 0.5) * self.size;
+                        height(position.x, position.y)
+                    }
+                };
+                heights.push(height * self.vertical_scale);
+            }
+        }
+
+        let mut terrain = Terrain {
+            heights,
+            normals: vec![],
+            resolution,
+            size: self.size,
+            texture_repeat: self.texture_repeat,
+        };
+        terrain.normals = (0..=resolution.y)
+            .flat_map(|row| (0..=resolution.x).map(move |column| (column, row)))
+            .map(|(column, row)| terrain.grid_normal(column, row))
+            .collect();
+        terrain
+    }
+}
+
+/// Bilinearly samples the image, with the fraction going from the center of the first
+/// pixel to the center of the last.
+fn sample_image(image: &image::ImageBuffer<image::Luma<f32>, Vec<f32>>, fraction: Vec2) -> f32 {
+    let last = uvec2(image.width(), image.height()).saturating_sub(UVec2::ONE);
+    let point = fraction * last.as_vec2();
+    let corner = point.floor().as_uvec2().min(last.saturating_sub(UVec2::ONE));
+    let t = point - corner.as_vec2();
+    let pixel = |x: u32, y: u32| image.get_pixel(x.min(last.x), y.min(last.y)).0[0];
+    let top = pixel(corner.x, corner.y) * (1.0 - t.x) + pixel(corner.x + 1, corner.y) * t.x;
+    let bottom = pixel(corner.x, corner.y + 1) * (1.0 - t.x) + pixel(corner.x + 1, corner.y + 1) * t.x;
+    top * (1.0 - t.y) + bottom * t.y
+}
+
+/// A regular grid of heights centered on the origin in the xz plane.
+pub struct Terrain {
+    heights: Vec<f32>,
+    normals: Vec<Vec3>,
+    resolution: UVec2,
+    size: Vec2,
+    texture_repeat: Vec2,
+}
+
+impl Terrain {
+    /// The number of quads along x and z.
+    pub fn resolution(&self) -> UVec2 {
+        self.resolution
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.size
+    }
+
+    fn index(&self, column: u32, row: u32) -> usize {
+        (row.min(self.resolution.y) * (self.resolution.x + 1) + column.min(self.resolution.x)) as usize
+    }
+
+    fn spacing(&self) -> Vec2 {
+        self.size / self.resolution.as_vec2()
+    }
+
+    /// The position of a grid vertex, with rows going along z.
+    pub fn vertex(&self, column: u32, row: u32) -> Vec3 {
+        let position = vec2(column as f32, row as f32) * self.spacing() - self.size * 0.5;
+        vec3(position.x, self.heights[self.index(column, row)], position.y)
+    }
+
+    pub fn vertex_normal(&self, column: u32, row: u32) -> Vec3 {
+        self.normals[self.index(column, row)]
+    }
+
+    /// The normal from the central differences of the neighbouring heights.
+    fn grid_normal(&self, column: u32, row: u32) -> Vec3 {
+        let height = |column: u32, row: u32| self.heights[self.index(column, row)];
+        let (left, right) = (column.saturating_sub(1), (column + 1).min(self.resolution.x));
+        let (back, front) = (row.saturating_sub(1), (row + 1).min(self.resolution.y));
+        let spacing = self.spacing();
+        let dx = (height(right, row) - height(left, row)) / ((right - left) as f32 * spacing.x);
+        let dz = (height(column, front) - height(column, back)) / ((front - back) as f32 * spacing.y);
+        vec3(-dx, 1.0, -dz).normalize()
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        let (min, max) = self.heights.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &h| (min.min(h), max.max(h)));
+        let half = self.size * 0.5;
+        Aabb::new(vec3(-half.x, min, -half.y), vec3(half.x, max, half.y))
+    }
+
+    /// The grid cell under a world position, clamped to the terrain, and the position within it.
+    fn cell(&self, x: f32, z: f32) -> (UVec2, Vec2) {
+        let point = ((vec2(x, z) + self.size * 0.5) / self.spacing()).clamp(Vec2::ZERO, self.resolution.as_vec2());
+        let cell = point.floor().as_uvec2().min(self.resolution - 1);
+        (cell, point - cell.as_vec2())
+    }
+
+    /// Interpolates per-vertex values across the triangle of the mesh under a world position.
+    fn interpolate<T>(&self, x: f32, z: f32, value: impl Fn(u32, u32) -> T) -> T
+    where
+        T: std::ops::Add<Output = T> + std::ops::Sub<Output = T> + std::ops::Mul<f32, Output = T> + Copy,
+    {
+        let (cell, t) = self.cell(x, z);
+        let corner = value(cell.x, cell.y);
+        let opposite = value(cell.x + 1, cell.y + 1);
+        // The quads are split along the diagonal from the first corner to the opposite one.
+        if t.x >= t.y {
+            let side = value(cell.x + 1, cell.y);
+            corner + (side - corner) * t.x + (opposite - side) * t.y
+        } else {
+            let side = value(cell.x, cell.y + 1);
+            corner + (side - corner) * t.y + (opposite - side) * t.x
+        }
+    }
+
+    /// The height of the surface at a world position, exactly on the triangles of the mesh.
+    /// Positions off the terrain get the height at its edge.
+    pub fn height(&self, x: f32, z: f32) -> f32 {
+        self.interpolate(x, z, |column, row| self.heights[self.index(column, row)])
+    }
+
+    /// The smooth normal at a world position.
+    pub fn normal(&self, x: f32, z: f32) -> Vec3 {
+        self.interpolate(x, z, |column, row| self.vertex_normal(column, row)).normalize()
+    }
+
+    /// A world position lifted onto the surface.
+    pub fn on_surface(&self, position: Vec3) -> Vec3 {
+        vec3(position.x, self.height(position.x, position.z), position.z)
+    }
+
+    fn tex_coords(&self, column: u32, row: u32) -> Vec2 {
+        vec2(column as f32, row as f32) / self.resolution.as_vec2() * self.texture_repeat
+    }
+
+    pub fn mesh_data(&self) -> MeshData {
+        self.region_data(UVec2::ZERO, self.resolution, 1, 0.0)
+    }
+
+    /// The part of the grid from `first` to `last`, using every `step`th vertex. When
+    /// `skirt_depth` is positive the borders get skirts hanging down that far, which hide the
+    /// cracks against neighbouring regions of a different step.
+    pub fn region_data(&self, first: UVec2, last: UVec2, step: u32, skirt_depth: f32) -> MeshData {
+        let last = last.min(self.resolution);
+        let samples = |first: u32, last: u32| {
+            (first..last).step_by(step.max(1) as usize).chain(std::iter::once(last)).collect::<Vec<_>>()
+        };
+        let (columns, rows) = (samples(first.x, last.x), samples(first.y, last.y));
+        let (last_column, last_row) = (columns.len() as u32 - 1, rows.len() as u32 - 1);
+        let mut geometry = Geometry::default();
+        geometry.grid(last_column, last_row, |i, j| {
+            let (column, row) = (columns[i as usize], rows[j as usize]);
+            (self.vertex(column, row), self.tex_coords(column, row), self.vertex_normal(column, row))
+        });
+
+        if skirt_depth > 0.0 {
+            let borders = [
+                ((0..=last_column).map(|i| (i, 0)).collect::<Vec<_>>(), Vec3::NEG_Z),
+                ((0..=last_column).map(|i| (i, last_row)).collect(), Vec3::Z),
+                ((0..=last_row).map(|j| (0, j)).collect(), Vec3::NEG_X),
+                ((0..=last_row).map(|j| (last_column, j)).collect(), Vec3::X),
+            ];
+            for (border, outward) in borders {
+                let first_lowered = geometry.num_vertices();
+                for &(i, j) in &border {
+                    let (column, row) = (columns[i as usize], rows[j as usize]);
+                    let position = self.vertex(column, row) - Vec3::Y * skirt_depth;
+                    geometry.vertex(position, self.tex_coords(column, row), self.vertex_normal(column, row));
+                }
+                let top = |k: usize| border[k].1 * (last_column + 1) + border[k].0;
+                let bottom = |k: usize| first_lowered + k as u32;
+                for k in 0..border.len() - 1 {
+                    geometry.triangle_facing(top(k), top(k + 1), bottom(k + 1), outward);
+                    geometry.triangle_facing(top(k), bottom(k + 1), bottom(k), outward);
+                }
+            }
+        }
+        geometry.into_data()
+    }
+
+    pub fn mesh(&self, material: usize, device: &wgpu::Device) -> Mesh {
+        shapes::mesh("Terrain", self.mesh_data(), material, device)
+    }
+}
+
+/// The level of detail out of `lod_levels` for a chunk `distance` from the camera.
+fn lod_for_distance(distance: f32, lod_distance: f32, lod_levels: usize) -> usize {
+    if distance <= lod_distance {
+        return 0;
+    }
+    ((distance / lod_distance).log2().ceil() as usize).min(lod_levels.saturating_sub(1))
+}
+
+/// The level of detail of every chunk with these bounds, or `None` for the chunks outside the
+/// frustum of the camera.
+fn select_lods<'a>(bounds: impl Iterator<Item = &'a Aabb>, camera: &Camera, lod_for_distance: impl Fn(f32) -> usize) -> Vec<Option<usize>> {
+    let eye = camera.eye();
+    let frustum = camera.frustum();
+    bounds
+        .map(|bounds| frustum.intersects_aabb(bounds).then(|| lod_for_distance(bounds.distance(eye))))
+        .collect()
+}
+
+pub struct TerrainChunk {
+    pub bounds: Aabb,
+    /// One mesh per level of detail, each with half the resolution of the one before.
+    pub lods: Vec<Mesh>,
+}
+
+/// Splits a [`Terrain`] into square chunks with several levels of detail each, so that chunks
+/// outside the view are skipped and distant ones are drawn with fewer triangles.
+pub struct ChunkedTerrain {
+    terrain: Terrain,
+    chunks: Vec<TerrainChunk>,
+    /// The distance from the camera up to which chunks are drawn at full resolution. Every
+    /// following level of detail reaches twice as far as the one before.
+    pub lod_distance: f32,
+    selected: Vec<Option<usize>>,
+}
+
+impl ChunkedTerrain {
+    /// `chunk_size` is the number of quads along the sides of a chunk at full resolution, and
+    /// `lod_levels` is limited to the number of times it can be halved. `skirt_depth` should be
+    /// larger than the height difference between neighbouring vertices at the coarsest level.
+    pub fn new(terrain: Terrain, chunk_size: u32, lod_levels: u32, skirt_depth: f32, lod_distance: f32, material: usize, device: &wgpu::Device) -> Self {
+        let chunk_size = chunk_size.max(1);
+        let lod_levels = lod_levels.clamp(1, chunk_size.ilog2() + 1);
+        let counts = (terrain.resolution + chunk_size - 1) / chunk_size;
+        let mut chunks = vec![];
+        for z in 0..counts.y {
+            for x in 0..counts.x {
+                let first = uvec2(x, z) * chunk_size;
+                let lods = (0..lod_levels)
+                    .map(|level| {
+                        let data = terrain.region_data(first, first + chunk_size, 1 << level, skirt_depth);
+                        shapes::mesh(&format!("Terrain Chunk {x} {z} LOD {level}"), data, material, device)
+                    })
+                    .collect::<Vec<_>>();
+                let bounds = lods[0].bounds.expect("terrain chunks are never empty");
+                chunks.push(TerrainChunk { bounds, lods });
+            }
+        }
+        let selected = vec![Some(0); chunks.len()];
+        Self { terrain, chunks, lod_distance, selected }
+    }
+
+    pub fn terrain(&self) -> &Terrain {
+        &self.terrain
+    }
+
+    pub fn chunks(&self) -> &[TerrainChunk] {
+        &self.chunks
+    }
+
+    pub fn lod_for_distance(&self, distance: f32) -> usize {
+        lod_for_distance(distance, self.lod_distance, self.chunks.first().map_or(0, |chunk| chunk.lods.len()))
+    }
+
+    /// Picks the level of detail of every chunk by its distance from the eye of the camera,
+    /// and skips the chunks outside its frustum.
+    pub fn update(&mut self, camera: &Camera) {
+        self.selected = select_lods(self.chunks.iter().map(|chunk| &chunk.bounds), camera, |distance| self.lod_for_distance(distance));
+    }
+
+    /// The meshes picked by the last [`ChunkedTerrain::update`].
+    pub fn visible_meshes(&self) -> impl Iterator<Item = &Mesh> {
+        self.chunks
+            .iter()
+            .zip(&self.selected)
+            .filter_map(|(chunk, lod)| lod.map(|lod| &chunk.lods[lod]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{actual} instead of {expected}");
+    }
+
+    #[test]
+    fn height_follows_the_heightmap() {
+        let pixel = |x: u32, y: u32| (x * 40 + y * 15 + (x * y) % 3 * 20) as u8;
+        let image = image::GrayImage::from_fn(5, 4, |x, y| image::Luma([pixel(x, y)]));
+        let terrain = TerrainBuilder::from_image(&image::DynamicImage::ImageLuma8(image), vec2(8.0, 6.0))
+            .vertical_scale(2.0)
+            .build();
+        assert_eq!(terrain.resolution(), uvec2(4, 3));
+        let expected = |column: u32, row: u32| pixel(column, row) as f32 / 255.0 * 2.0;
+
+        for row in 0..=3 {
+            for column in 0..=4 {
+                let vertex = terrain.vertex(column, row);
+                assert_close(vertex.y, expected(column, row));
+                assert_close(terrain.height(vertex.x, vertex.z), expected(column, row));
+            }
+        }
+        // Halfway along the sides and the diagonal of every quad the height is the average of
+        // the two ends, as on the triangles of the mesh.
+        for row in 0..3 {
+            for column in 0..4 {
+                for (next_column, next_row) in [(column + 1, row), (column, row + 1), (column + 1, row + 1)] {
+                    let middle = (terrain.vertex(column, row) + terrain.vertex(next_column, next_row)) * 0.5;
+                    assert_close(terrain.height(middle.x, middle.z), (expected(column, row) + expected(next_column, next_row)) * 0.5);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn height_is_exact_on_a_slope() {
+        let slope = |x: f32, z: f32| 0.5 * x - 0.25 * z + 1.0;
+        let terrain = TerrainBuilder::from_fn(slope, vec2(10.0, 6.0)).resolution(7, 5).build();
+        for (x, z) in [(0.0, 0.0), (-4.9, 2.9), (1.23, -0.77), (3.3, 1.1)] {
+            assert_close(terrain.height(x, z), slope(x, z));
+        }
+        // Off the terrain the height of its edge is used.
+        assert_close(terrain.height(8.0, 0.0), slope(5.0, 0.0));
+    }
+
+    #[test]
+    fn chunks_get_coarser_with_distance() {
+        // A row of chunks along x, seen from above the second one looking down the row.
+        let terrain = TerrainBuilder::from_fn(|x, z| (x * 0.3).sin() + (z * 0.2).cos(), vec2(256.0, 32.0)).resolution(64, 8).build();
+        let (chunk_size, lod_distance, lod_levels) = (8, 20.0, 4);
+        let bounds = (0..8)
+            .map(|x| terrain.region_data(uvec2(x * chunk_size, 0), uvec2(x + 1, 1) * chunk_size, 1, 0.0).aabb().unwrap())
+            .collect::<Vec<_>>();
+        let camera = Camera::new(vec3(-90.0, 10.0, 0.0), vec3(100.0, 0.0, 0.0), Vec3::Y, 1.0, 45.0, 0.1, 500.0);
+        let lods = select_lods(bounds.iter(), &camera, |distance| lod_for_distance(distance, lod_distance, lod_levels));
+
+        // The first chunk ends 6 units behind the eye.
+        assert_eq!(lods[0], None);
+        let ahead = lods[1..].iter().map(|lod| lod.expect("chunks ahead are visible")).collect::<Vec<_>>();
+        assert!(ahead.windows(2).all(|pair| pair[0] <= pair[1]), "{ahead:?}");
+        assert_eq!(ahead.first(), Some(&0));
+        assert_eq!(ahead.last(), Some(&(lod_levels - 1)));
+    }
+
+    #[test]
+    fn skirts_hang_below_their_edge() {
+        let terrain = TerrainBuilder::from_fn(|x, z| (x * 0.7).sin() + (z * 0.4).cos(), vec2(16.0, 16.0)).resolution(16, 16).build();
+        let (first, last, depth) = (uvec2(4, 4), uvec2(12, 12), 0.5);
+        let data = terrain.region_data(first, last, 2, depth);
+        let grid_vertices = 5 * 5;
+        assert_eq!(data.num_vertices(), grid_vertices + 4 * 5);
+
+        let (min, max) = (terrain.vertex(first.x, first.y), terrain.vertex(last.x, last.y));
+        let on_border = |position: Vec3| {
+            [position.x - min.x, position.x - max.x, position.z - min.z, position.z - max.z].iter().any(|offset| offset.abs() < 1e-5)
+        };
+        for &position in &data.positions[grid_vertices..] {
+            assert!(on_border(position), "{position} is not on the border");
+            let top = data.positions[..grid_vertices]
+                .iter()
+                .find(|top| top.x == position.x && top.z == position.z)
+                .unwrap_or_else(|| panic!("nothing above {position}"));
+            assert_close(position.y, top.y - depth);
+        }
+    }
+}
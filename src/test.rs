@@ -1,5 +1,7 @@
-use glam::{vec3, Quat, Vec3};
-use graphics::{self, camera, model::{self, DrawModel, ModelVertex, Vertex, ModelInstance, ModelInstanceRaw}, resources, texture::Texture, window::create_render_pipeline, App};
+use std::f32::consts::TAU;
+use std::time::Instant;
+use glam::{uvec3, vec3, Quat, Vec3};
+use graphics::{self, animation::{Animation, Easing, Repeat, Track}, camera, light::LightUniform, model::{self, DrawModel, Mesh, ModelVertex, Vertex, ModelInstance, ModelInstanceRaw}, resources, shaders, texture::Texture, voxel::{TextureAtlas, VoxelChunk, VoxelVertex, AIR}, window::create_render_pipeline, App};
 use wgpu::{util::DeviceExt, Queue, RenderPass};
 
 const NUM_INSTANCES_PER_ROW: u32 = 10;
@@ -21,25 +23,31 @@ struct GameState {
     instances: Vec<ModelInstance>,
     instance_buffer: wgpu::Buffer,
     obj_model: model::MatModel,
+    voxel_render_pipeline: wgpu::RenderPipeline,
+    voxel_mesh: Mesh,
+    voxel_instance_buffer: wgpu::Buffer,
 }
 
 struct Game {
     state: Option<GameState>,
     light_uniform: LightUniform,
+    light_animation: Animation<LightUniform>,
+    last_update: Instant,
 }
 
 impl Game {
     fn new() -> Self {
-        let light_uniform = LightUniform {
-            position: vec3(2.0, 2.0, 2.0),
-            _padding: 0,
-            color: vec3(1.0, 1.0, 1.0),
-            _padding2: 0,
-        };
+        let light_uniform = LightUniform::new(vec3(2.0, 2.0, 2.0), vec3(1.0, 1.0, 1.0));
+        // Circles the light around the y axis once every six seconds.
+        let orbit = Track::tween(0.0, TAU, 6.0, Easing::Linear)
+            .bind(|light: &mut LightUniform, angle| light.position = Quat::from_axis_angle(Vec3::Y, angle) * vec3(2.0, 2.0, 2.0));
+        let light_animation = Animation::new(orbit).repeat(Repeat::Loop);
 
         Self {
             state: None,
             light_uniform,
+            light_animation,
+            last_update: Instant::now(),
         }
     }
 
@@ -69,11 +77,10 @@ impl App for Game {
         &mut self,
         queue: &Queue,
     ) {
-        let old_position: Vec3 = self.light_uniform.position.into();
-        self.light_uniform.position =
-            (Quat::from_axis_angle((0.0, 1.0, 0.0).into(), 1.0_f32.to_radians())
-                * old_position)
-                .into();
+        let now = Instant::now();
+        let seconds = (now - self.last_update).as_secs_f32();
+        self.last_update = now;
+        self.light_animation.update(seconds, &mut self.light_uniform);
         queue.write_buffer(&self.state().light_buffer, 0, bytemuck::cast_slice(&[self.light_uniform]));
     }
 
@@ -102,26 +109,33 @@ impl App for Game {
         }
     }
 
-    fn render(
-        &mut self,
-        render_pass: &mut RenderPass,
+    fn render<'a>(
+        &'a mut self,
+        render_pass: &mut RenderPass<'a>,
     ) {
-        unsafe {
-            render_pass.set_vertex_buffer(1, std::mem::transmute(self.state().instance_buffer.slice(..)));
-
-            render_pass.set_pipeline(to_static(&self.state().light_render_pipeline));
-            render_pass.draw_model_no_mat(
-                to_static(&self.state().obj_model),
-                to_static_array(&[&self.state().camera_bind_group, &self.state().light_bind_group]),
-            );
-
-            render_pass.set_pipeline(to_static(&self.state().render_pipeline));
-            render_pass.draw_model_instanced(
-                to_static(&self.state().obj_model),
-                0..self.state().instances.len() as u32,
-                to_static_array(&[&self.state().camera_bind_group, &self.state().light_bind_group]),
-            );
-        }
+        let state = self.state();
+        render_pass.set_vertex_buffer(1, state.instance_buffer.slice(..));
+
+        render_pass.set_pipeline(&state.light_render_pipeline);
+        render_pass.draw_model_no_mat(
+            &state.obj_model,
+            &[&state.camera_bind_group, &state.light_bind_group],
+        );
+
+        render_pass.set_pipeline(&state.render_pipeline);
+        render_pass.draw_model_instanced(
+            &state.obj_model,
+            0..state.instances.len() as u32,
+            &[&state.camera_bind_group, &state.light_bind_group],
+        );
+
+        render_pass.set_vertex_buffer(1, state.voxel_instance_buffer.slice(..));
+        render_pass.set_pipeline(&state.voxel_render_pipeline);
+        render_pass.draw_mesh(
+            &state.voxel_mesh,
+            Some(&state.obj_model.materials[state.voxel_mesh.material]),
+            &[&state.camera_bind_group, &state.light_bind_group],
+        );
     }
     
     fn setup(
@@ -131,43 +145,7 @@ impl App for Game {
         config: &wgpu::SurfaceConfiguration,
     ) {
 
-        let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 3,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-            label: Some("texture_bind_group_layout"),
-        });
+        let texture_bind_group_layout = model::ModelMaterial::bind_group_layout(device);
 
         let mut camera = camera::Camera::new(
             (0.0, 1.0, 2.0).into(),
@@ -258,21 +236,24 @@ impl App for Game {
                 push_constant_ranges: &[],
             }
         );
-        let render_pipeline = {
-            let shader = wgpu::ShaderModuleDescriptor {
-                label: Some("Normal Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("lit.wgsl").into()),
-            };
-            create_render_pipeline(
-                &device,
-                &render_pipeline_layout,
-                config.format,
-                Some(Texture::DEPTH_FORMAT),
-                &[ModelVertex::desc(), ModelInstanceRaw::desc()],
-                shader,
-            )
-        };
+        let render_pipeline = create_render_pipeline(
+            device,
+            &render_pipeline_layout,
+            config.format,
+            Some(Texture::DEPTH_FORMAT),
+            &[ModelVertex::desc(), ModelInstanceRaw::desc()],
+            shaders::pbr(),
+        );
         
+        let voxel_render_pipeline = create_render_pipeline(
+            device,
+            &render_pipeline_layout,
+            config.format,
+            Some(Texture::DEPTH_FORMAT),
+            &[VoxelVertex::desc(), ModelInstanceRaw::desc()],
+            shaders::voxel(),
+        );
+
         let light_render_pipeline = {
             let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Light Pipeline Layout"),
@@ -287,7 +268,7 @@ impl App for Game {
                 source: wgpu::ShaderSource::Wgsl(include_str!("light.wgsl").into()),
             };
             create_render_pipeline(
-                &device,
+                device,
                 &layout,
                 config.format,
                 Some(Texture::DEPTH_FORMAT),
@@ -323,7 +304,21 @@ impl App for Game {
             }
         );
 
-        let obj_model = resources::load_model("cube.obj", &device, &queue, &texture_bind_group_layout).unwrap();
+        let obj_model = resources::load_model("cube.obj", false, device, queue, &texture_bind_group_layout).unwrap();
+
+        // Rolling hills of blocks under the cubes, far more than could be drawn one instance each.
+        let voxel_chunk = VoxelChunk::from_fn(uvec3(64, 16, 64), |block| {
+            let height = 6.0 + 3.0 * (block.x as f32 * 0.2).sin() * (block.z as f32 * 0.15).cos();
+            if (block.y as f32) < height { 1 } else { AIR }
+        });
+        let voxel_mesh = voxel_chunk.mesh(TextureAtlas::new(1, 1), |_, _| 0, 0, device);
+        let voxel_instance_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Voxel Instance Buffer"),
+                contents: bytemuck::cast_slice(&[ModelInstance::new(vec3(-32.0, -14.0, -32.0), Quat::IDENTITY).to_raw()]),
+                usage: wgpu::BufferUsages::VERTEX,
+            }
+        );
 
         self.state = Some(GameState {
             render_pipeline,
@@ -336,25 +331,9 @@ impl App for Game {
             instances,
             instance_buffer,
             obj_model,
+            voxel_render_pipeline,
+            voxel_mesh,
+            voxel_instance_buffer,
         });
     }
 }
-
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct LightUniform {
-    position: Vec3,
-    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
-    _padding: u32,
-    color: Vec3,
-    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
-    _padding2: u32,
-}
-
-unsafe fn to_static<T>(src: &T) -> &'static T {
-    std::mem::transmute(src)
-}
-
-unsafe fn to_static_array<T>(src: &[&T]) -> &'static [&'static T] {
-    std::mem::transmute(src)
-}
\ No newline at end of file
@@ -5,6 +5,8 @@ pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    /// The file the texture was loaded from, so exporters can copy it.
+    pub path: Option<std::path::PathBuf>,
 }
 
 impl Texture {
@@ -19,6 +21,18 @@ impl Texture {
         Self::from_image(device, queue, &img, Some(label), is_normal_map)
     }
 
+    /// A single texel of `color`, for materials with a constant color or a flat normal map.
+    pub fn from_color(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color: [u8; 4],
+        label: &str,
+        is_normal_map: bool,
+    ) -> Result<Self> {
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(color)));
+        Self::from_image(device, queue, &img, Some(label), is_normal_map)
+    }
+
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
@@ -69,19 +83,29 @@ impl Texture {
         );
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(
+        let sampler = Self::create_sampler(device, wgpu::AddressMode::ClampToEdge);
+        
+        Ok(Self { texture, view, sampler, path: None })
+    }
+
+    /// Swaps the sampler for one that handles coordinates outside 0 to 1 with `address_mode`,
+    /// such as [`wgpu::AddressMode::Repeat`] for textures tiled across terrain.
+    pub fn address_mode(self, device: &wgpu::Device, address_mode: wgpu::AddressMode) -> Self {
+        Self { sampler: Self::create_sampler(device, address_mode), ..self }
+    }
+
+    fn create_sampler(device: &wgpu::Device, address_mode: wgpu::AddressMode) -> wgpu::Sampler {
+        device.create_sampler(
             &wgpu::SamplerDescriptor {
-                address_mode_u: wgpu::AddressMode::ClampToEdge,
-                address_mode_v: wgpu::AddressMode::ClampToEdge,
-                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                address_mode_u: address_mode,
+                address_mode_v: address_mode,
+                address_mode_w: address_mode,
                 mag_filter: wgpu::FilterMode::Linear,
                 min_filter: wgpu::FilterMode::Nearest,
                 mipmap_filter: wgpu::FilterMode::Nearest,
                 ..Default::default()
             }
-        );
-        
-        Ok(Self { texture, view, sampler })
+        )
     }
 
     
@@ -122,6 +146,6 @@ impl Texture {
             }
         );
 
-        Self { texture, view, sampler }
+        Self { texture, view, sampler, path: None }
     }
 }
\ No newline at end of file
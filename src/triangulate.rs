@@ -0,0 +1,508 @@
+use glam::Vec2;
+
+/// Twice the signed area of a polygon, positive when it is counter-clockwise.
+pub fn signed_area(polygon: &[Vec2]) -> f32 {
+    (0..polygon.len())
+        .map(|i| polygon[i].perp_dot(polygon[(i + 1) % polygon.len()]))
+        .sum()
+}
+
+// Ear clipping with holes, following Mapbox's earcut. The points are kept in a doubly linked
+// list so holes can be bridged in and ears clipped without shifting everything around.
+
+struct Node {
+    index: u32,
+    point: Vec2,
+    prev: usize,
+    next: usize,
+    steiner: bool,
+}
+
+/// Negative when `q` is a convex corner of a counter-clockwise polygon.
+fn area(p: Vec2, q: Vec2, r: Vec2) -> f32 {
+    (q.y - p.y) * (r.x - q.x) - (q.x - p.x) * (r.y - q.y)
+}
+
+fn inside_triangle(a: Vec2, b: Vec2, c: Vec2, point: Vec2) -> bool {
+    (c.x - point.x) * (a.y - point.y) >= (a.x - point.x) * (c.y - point.y)
+        && (a.x - point.x) * (b.y - point.y) >= (b.x - point.x) * (a.y - point.y)
+        && (b.x - point.x) * (c.y - point.y) >= (c.x - point.x) * (b.y - point.y)
+}
+
+/// Whether `q` lies in the bounding box of `p` and `r`.
+fn on_segment(p: Vec2, q: Vec2, r: Vec2) -> bool {
+    q.cmple(p.max(r)).all() && q.cmpge(p.min(r)).all()
+}
+
+fn segments_intersect(p1: Vec2, q1: Vec2, p2: Vec2, q2: Vec2) -> bool {
+    let sign = |value: f32| if value == 0.0 { 0.0 } else { value.signum() };
+    let o1 = sign(area(p1, q1, p2));
+    let o2 = sign(area(p1, q1, q2));
+    let o3 = sign(area(p2, q2, p1));
+    let o4 = sign(area(p2, q2, q1));
+    (o1 != o2 && o3 != o4)
+        || (o1 == 0.0 && on_segment(p1, p2, q1))
+        || (o2 == 0.0 && on_segment(p1, q2, q1))
+        || (o3 == 0.0 && on_segment(p2, p1, q2))
+        || (o4 == 0.0 && on_segment(p2, q1, q2))
+}
+
+#[derive(Default)]
+struct Triangulator {
+    nodes: Vec<Node>,
+    indices: Vec<u32>,
+}
+
+impl Triangulator {
+    fn point(&self, node: usize) -> Vec2 {
+        self.nodes[node].point
+    }
+
+    fn index(&self, node: usize) -> u32 {
+        self.nodes[node].index
+    }
+
+    fn next(&self, node: usize) -> usize {
+        self.nodes[node].next
+    }
+
+    fn prev(&self, node: usize) -> usize {
+        self.nodes[node].prev
+    }
+
+    fn corner(&self, node: usize) -> f32 {
+        area(self.point(self.prev(node)), self.point(node), self.point(self.next(node)))
+    }
+
+    fn insert(&mut self, index: u32, point: Vec2, last: Option<usize>) -> usize {
+        let node = self.nodes.len();
+        let (prev, next) = last.map_or((node, node), |last| (last, self.next(last)));
+        self.nodes.push(Node { index, point, prev, next, steiner: false });
+        self.nodes[prev].next = node;
+        self.nodes[next].prev = node;
+        node
+    }
+
+    fn remove(&mut self, node: usize) {
+        let (prev, next) = (self.prev(node), self.next(node));
+        self.nodes[next].prev = prev;
+        self.nodes[prev].next = next;
+    }
+
+    /// Links a ring with the given winding and returns its last node.
+    fn ring(&mut self, points: &[Vec2], first_index: u32, counter_clockwise: bool) -> Option<usize> {
+        let mut order = (0..points.len()).collect::<Vec<_>>();
+        if counter_clockwise != (signed_area(points) > 0.0) {
+            order.reverse();
+        }
+        let mut last = None;
+        for i in order {
+            last = Some(self.insert(first_index + i as u32, points[i], last));
+        }
+        let last = last?;
+        if self.point(last) == self.point(self.next(last)) {
+            self.remove(last);
+            return Some(self.next(last));
+        }
+        Some(last)
+    }
+
+    /// Removes duplicate points and collinear corners between `start` and `end`.
+    fn filter_points(&mut self, start: usize, end: Option<usize>) -> usize {
+        let mut end = end.unwrap_or(start);
+        let mut node = start;
+        loop {
+            let mut again = false;
+            if !self.nodes[node].steiner && (self.point(node) == self.point(self.next(node)) || self.corner(node) == 0.0) {
+                self.remove(node);
+                node = self.prev(node);
+                end = node;
+                if node == self.next(node) {
+                    break;
+                }
+                again = true;
+            } else {
+                node = self.next(node);
+            }
+            if !again && node == end {
+                break;
+            }
+        }
+        end
+    }
+
+    /// Only reflex corners can reach into a convex corner's triangle without crossing its edges.
+    fn is_ear(&self, ear: usize) -> bool {
+        let (a, c) = (self.prev(ear), self.next(ear));
+        let (pa, pb, pc) = (self.point(a), self.point(ear), self.point(c));
+        if area(pa, pb, pc) >= 0.0 {
+            return false;
+        }
+        let mut node = self.next(c);
+        while node != a {
+            if inside_triangle(pa, pb, pc, self.point(node)) && self.corner(node) >= 0.0 {
+                return false;
+            }
+            node = self.next(node);
+        }
+        true
+    }
+
+    fn push_triangle(&mut self, a: usize, b: usize, c: usize) {
+        self.indices.extend([self.index(a), self.index(b), self.index(c)]);
+    }
+
+    /// Clips ears until none are left. When a full loop finds none, the polygon is cleaned up,
+    /// then small self-intersections are cut off, and as a last resort it is split in two.
+    fn clip_ears(&mut self, mut ear: usize, pass: u32) {
+        let mut stop = ear;
+        while self.prev(ear) != self.next(ear) {
+            let (prev, next) = (self.prev(ear), self.next(ear));
+            if self.is_ear(ear) {
+                self.push_triangle(prev, ear, next);
+                self.remove(ear);
+                ear = self.next(next);
+                stop = ear;
+                continue;
+            }
+            ear = next;
+            if ear == stop {
+                match pass {
+                    0 => {
+                        let ear = self.filter_points(ear, None);
+                        self.clip_ears(ear, 1);
+                    }
+                    1 => {
+                        let ear = self.filter_points(ear, None);
+                        let ear = self.cure_local_intersections(ear);
+                        self.clip_ears(ear, 2);
+                    }
+                    _ => self.split_and_clip(ear),
+                }
+                break;
+            }
+        }
+    }
+
+    fn locally_inside(&self, a: usize, b: usize) -> bool {
+        let (prev, point, next, other) = (self.point(self.prev(a)), self.point(a), self.point(self.next(a)), self.point(b));
+        if area(prev, point, next) < 0.0 {
+            area(point, other, next) >= 0.0 && area(point, prev, other) >= 0.0
+        } else {
+            area(point, other, prev) < 0.0 || area(point, next, other) < 0.0
+        }
+    }
+
+    fn cure_local_intersections(&mut self, mut start: usize) -> usize {
+        let mut node = start;
+        loop {
+            let a = self.prev(node);
+            let next = self.next(node);
+            let b = self.next(next);
+            if self.point(a) != self.point(b)
+                && segments_intersect(self.point(a), self.point(node), self.point(next), self.point(b))
+                && self.locally_inside(a, b)
+                && self.locally_inside(b, a)
+            {
+                self.push_triangle(a, node, b);
+                self.remove(node);
+                self.remove(next);
+                node = b;
+                start = b;
+            }
+            node = self.next(node);
+            if node == start {
+                break;
+            }
+        }
+        self.filter_points(node, None)
+    }
+
+    fn intersects_polygon(&self, a: usize, b: usize) -> bool {
+        let (index_a, index_b) = (self.index(a), self.index(b));
+        let mut node = a;
+        loop {
+            let next = self.next(node);
+            let touches = [self.index(node), self.index(next)].iter().any(|&i| i == index_a || i == index_b);
+            if !touches && segments_intersect(self.point(node), self.point(next), self.point(a), self.point(b)) {
+                return true;
+            }
+            node = next;
+            if node == a {
+                return false;
+            }
+        }
+    }
+
+    fn middle_inside(&self, a: usize, b: usize) -> bool {
+        let middle = (self.point(a) + self.point(b)) * 0.5;
+        let mut inside = false;
+        let mut node = a;
+        loop {
+            let (p, q) = (self.point(node), self.point(self.next(node)));
+            if (p.y > middle.y) != (q.y > middle.y) && middle.x < (q.x - p.x) * (middle.y - p.y) / (q.y - p.y) + p.x {
+                inside = !inside;
+            }
+            node = self.next(node);
+            if node == a {
+                return inside;
+            }
+        }
+    }
+
+    fn is_valid_diagonal(&self, a: usize, b: usize) -> bool {
+        let (pa, pb) = (self.point(a), self.point(b));
+        let (a_prev, b_prev) = (self.point(self.prev(a)), self.point(self.prev(b)));
+        let visible = self.locally_inside(a, b)
+            && self.locally_inside(b, a)
+            && self.middle_inside(a, b)
+            && (area(a_prev, pa, b_prev) != 0.0 || area(pa, b_prev, pb) != 0.0);
+        let zero_length = pa == pb && self.corner(a) > 0.0 && self.corner(b) > 0.0;
+        self.index(self.next(a)) != self.index(b)
+            && self.index(self.prev(a)) != self.index(b)
+            && !self.intersects_polygon(a, b)
+            && (visible || zero_length)
+    }
+
+    /// Joins `a` and `b` with a pair of coincident edges, which cuts the ring in two when both
+    /// are on it, or merges a hole into it. Returns the copy of `b`.
+    fn split(&mut self, a: usize, b: usize) -> usize {
+        let (a_next, b_prev) = (self.next(a), self.prev(b));
+        let a2 = self.nodes.len();
+        let b2 = a2 + 1;
+        self.nodes.push(Node { next: a_next, prev: b2, ..self.nodes[a] });
+        self.nodes.push(Node { next: a2, prev: b_prev, ..self.nodes[b] });
+        self.nodes[a].next = b;
+        self.nodes[b].prev = a;
+        self.nodes[a_next].prev = a2;
+        self.nodes[b_prev].next = b2;
+        b2
+    }
+
+    fn split_and_clip(&mut self, start: usize) {
+        let mut a = start;
+        loop {
+            let mut b = self.next(self.next(a));
+            while b != self.prev(a) {
+                if self.index(a) != self.index(b) && self.is_valid_diagonal(a, b) {
+                    let c = self.split(a, b);
+                    let a = self.filter_points(a, Some(self.next(a)));
+                    let c = self.filter_points(c, Some(self.next(c)));
+                    self.clip_ears(a, 0);
+                    self.clip_ears(c, 0);
+                    return;
+                }
+                b = self.next(b);
+            }
+            a = self.next(a);
+            if a == start {
+                return;
+            }
+        }
+    }
+
+    fn sector_contains_sector(&self, m: usize, p: usize) -> bool {
+        let point = self.point(m);
+        area(self.point(self.prev(m)), point, self.point(self.prev(p))) < 0.0
+            && area(self.point(self.next(p)), point, self.point(self.next(m))) < 0.0
+    }
+
+    /// Finds an outline node that can be connected to the leftmost point of a hole, using
+    /// David Eberly's ray casting method.
+    fn find_hole_bridge(&self, hole: usize, outer: usize) -> Option<usize> {
+        let h = self.point(hole);
+        let mut hit_x = f32::NEG_INFINITY;
+        let mut candidate = None;
+        let mut node = outer;
+        loop {
+            let next = self.next(node);
+            let (p, q) = (self.point(node), self.point(next));
+            if h.y <= p.y && h.y >= q.y && q.y != p.y {
+                // Rays through a vertex take its exact x, so the vertex is found in the triangle below.
+                let x = match h.y {
+                    y if y == p.y => p.x,
+                    y if y == q.y => q.x,
+                    _ => p.x + (h.y - p.y) * (q.x - p.x) / (q.y - p.y),
+                };
+                if x <= h.x && x > hit_x {
+                    hit_x = x;
+                    let endpoint = if p.x < q.x { node } else { next };
+                    if x == h.x {
+                        return Some(endpoint);
+                    }
+                    candidate = Some(endpoint);
+                }
+            }
+            node = next;
+            if node == outer {
+                break;
+            }
+        }
+
+        // A reflex corner inside the triangle between the hole, the hit and the candidate can
+        // block the view, in which case the one closest in angle to the ray is used instead.
+        let mut bridge = candidate?;
+        let stop = bridge;
+        let m = self.point(bridge);
+        let (first, last) = if h.y < m.y { (h.x, hit_x) } else { (hit_x, h.x) };
+        let mut tan_min = f32::INFINITY;
+        let mut node = bridge;
+        loop {
+            let p = self.point(node);
+            if h.x >= p.x && p.x >= m.x && h.x != p.x && inside_triangle(Vec2::new(first, h.y), m, Vec2::new(last, h.y), p) {
+                let tan = (h.y - p.y).abs() / (h.x - p.x);
+                let current = self.point(bridge);
+                let closer = tan < tan_min
+                    || (tan == tan_min && (p.x > current.x || (p.x == current.x && self.sector_contains_sector(bridge, node))));
+                if self.locally_inside(node, hole) && closer {
+                    bridge = node;
+                    tan_min = tan;
+                }
+            }
+            node = self.next(node);
+            if node == stop {
+                return Some(bridge);
+            }
+        }
+    }
+
+    fn eliminate_hole(&mut self, hole: usize, outer: usize) -> usize {
+        let Some(bridge) = self.find_hole_bridge(hole, outer) else {
+            return outer;
+        };
+        let bridge_reverse = self.split(bridge, hole);
+        self.filter_points(bridge_reverse, Some(self.next(bridge_reverse)));
+        self.filter_points(bridge, Some(self.next(bridge)))
+    }
+
+    fn leftmost(&self, start: usize) -> usize {
+        let mut leftmost = start;
+        let mut node = self.next(start);
+        while node != start {
+            let (p, l) = (self.point(node), self.point(leftmost));
+            if p.x < l.x || (p.x == l.x && p.y < l.y) {
+                leftmost = node;
+            }
+            node = self.next(node);
+        }
+        leftmost
+    }
+}
+
+/// Triangulates a simple polygon with holes by ear clipping. The vertices are numbered
+/// in the order of `outer` followed by every hole, and the returned triangles are
+/// counter-clockwise. Either winding is accepted for the outline and the holes.
+pub fn triangulate(outer: &[Vec2], holes: &[Vec<Vec2>]) -> Vec<u32> {
+    let mut triangulator = Triangulator::default();
+    let Some(mut start) = triangulator.ring(outer, 0, true) else {
+        return vec![];
+    };
+    if triangulator.next(start) == triangulator.prev(start) {
+        return vec![];
+    }
+
+    let mut first_index = outer.len() as u32;
+    let mut leftmost = vec![];
+    for hole in holes {
+        if let Some(ring) = triangulator.ring(hole, first_index, false) {
+            if ring == triangulator.next(ring) {
+                triangulator.nodes[ring].steiner = true;
+            }
+            leftmost.push(triangulator.leftmost(ring));
+        }
+        first_index += hole.len() as u32;
+    }
+    leftmost.sort_by(|&a, &b| triangulator.point(a).x.total_cmp(&triangulator.point(b).x));
+    for hole in leftmost {
+        start = triangulator.eliminate_hole(hole, start);
+    }
+
+    triangulator.clip_ears(start, 0);
+    triangulator.indices
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec2;
+    use super::*;
+
+    /// The corners of every triangle, looked up in `outer` followed by the holes.
+    fn triangles(outer: &[Vec2], holes: &[Vec<Vec2>]) -> Vec<[Vec2; 3]> {
+        let points = outer.iter().chain(holes.iter().flatten()).copied().collect::<Vec<_>>();
+        triangulate(outer, holes)
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|i| points[triangle[i] as usize]))
+            .collect()
+    }
+
+    fn total_area(triangles: &[[Vec2; 3]]) -> f32 {
+        triangles.iter().map(|triangle| signed_area(triangle) / 2.0).sum()
+    }
+
+    fn assert_counter_clockwise(triangles: &[[Vec2; 3]]) {
+        for triangle in triangles {
+            assert!(signed_area(triangle) > 0.0, "{triangle:?} is clockwise");
+        }
+    }
+
+    /// A comb with three teeth, concave between each of them.
+    fn comb() -> Vec<Vec2> {
+        vec![
+            vec2(0.0, 0.0),
+            vec2(5.0, 0.0),
+            vec2(5.0, 3.0),
+            vec2(4.0, 3.0),
+            vec2(4.0, 1.0),
+            vec2(3.0, 1.0),
+            vec2(3.0, 3.0),
+            vec2(2.0, 3.0),
+            vec2(2.0, 1.0),
+            vec2(1.0, 1.0),
+            vec2(1.0, 3.0),
+            vec2(0.0, 3.0),
+        ]
+    }
+
+    #[test]
+    fn concave_polygon_is_covered_exactly() {
+        let outer = comb();
+        let triangles = triangles(&outer, &[]);
+        assert_eq!(triangles.len(), outer.len() - 2);
+        assert_counter_clockwise(&triangles);
+        assert_eq!(total_area(&triangles), signed_area(&outer) / 2.0);
+    }
+
+    #[test]
+    fn hole_is_left_empty() {
+        let outer = vec![vec2(0.0, 0.0), vec2(4.0, 0.0), vec2(4.0, 4.0), vec2(0.0, 4.0)];
+        let hole = vec![vec2(1.0, 1.0), vec2(3.0, 1.0), vec2(3.0, 3.0), vec2(1.0, 3.0)];
+        let triangles = triangles(&outer, &[hole]);
+        assert_counter_clockwise(&triangles);
+        assert_eq!(total_area(&triangles), 16.0 - 4.0);
+        for triangle in &triangles {
+            let center = triangle.iter().sum::<Vec2>() / 3.0;
+            assert!(!(center.cmpgt(Vec2::ONE).all() && center.cmplt(Vec2::splat(3.0)).all()), "{triangle:?} is in the hole");
+        }
+    }
+
+    #[test]
+    fn winding_does_not_matter() {
+        // Each triangle starts at its lowest corner so the same triangles compare equal.
+        let canonical = |mut triangles: Vec<[Vec2; 3]>| {
+            for triangle in &mut triangles {
+                let lowest = (0..3).min_by(|&a, &b| triangle[a].to_array().partial_cmp(&triangle[b].to_array()).unwrap()).unwrap();
+                triangle.rotate_left(lowest);
+            }
+            triangles.sort_by(|a, b| a.map(|p| p.to_array()).partial_cmp(&b.map(|p| p.to_array())).unwrap());
+            triangles
+        };
+        let outer = comb();
+        let holes = [vec![vec2(0.25, 0.25), vec2(0.75, 0.25), vec2(0.5, 0.75)]];
+        let reversed = |points: &[Vec2]| points.iter().rev().copied().collect::<Vec<_>>();
+        let reversed_holes = [reversed(&holes[0])];
+        let expected = canonical(triangles(&outer, &holes));
+        assert_eq!(canonical(triangles(&reversed(&outer), &holes)), expected);
+        assert_eq!(canonical(triangles(&outer, &reversed_holes)), expected);
+        assert_eq!(canonical(triangles(&reversed(&outer), &reversed_holes)), expected);
+    }
+}
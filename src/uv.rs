@@ -0,0 +1,165 @@
+use std::{collections::HashMap, f32::consts::PI};
+use glam::{Vec2, Vec3};
+use crate::{builder::MeshBuilder, mesh_data::MeshData, model::{Mesh, ModelVertex}};
+
+/// How to flatten positions into texture coordinates.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum UvProjection {
+    /// Straight along `normal`, as seen from its tip, one UV unit per world unit.
+    Planar { normal: Vec3 },
+    /// Planar along whichever axis is closest to each face normal, so every side of a box
+    /// gets its own upright copy. Also known as triplanar mapping.
+    Box,
+    /// Once around `axis` through the center of the mesh in u, one UV unit per world unit
+    /// along it in v.
+    Cylindrical { axis: Vec3 },
+    /// Longitude around `axis` through the center of the mesh in u, latitude from the top
+    /// down in v.
+    Spherical { axis: Vec3 },
+}
+
+/// A projection with the scale and offset applied to the coordinates it gives.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UvMapping {
+    pub projection: UvProjection,
+    pub scale: Vec2,
+    pub offset: Vec2,
+}
+
+impl UvMapping {
+    pub fn new(projection: UvProjection) -> Self {
+        Self { projection, scale: Vec2::ONE, offset: Vec2::ZERO }
+    }
+
+    pub fn scale(mut self, scale: Vec2) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn offset(mut self, offset: Vec2) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+/// The directions of u and of up in the image for looking down at a plane from the tip of
+/// `normal`, keeping the image upright on walls.
+fn plane_axes(normal: Vec3) -> (Vec3, Vec3) {
+    let normal = normal.normalize_or_zero();
+    let up = if normal.y.abs() > 0.999 { Vec3::NEG_Z * normal.y.signum() } else { Vec3::Y };
+    let right = up.cross(normal).normalize();
+    (right, normal.cross(right))
+}
+
+fn planar(position: Vec3, normal: Vec3) -> Vec2 {
+    let (right, up) = plane_axes(normal);
+    // Texture v runs down the image.
+    Vec2::new(position.dot(right), -position.dot(up))
+}
+
+/// The axis along `axis` and two across it, so positions can be turned into angles.
+fn around(axis: Vec3) -> (Vec3, Vec3, Vec3) {
+    let axis = axis.normalize_or_zero();
+    let (right, up) = plane_axes(axis);
+    (axis, right, up)
+}
+
+/// Shifts the u of corners that wrapped past the seam, so the triangle does not stretch
+/// back across the whole texture. Corners on the axis have no angle and take the u of the
+/// others.
+fn unwrap_seam(uvs: &mut [Vec2; 3], on_axis: [bool; 3]) {
+    let angled = (0..3).filter(|&i| !on_axis[i]).collect::<Vec<_>>();
+    if let Some(&first) = angled.first() {
+        for &i in &angled {
+            if uvs[i].x - uvs[first].x > 0.5 {
+                uvs[i].x -= 1.0;
+            } else if uvs[first].x - uvs[i].x > 0.5 {
+                uvs[i].x += 1.0;
+            }
+        }
+        let u = angled.iter().map(|&i| uvs[i].x).sum::<f32>() / angled.len() as f32;
+        for i in (0..3).filter(|&i| on_axis[i]) {
+            uvs[i].x = u;
+        }
+    }
+}
+
+impl UvMapping {
+    fn triangle(&self, corners: [Vec3; 3], center: Vec3) -> [Vec2; 3] {
+        let face_normal = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+        let mut uvs = match self.projection {
+            UvProjection::Planar { normal } => corners.map(|p| planar(p, normal)),
+            UvProjection::Box => {
+                let size = face_normal.abs();
+                let normal = if size.x >= size.y && size.x >= size.z {
+                    Vec3::X * face_normal.x.signum()
+                } else if size.y >= size.z {
+                    Vec3::Y * face_normal.y.signum()
+                } else {
+                    Vec3::Z * face_normal.z.signum()
+                };
+                corners.map(|p| planar(p, normal))
+            }
+            UvProjection::Cylindrical { axis } | UvProjection::Spherical { axis } => {
+                let (axis, right, up) = around(axis);
+                let spherical = matches!(self.projection, UvProjection::Spherical { .. });
+                let local = corners.map(|p| p - center);
+                let on_axis = local.map(|p| p.reject_from(axis).length_squared() <= 1e-12 * p.length_squared().max(1e-12));
+                let mut uvs = local.map(|p| {
+                    // Counter-clockwise seen from the tip of the axis, so the image is not
+                    // mirrored seen from outside.
+                    let u = 0.5 + p.dot(up).atan2(p.dot(right)) / (2.0 * PI);
+                    let v = if spherical { (p.normalize_or_zero().dot(axis).clamp(-1.0, 1.0)).acos() / PI } else { -p.dot(axis) };
+                    Vec2::new(u, v)
+                });
+                unwrap_seam(&mut uvs, on_axis);
+                uvs
+            }
+        };
+        for uv in &mut uvs {
+            *uv = *uv * self.scale + self.offset;
+        }
+        uvs
+    }
+}
+
+/// Replaces the texture coordinates by projecting the positions, then recomputes the
+/// tangents. Vertices are split where faces need different coordinates, on the edges of the
+/// box sides and along the seam of the curved projections.
+pub fn project_uvs(data: &mut MeshData, mapping: UvMapping) {
+    let center = data.aabb().map_or(Vec3::ZERO, |aabb| aabb.center());
+    let mut vertices = HashMap::new();
+    let mut sources = vec![];
+    let mut tex_coords = vec![];
+    let mut indices = Vec::with_capacity(data.indices.len());
+    for t in data.indices.chunks_exact(3) {
+        let corners = [t[0], t[1], t[2]];
+        let uvs = mapping.triangle(corners.map(|i| data.positions[i as usize]), center);
+        for (vertex, uv) in corners.into_iter().zip(uvs) {
+            let index = *vertices.entry((vertex, uv.to_array().map(f32::to_bits))).or_insert_with(|| {
+                sources.push(vertex);
+                tex_coords.push(uv);
+                sources.len() as u32 - 1
+            });
+            indices.push(index);
+        }
+    }
+    data.remap_vertices(&sources);
+    data.tex_coords = tex_coords;
+    data.indices = indices;
+    data.compute_tangents();
+}
+
+/// Rebuilds a mesh with projected texture coordinates, keeping its name and material.
+/// Needs the data kept with [`MeshBuilder::retain_data`], which [`crate::resources::load_model`]
+/// keeps when asked to.
+pub fn project_mesh_uvs(mesh: &Mesh, mapping: UvMapping, device: &wgpu::Device) -> Option<Mesh> {
+    let mut data = mesh.data.clone()?;
+    project_uvs(&mut data, mapping);
+    Some(
+        MeshBuilder::<ModelVertex>::from_data(&mesh.name, data)
+            .material(mesh.material)
+            .retain_data(true)
+            .build(device),
+    )
+}
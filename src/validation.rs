@@ -0,0 +1,258 @@
+use std::{collections::HashMap, fmt};
+use glam::{Vec2, Vec3};
+use crate::{mesh_data::MeshData, model::MatModel, simplify::weld_points};
+
+/// The UV square is sampled on a grid this many cells across for coverage and overlap.
+const UV_GRID_SIZE: usize = 256;
+
+/// How far a normal or tangent may be from unit length.
+const LENGTH_TOLERANCE: f32 = 1e-3;
+
+/// Problems and statistics of one mesh. Edges are counted between positions, so UV and normal
+/// seams do not show up as borders.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshReport {
+    pub num_vertices: usize,
+    pub num_triangles: usize,
+    /// Indices past the end of the vertices, or left over after the last whole triangle.
+    pub invalid_indices: usize,
+    /// Vertices with NaN or infinite positions.
+    pub invalid_positions: usize,
+    /// Triangles using the same vertex twice.
+    pub degenerate_triangles: usize,
+    /// Triangles with distinct vertices but no area.
+    pub zero_area_triangles: usize,
+    /// Edges with only one triangle.
+    pub boundary_edges: usize,
+    /// Edges shared by more than two triangles.
+    pub non_manifold_edges: usize,
+    /// Edges whose two triangles run along them the same way, so one of them is wound backwards.
+    pub inconsistent_edges: usize,
+    /// Triangles facing away from their vertex normals, which light from behind.
+    pub flipped_triangles: usize,
+    pub invalid_normals: usize,
+    pub unnormalized_normals: usize,
+    pub invalid_tangents: usize,
+    /// Tangents off unit length, not at a right angle to the normal or without a handedness of
+    /// 1 or -1.
+    pub malformed_tangents: usize,
+    /// Triangles with no area in UV space, which have no tangent frame.
+    pub degenerate_uv_triangles: usize,
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+    /// The part of the UV square covered by triangles.
+    pub uv_coverage: f32,
+    /// The part of the covered UV square covered more than once.
+    pub uv_overlap: f32,
+}
+
+impl MeshReport {
+    /// Whether anything is likely to render wrongly. Boundaries, overlapping UVs and UVs
+    /// outside the square are allowed, since plenty of meshes have them on purpose.
+    pub fn has_errors(&self) -> bool {
+        self.invalid_indices
+            + self.invalid_positions
+            + self.degenerate_triangles
+            + self.zero_area_triangles
+            + self.non_manifold_edges
+            + self.inconsistent_edges
+            + self.flipped_triangles
+            + self.invalid_normals
+            + self.unnormalized_normals
+            + self.invalid_tangents
+            + self.malformed_tangents
+            > 0
+    }
+}
+
+impl fmt::Display for MeshReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} vertices, {} triangles", self.num_vertices, self.num_triangles)?;
+        let counts = [
+            ("invalid indices", self.invalid_indices),
+            ("invalid positions", self.invalid_positions),
+            ("degenerate triangles", self.degenerate_triangles),
+            ("zero area triangles", self.zero_area_triangles),
+            ("boundary edges", self.boundary_edges),
+            ("non-manifold edges", self.non_manifold_edges),
+            ("inconsistently wound edges", self.inconsistent_edges),
+            ("flipped triangles", self.flipped_triangles),
+            ("invalid normals", self.invalid_normals),
+            ("unnormalized normals", self.unnormalized_normals),
+            ("invalid tangents", self.invalid_tangents),
+            ("malformed tangents", self.malformed_tangents),
+            ("degenerate UV triangles", self.degenerate_uv_triangles),
+        ];
+        for (name, count) in counts.into_iter().filter(|&(_, count)| count > 0) {
+            writeln!(f, "{count} {name}")?;
+        }
+        write!(
+            f,
+            "UVs from {} to {}, {:.1}% covered, {:.1}% overlapping",
+            self.uv_min,
+            self.uv_max,
+            self.uv_coverage * 100.0,
+            self.uv_overlap * 100.0,
+        )
+    }
+}
+
+fn is_finite(values: &[f32]) -> bool {
+    values.iter().all(|value| value.is_finite())
+}
+
+/// Checks a mesh for the problems that show up as black, inside-out or garbled renders.
+pub fn validate(data: &MeshData) -> MeshReport {
+    let num_vertices = data.num_vertices();
+    let mut report = MeshReport { num_vertices, num_triangles: data.num_triangles(), ..Default::default() };
+
+    report.invalid_positions = data.positions.iter().filter(|p| !p.is_finite()).count();
+    report.invalid_normals = data.normals.iter().filter(|n| !n.is_finite()).count();
+    report.unnormalized_normals = data
+        .normals
+        .iter()
+        .filter(|n| n.is_finite() && (n.length() - 1.0).abs() > LENGTH_TOLERANCE)
+        .count();
+    report.invalid_tangents = data.tangents.iter().filter(|t| !t.is_finite()).count();
+    report.malformed_tangents = data
+        .tangents
+        .iter()
+        .zip(&data.normals)
+        .filter(|(t, n)| {
+            let direction = t.truncate();
+            t.is_finite()
+                && ((direction.length() - 1.0).abs() > LENGTH_TOLERANCE
+                    || (n.is_finite() && direction.dot(**n).abs() > LENGTH_TOLERANCE)
+                    || t.w.abs() != 1.0)
+        })
+        .count();
+
+    report.invalid_indices = data.indices.iter().filter(|&&i| i as usize >= num_vertices).count() + data.indices.len() % 3;
+    let triangles = data
+        .indices
+        .chunks_exact(3)
+        .map(|t| [t[0], t[1], t[2]])
+        .filter(|t| t.iter().all(|&i| (i as usize) < num_vertices && data.positions[i as usize].is_finite()))
+        .collect::<Vec<_>>();
+
+    let scale = data.aabb().filter(|aabb| aabb.size().is_finite()).map_or(1.0, |aabb| aabb.size().max_element());
+    let (point_of_vertex, _) = weld_points(&data.positions, scale * 1e-5);
+    let mut edges: HashMap<(u32, u32), (u32, u32)> = HashMap::new();
+    for &[a, b, c] in &triangles {
+        if a == b || b == c || c == a {
+            report.degenerate_triangles += 1;
+            continue;
+        }
+        let [pa, pb, pc] = [a, b, c].map(|i| data.positions[i as usize]);
+        let cross = (pb - pa).cross(pc - pa);
+        if cross.length() <= f32::EPSILON * scale * scale {
+            report.zero_area_triangles += 1;
+            continue;
+        }
+        let corner_normals = [a, b, c].map(|i| data.normals.get(i as usize).copied().unwrap_or(Vec3::ZERO));
+        if corner_normals.iter().sum::<Vec3>().dot(cross) < 0.0 {
+            report.flipped_triangles += 1;
+        }
+        let [ta, tb, tc] = [a, b, c].map(|i| data.tex_coords.get(i as usize).copied().unwrap_or(Vec2::ZERO));
+        if (tb - ta).perp_dot(tc - ta).abs() <= f32::EPSILON {
+            report.degenerate_uv_triangles += 1;
+        }
+
+        let points = [a, b, c].map(|i| point_of_vertex[i as usize]);
+        for i in 0..3 {
+            let (from, to) = (points[i], points[(i + 1) % 3]);
+            // Counts the triangles running along the edge each way.
+            let counts = edges.entry((from.min(to), from.max(to))).or_default();
+            if from < to {
+                counts.0 += 1;
+            } else {
+                counts.1 += 1;
+            }
+        }
+    }
+    for &(forward, backward) in edges.values() {
+        match forward + backward {
+            1 => report.boundary_edges += 1,
+            2 if forward != 1 => report.inconsistent_edges += 1,
+            2 => {}
+            _ => report.non_manifold_edges += 1,
+        }
+    }
+
+    let tex_coords = data.tex_coords.iter().filter(|t| is_finite(&t.to_array()));
+    (report.uv_min, report.uv_max) = tex_coords.fold((Vec2::INFINITY, Vec2::NEG_INFINITY), |(min, max), &t| (min.min(t), max.max(t)));
+    if report.uv_min.x > report.uv_max.x {
+        (report.uv_min, report.uv_max) = (Vec2::ZERO, Vec2::ZERO);
+    }
+    (report.uv_coverage, report.uv_overlap) = uv_coverage(data, &triangles);
+    report
+}
+
+/// Rasterizes the triangles in UV space over the unit square and returns the part of it that
+/// is covered and the part of that covered more than once. Cells count by their centers, with
+/// the top left rule deciding centers right on an edge so neighbours never share a cell.
+fn uv_coverage(data: &MeshData, triangles: &[[u32; 3]]) -> (f32, f32) {
+    if data.tex_coords.len() < data.num_vertices() {
+        return (0.0, 0.0);
+    }
+    let mut cells = vec![0u8; UV_GRID_SIZE * UV_GRID_SIZE];
+    let grid = UV_GRID_SIZE as f32;
+    for triangle in triangles {
+        let mut corners = triangle.map(|i| data.tex_coords[i as usize] * grid);
+        if !corners.iter().all(|c| c.is_finite()) {
+            continue;
+        }
+        let area = (corners[1] - corners[0]).perp_dot(corners[2] - corners[0]);
+        if area == 0.0 {
+            continue;
+        }
+        // Mirrored islands are walked the other way round so every triangle is counter-clockwise.
+        if area < 0.0 {
+            corners.swap(1, 2);
+        }
+        let min = corners.iter().fold(Vec2::INFINITY, |min, &c| min.min(c)).max(Vec2::ZERO);
+        let max = corners.iter().fold(Vec2::NEG_INFINITY, |max, &c| max.max(c)).min(Vec2::splat(grid));
+        let edges = [0, 1, 2].map(|i| (corners[i], corners[(i + 1) % 3]));
+        for y in min.y.floor() as usize..(max.y.ceil() as usize).min(UV_GRID_SIZE) {
+            for x in min.x.floor() as usize..(max.x.ceil() as usize).min(UV_GRID_SIZE) {
+                let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let inside = edges.iter().all(|&(from, to)| {
+                    let side = (to - from).perp_dot(center - from);
+                    let edge = to - from;
+                    side > 0.0 || (side == 0.0 && (edge.y < 0.0 || (edge.y == 0.0 && edge.x > 0.0)))
+                });
+                if inside {
+                    let cell = &mut cells[x + y * UV_GRID_SIZE];
+                    *cell = cell.saturating_add(1);
+                }
+            }
+        }
+    }
+    let covered = cells.iter().filter(|&&count| count > 0).count();
+    let overlapping = cells.iter().filter(|&&count| count > 1).count();
+    (covered as f32 / cells.len() as f32, overlapping as f32 / covered.max(1) as f32)
+}
+
+/// The reports of every mesh in a model along with its materials.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ModelReport {
+    pub num_materials: usize,
+    /// Meshes pointing at a material the model does not have.
+    pub missing_materials: usize,
+    /// One per mesh, or `None` for meshes built without retaining their data.
+    pub meshes: Vec<Option<MeshReport>>,
+}
+
+impl ModelReport {
+    pub fn has_errors(&self) -> bool {
+        self.missing_materials > 0 || self.meshes.iter().flatten().any(MeshReport::has_errors)
+    }
+}
+
+pub fn validate_model(model: &MatModel) -> ModelReport {
+    ModelReport {
+        num_materials: model.materials.len(),
+        missing_materials: model.meshes.iter().filter(|mesh| mesh.material >= model.materials.len()).count(),
+        meshes: model.meshes.iter().map(|mesh| mesh.data.as_ref().map(validate)).collect(),
+    }
+}
@@ -0,0 +1,304 @@
+use glam::{vec4, IVec3, UVec3, Vec2, Vec3, Vec4};
+use crate::{bounds::Aabb, model::{Mesh, Vertex}};
+
+pub type BlockId = u16;
+
+/// The empty block. Every other block is solid and hides the faces next to it.
+pub const AIR: BlockId = 0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Face {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl Face {
+    pub const ALL: [Face; 6] = [Face::PosX, Face::NegX, Face::PosY, Face::NegY, Face::PosZ, Face::NegZ];
+
+    pub fn normal(self) -> IVec3 {
+        match self {
+            Face::PosX => IVec3::X,
+            Face::NegX => IVec3::NEG_X,
+            Face::PosY => IVec3::Y,
+            Face::NegY => IVec3::NEG_Y,
+            Face::PosZ => IVec3::Z,
+            Face::NegZ => IVec3::NEG_Z,
+        }
+    }
+
+    fn axis(self) -> usize {
+        match self {
+            Face::PosX | Face::NegX => 0,
+            Face::PosY | Face::NegY => 1,
+            Face::PosZ | Face::NegZ => 2,
+        }
+    }
+
+    fn is_positive(self) -> bool {
+        matches!(self, Face::PosX | Face::PosY | Face::PosZ)
+    }
+
+    /// The directions of the right and the top of the texture seen from outside, with the
+    /// sides kept upright.
+    fn texture_axes(self) -> (Vec3, Vec3) {
+        match self {
+            Face::PosX => (Vec3::NEG_Z, Vec3::Y),
+            Face::NegX => (Vec3::Z, Vec3::Y),
+            Face::PosY => (Vec3::X, Vec3::NEG_Z),
+            Face::NegY => (Vec3::X, Vec3::Z),
+            Face::PosZ => (Vec3::X, Vec3::Y),
+            Face::NegZ => (Vec3::NEG_X, Vec3::Y),
+        }
+    }
+}
+
+/// A texture split into a grid of equally sized tiles, counted row by row from the top left.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TextureAtlas {
+    pub columns: u32,
+    pub rows: u32,
+}
+
+impl TextureAtlas {
+    pub fn new(columns: u32, rows: u32) -> Self {
+        Self { columns: columns.max(1), rows: rows.max(1) }
+    }
+
+    /// The texture coordinates of the top left corner of a tile and its size.
+    pub fn tile(&self, index: u32) -> Vec4 {
+        let (column, row) = (index % self.columns, (index / self.columns).min(self.rows - 1));
+        let size = Vec2::ONE / Vec2::new(self.columns as f32, self.rows as f32);
+        vec4(column as f32 * size.x, row as f32 * size.y, size.x, size.y)
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VoxelVertex {
+    pub position: Vec3,
+    /// Counted in blocks across the face, so merged faces repeat their tile once per block.
+    pub tex_coords: Vec2,
+    pub normal: Vec3,
+    pub tangent: Vec4,
+    /// The atlas tile as the corner and size from [`TextureAtlas::tile`].
+    pub tile: Vec4,
+    /// From 0 for a fully occluded corner to 1 for an open one.
+    pub ambient_occlusion: f32,
+    _padding: [u32; 3],
+}
+
+impl Vertex for VoxelVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<VoxelVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                // Locations 5 to 11 are taken by the instance.
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32,
+                },
+            ],
+        }
+    }
+}
+
+/// A box of blocks with one corner on the origin, one unit per block.
+#[derive(Clone, Debug)]
+pub struct VoxelChunk {
+    size: UVec3,
+    blocks: Vec<BlockId>,
+}
+
+impl VoxelChunk {
+    pub fn new(size: UVec3) -> Self {
+        Self { size, blocks: vec![AIR; (size.x * size.y * size.z) as usize] }
+    }
+
+    pub fn from_fn(size: UVec3, mut block: impl FnMut(UVec3) -> BlockId) -> Self {
+        let mut chunk = Self::new(size);
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let position = UVec3::new(x, y, z);
+                    chunk.set_block(position, block(position));
+                }
+            }
+        }
+        chunk
+    }
+
+    pub fn size(&self) -> UVec3 {
+        self.size
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        Aabb::new(Vec3::ZERO, self.size.as_vec3())
+    }
+
+    fn index(&self, position: IVec3) -> Option<usize> {
+        let size = self.size.as_ivec3();
+        (position.cmpge(IVec3::ZERO).all() && position.cmplt(size).all())
+            .then(|| (position.x + size.x * (position.y + size.y * position.z)) as usize)
+    }
+
+    /// Blocks outside the chunk are air.
+    pub fn block(&self, position: IVec3) -> BlockId {
+        self.index(position).map_or(AIR, |index| self.blocks[index])
+    }
+
+    /// Positions outside the chunk are ignored.
+    pub fn set_block(&mut self, position: UVec3, block: BlockId) {
+        if let Some(index) = self.index(position.as_ivec3()) {
+            self.blocks[index] = block;
+        }
+    }
+
+    pub fn num_solid_blocks(&self) -> usize {
+        self.blocks.iter().filter(|&&block| block != AIR).count()
+    }
+
+    /// How open each corner of a block face is, from 0 to 3, counter-clockwise from the lowest
+    /// corner along the two axes of the face. A corner with both neighbouring blocks in front
+    /// of the face filled is closed whatever the diagonal block is.
+    fn face_occlusion(&self, position: IVec3, face: Face, u_axis: IVec3, v_axis: IVec3) -> [u8; 4] {
+        let front = position + face.normal();
+        let solid = |offset: IVec3| (self.block(front + offset) != AIR) as u8;
+        [(-1, -1), (1, -1), (1, 1), (-1, 1)].map(|(u, v)| {
+            let (side_u, side_v) = (solid(u_axis * u), solid(v_axis * v));
+            if side_u + side_v == 2 {
+                0
+            } else {
+                3 - side_u - side_v - solid(u_axis * u + v_axis * v)
+            }
+        })
+    }
+
+    /// Builds the visible faces of the chunk. Faces between two solid blocks are culled and the
+    /// rest are merged greedily into rectangles of the same block. Faces with uneven ambient
+    /// occlusion are kept apart, so the merging never changes the shading. `tile` picks the
+    /// atlas tile for each side of a block.
+    pub fn vertices(&self, atlas: TextureAtlas, tile: impl Fn(BlockId, Face) -> u32) -> (Vec<VoxelVertex>, Vec<u32>) {
+        let size = self.size.as_ivec3();
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        for face in Face::ALL {
+            let axis = face.axis();
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            let (u_axis, v_axis) = (IVec3::AXES[u], IVec3::AXES[v]);
+            let (width, height) = (size[u], size[v]);
+            let (right, up) = face.texture_axes();
+            let normal = face.normal().as_vec3();
+
+            for layer in 0..size[axis] {
+                let mut mask = Vec::with_capacity((width * height) as usize);
+                for row in 0..height {
+                    for column in 0..width {
+                        let position = IVec3::AXES[axis] * layer + u_axis * column + v_axis * row;
+                        let block = self.block(position);
+                        let visible = block != AIR && self.block(position + face.normal()) == AIR;
+                        mask.push(visible.then(|| (block, self.face_occlusion(position, face, u_axis, v_axis))));
+                    }
+                }
+
+                for row in 0..height {
+                    for column in 0..width {
+                        let Some(key) = mask[(column + row * width) as usize] else {
+                            continue;
+                        };
+                        let (block, occlusion) = key;
+                        let mergeable = occlusion.iter().all(|&corner| corner == occlusion[0]);
+                        let matches = |column: i32, row: i32| mask[(column + row * width) as usize] == Some(key);
+                        let mut quad_width = 1;
+                        while mergeable && column + quad_width < width && matches(column + quad_width, row) {
+                            quad_width += 1;
+                        }
+                        let mut quad_height = 1;
+                        while mergeable
+                            && row + quad_height < height
+                            && (column..column + quad_width).all(|column| matches(column, row + quad_height))
+                        {
+                            quad_height += 1;
+                        }
+                        for row in row..row + quad_height {
+                            for column in column..column + quad_width {
+                                mask[(column + row * width) as usize] = None;
+                            }
+                        }
+
+                        let plane = layer + face.is_positive() as i32;
+                        let origin = (IVec3::AXES[axis] * plane + u_axis * column + v_axis * row).as_vec3();
+                        let tile = atlas.tile(tile(block, face));
+                        let first = vertices.len() as u32;
+                        for (corner, (along_u, along_v)) in [(0, 0), (1, 0), (1, 1), (0, 1)].into_iter().enumerate() {
+                            let offset = (u_axis * along_u * quad_width + v_axis * along_v * quad_height).as_vec3();
+                            vertices.push(VoxelVertex {
+                                position: origin + offset,
+                                tex_coords: Vec2::new(offset.dot(right), -offset.dot(up)),
+                                normal,
+                                tangent: right.extend(1.0),
+                                tile,
+                                ambient_occlusion: occlusion[corner] as f32 / 3.0,
+                                _padding: [0; 3],
+                            });
+                        }
+                        // Splitting along the brighter diagonal keeps the occlusion from
+                        // bleeding across the whole face.
+                        let corners = if occlusion[0] + occlusion[2] >= occlusion[1] + occlusion[3] {
+                            [0, 1, 2, 0, 2, 3]
+                        } else {
+                            [0, 1, 3, 1, 2, 3]
+                        };
+                        // The corners go counter-clockwise around the positive axis.
+                        if face.is_positive() {
+                            indices.extend(corners.map(|corner| first + corner));
+                        } else {
+                            indices.extend(corners.iter().rev().map(|corner| first + corner));
+                        }
+                    }
+                }
+            }
+        }
+        (vertices, indices)
+    }
+
+    pub fn mesh(&self, atlas: TextureAtlas, tile: impl Fn(BlockId, Face) -> u32, material: usize, device: &wgpu::Device) -> Mesh {
+        let (vertices, indices) = self.vertices(atlas, tile);
+        Mesh {
+            bounds: Some(self.bounds()),
+            ..Mesh::from_vertices("Voxel Chunk", &vertices, &indices, material, device)
+        }
+    }
+}
+
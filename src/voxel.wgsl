@@ -0,0 +1,85 @@
+// Voxel chunks with their tiles from the texture atlas and ambient occlusion, completed by
+// lit_common.wgsl.
+
+// Vertex shader
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) texture_coordinates: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec4<f32>,
+    @location(4) tile: vec4<f32>,
+    @location(12) ambient_occlusion: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) texture_coordinates: vec2<f32>,
+    @location(1) tangent_position: vec3<f32>,
+    @location(2) tangent_light_position: vec3<f32>,
+    @location(3) tangent_view_position: vec3<f32>,
+    @location(4) tile: vec4<f32>,
+    @location(5) ambient_occlusion: f32,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = instance_model_matrix(instance);
+    let normal_matrix = instance_normal_matrix(instance);
+
+    let world_normal = normalize(normal_matrix * model.normal);
+    let world_tangent = normalize(normal_matrix * model.tangent.xyz);
+    let world_bitangent = cross(world_normal, world_tangent) * model.tangent.w;
+    let tangent_matrix = transpose(mat3x3<f32>(
+        world_tangent,
+        world_bitangent,
+        world_normal,
+    ));
+
+    var world_position: vec4<f32> = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.texture_coordinates = model.texture_coordinates;
+    out.clip_position = camera.view_proj * world_position;
+    out.tangent_position = tangent_matrix * world_position.xyz;
+    out.tangent_view_position = tangent_matrix * camera.view_pos.xyz;
+    out.tangent_light_position = tangent_matrix * light.position;
+    out.tile = model.tile;
+    out.ambient_occlusion = model.ambient_occlusion;
+
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Merged faces repeat their atlas tile once per block. The gradients are taken before
+    // wrapping so the mipmap does not jump at the tile edges.
+    let tile_coordinates = in.tile.xy + fract(in.texture_coordinates) * in.tile.zw;
+    let ddx = dpdx(in.texture_coordinates) * in.tile.zw;
+    let ddy = dpdy(in.texture_coordinates) * in.tile.zw;
+    let texture_color = textureSampleGrad(t_diffuse, s_diffuse, tile_coordinates, ddx, ddy);
+    let normal: vec4<f32> = textureSampleGrad(t_normal, s_normal, tile_coordinates, ddx, ddy);
+
+    let occlusion = mix(0.35, 1.0, in.ambient_occlusion);
+    let ambient_color = light.ambient;
+
+    let tangent_normal = normal.xyz * 2.0 - 1.0;
+    let light_dir = normalize(in.tangent_light_position - in.tangent_position);
+    let view_dir = normalize(in.tangent_view_position - in.tangent_position);
+    let half_dir = normalize(view_dir + light_dir);
+
+    let diffuse_strength = max(dot(tangent_normal, light_dir), 0.0);
+    let diffuse_color = light.color * diffuse_strength;
+
+    let specular_strength = pow(max(dot(tangent_normal, half_dir), 0.0), 32.0);
+    let specular_color = specular_strength * light.color;
+
+    let result = ((ambient_color + diffuse_color) * occlusion + specular_color) * texture_color.xyz;
+
+    return vec4<f32>(result, texture_color.a);
+}
\ No newline at end of file
@@ -18,7 +18,7 @@ pub async fn run(app: Box<dyn App>) -> Result<(), ()> {
             Event::WindowEvent {
                 window_id,
                 event
-            } if window_id == state.window().id() => if !state.window_event(&event) {
+            } if window_id == state.window().id() && !state.window_event(&event) => {
                 match event {
                     WindowEvent::CloseRequested => {
                         println!("The close button was pressed; stopping");
@@ -108,8 +108,7 @@ impl State<'_> {
         let surface_caps = surface.get_capabilities(&adapter);
         let surface_format = surface_caps.formats.iter()
             .copied()
-            .filter(|f| f.is_srgb())
-            .next()
+            .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -217,6 +216,30 @@ pub fn create_render_pipeline(
     depth_format: Option<wgpu::TextureFormat>,
     vertex_layouts: &[wgpu::VertexBufferLayout],
     shader: wgpu::ShaderModuleDescriptor,
+) -> wgpu::RenderPipeline {
+    create_pipeline(device, layout, color_format, depth_format, vertex_layouts, shader, wgpu::PrimitiveTopology::TriangleList)
+}
+
+/// Like [`create_render_pipeline`] but drawing a line list, for debug lines with `lines.wgsl`.
+pub fn create_line_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    depth_format: Option<wgpu::TextureFormat>,
+    vertex_layouts: &[wgpu::VertexBufferLayout],
+    shader: wgpu::ShaderModuleDescriptor,
+) -> wgpu::RenderPipeline {
+    create_pipeline(device, layout, color_format, depth_format, vertex_layouts, shader, wgpu::PrimitiveTopology::LineList)
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    depth_format: Option<wgpu::TextureFormat>,
+    vertex_layouts: &[wgpu::VertexBufferLayout],
+    shader: wgpu::ShaderModuleDescriptor,
+    topology: wgpu::PrimitiveTopology,
 ) -> wgpu::RenderPipeline {
     let shader = device.create_shader_module(shader);
 
@@ -241,7 +264,7 @@ pub fn create_render_pipeline(
             })],
         }),
         primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
+            topology,
             strip_index_format: None,
             front_face: wgpu::FrontFace::Ccw,
             cull_mode: Some(wgpu::Face::Back),